@@ -1,13 +1,22 @@
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(rename_all = "lowercase")]
+use crate::exec;
+use crate::sandbox::SandboxPermissions;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
 pub enum AppSource {
+    #[default]
     Desktop,
     Flatpak,
     Snap,
+    Pipx,
+    Uv,
     Standalone,
     Cargo,
     Npm,
@@ -15,32 +24,107 @@ pub enum AppSource {
     Rpm,
     Pacman,
     Brew,
+    Nix,
+    /// Source name declared by a plugin provider.
+    Custom(String),
 }
 
 impl AppSource {
+    /// Every source, in default priority order.
+    pub const ALL: &'static [AppSource] = &[
+        AppSource::Desktop,
+        AppSource::Flatpak,
+        AppSource::Snap,
+        AppSource::Pipx,
+        AppSource::Uv,
+        AppSource::Standalone,
+        AppSource::Cargo,
+        AppSource::Npm,
+        AppSource::Dpkg,
+        AppSource::Rpm,
+        AppSource::Pacman,
+        AppSource::Brew,
+        AppSource::Nix,
+    ];
+
     /// Priority for deduplication: lower number = higher priority.
     pub fn priority(&self) -> u8 {
         match self {
             AppSource::Desktop => 0,
             AppSource::Flatpak => 1,
             AppSource::Snap => 2,
-            AppSource::Standalone => 3,
-            AppSource::Cargo => 4,
-            AppSource::Npm => 5,
-            AppSource::Dpkg => 6,
-            AppSource::Rpm => 7,
-            AppSource::Pacman => 8,
-            AppSource::Brew => 9,
+            AppSource::Pipx => 3,
+            AppSource::Uv => 4,
+            AppSource::Standalone => 5,
+            AppSource::Cargo => 6,
+            AppSource::Npm => 7,
+            AppSource::Dpkg => 8,
+            AppSource::Rpm => 9,
+            AppSource::Pacman => 10,
+            AppSource::Brew => 11,
+            AppSource::Nix => 12,
+            AppSource::Custom(_) => u8::MAX,
         }
     }
 }
 
+impl FromStr for AppSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "desktop" => Ok(AppSource::Desktop),
+            "flatpak" => Ok(AppSource::Flatpak),
+            "snap" => Ok(AppSource::Snap),
+            "pipx" => Ok(AppSource::Pipx),
+            "uv" => Ok(AppSource::Uv),
+            "standalone" => Ok(AppSource::Standalone),
+            "cargo" => Ok(AppSource::Cargo),
+            "npm" => Ok(AppSource::Npm),
+            "dpkg" => Ok(AppSource::Dpkg),
+            "rpm" => Ok(AppSource::Rpm),
+            "pacman" => Ok(AppSource::Pacman),
+            "brew" => Ok(AppSource::Brew),
+            "nix" => Ok(AppSource::Nix),
+            name if is_valid_custom_name(name) => Ok(AppSource::Custom(name.to_string())),
+            _ => Err(format!(
+                "invalid source '{}': expected desktop, flatpak, snap, pipx, uv, standalone, cargo, npm, dpkg, rpm, pacman, brew, nix, or a plugin source name",
+                s
+            )),
+        }
+    }
+}
+
+/// Plugin source names are lowercase words of letters, digits, `-` and `_`.
+fn is_valid_custom_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl TryFrom<String> for AppSource {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<AppSource> for String {
+    fn from(source: AppSource) -> Self {
+        source.to_string()
+    }
+}
+
 impl fmt::Display for AppSource {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AppSource::Desktop => write!(f, "desktop"),
             AppSource::Flatpak => write!(f, "flatpak"),
             AppSource::Snap => write!(f, "snap"),
+            AppSource::Pipx => write!(f, "pipx"),
+            AppSource::Uv => write!(f, "uv"),
             AppSource::Standalone => write!(f, "standalone"),
             AppSource::Cargo => write!(f, "cargo"),
             AppSource::Npm => write!(f, "npm"),
@@ -48,22 +132,106 @@ impl fmt::Display for AppSource {
             AppSource::Rpm => write!(f, "rpm"),
             AppSource::Pacman => write!(f, "pacman"),
             AppSource::Brew => write!(f, "brew"),
+            AppSource::Nix => write!(f, "nix"),
+            AppSource::Custom(name) => write!(f, "{}", name),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// An installed application. New fields may be added in minor releases; build values
+/// with [`Application::new`] (or `..Default::default()` inside this crate) and set the
+/// fields you need.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct Application {
     pub name: String,
     pub exec_command: String,
     pub source: AppSource,
+    #[serde(default)]
     pub location: String,
     pub icon: Option<String>,
+    #[serde(default)]
     pub categories: Vec<String>,
     pub description: Option<String>,
+    /// Source-specific details such as package name or version.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Command line split into arguments, with desktop entry field codes left unexpanded.
+    /// Empty when the provider only knows `exec_command`.
+    #[serde(default)]
+    pub exec_args: Vec<String>,
+    /// Runs in a terminal (`Terminal=true` in its desktop entry).
+    #[serde(default)]
+    pub terminal: bool,
+    /// Secondary actions from `[Desktop Action <id>]` groups, in `Actions=` order.
+    #[serde(default)]
+    pub actions: Vec<DesktopAction>,
+    /// Why the entry is normally left out, e.g. `NoDisplay=true`. Only set for
+    /// applications listed with `--show-hidden`.
+    #[serde(default)]
+    pub hidden_reason: Option<String>,
+    /// Generic description such as "Web Browser" (`GenericName`), localized like `name`.
+    #[serde(default)]
+    pub generic_name: Option<String>,
+    /// `name` before localization, when a translation replaced it.
+    #[serde(default)]
+    pub untranslated_name: Option<String>,
+    /// Extra search terms (`Keywords`), localized like `name`.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Sandbox permissions, for flatpak applications.
+    #[serde(default)]
+    pub permissions: Option<SandboxPermissions>,
+}
+
+/// A desktop entry action such as "New Private Window".
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DesktopAction {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    /// Command line split into arguments, with field codes left unexpanded.
+    pub exec_args: Vec<String>,
 }
 
 impl Application {
+    /// An application with only its name, command and source set.
+    pub fn new(name: impl Into<String>, exec_command: impl Into<String>, source: AppSource) -> Self {
+        Self {
+            name: name.into(),
+            exec_command: exec_command.into(),
+            source,
+            ..Default::default()
+        }
+    }
+
+    /// Arguments to launch this application with, field codes unexpanded.
+    pub fn argv(&self) -> Vec<String> {
+        if !self.exec_args.is_empty() {
+            return self.exec_args.clone();
+        }
+        // A bare executable path may contain spaces
+        if Path::new(&self.exec_command).is_file() {
+            return vec![self.exec_command.clone()];
+        }
+        exec::split(&self.exec_command).unwrap_or_else(|_| {
+            self.exec_command
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    /// The names this application can be found by: `name`, then `untranslated_name`.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.untranslated_name.as_deref())
+    }
+
+    /// The action with identifier `id`.
+    pub fn action(&self, id: &str) -> Option<&DesktopAction> {
+        self.actions.iter().find(|a| a.id == id)
+    }
+
     /// Count how many optional metadata fields are populated.
     pub fn metadata_richness(&self) -> usize {
         let mut count = 0;
@@ -76,6 +244,9 @@ impl Application {
         if self.description.is_some() {
             count += 1;
         }
+        if !self.metadata.is_empty() {
+            count += 1;
+        }
         count
     }
 }
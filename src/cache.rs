@@ -0,0 +1,351 @@
+//! Persistent per-provider discovery cache.
+//!
+//! Each provider's results are stored in `<cache dir>/<cache key>.json` together with
+//! the modification times of the paths the provider declares in `watch_paths`. An entry
+//! is reused only while every stamp still matches, so installing or removing software
+//! invalidates it without any explicit expiry.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::Application;
+use crate::provider::{AppProvider, ProviderError};
+
+/// Bumped whenever the on-disk layout or the shape of `Application` changes.
+const CACHE_FORMAT: u32 = 9;
+
+/// Modification time of a watched path when the entry was written.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PathStamp {
+    path: PathBuf,
+    /// Symlink target, since profile switches (Nix) change the link and not any mtime.
+    resolved: Option<PathBuf>,
+    /// `None` when the path did not exist.
+    mtime_nanos: Option<u128>,
+}
+
+impl PathStamp {
+    fn of(path: &Path) -> Self {
+        let mtime_nanos = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos());
+        let resolved = if path.is_symlink() {
+            path.canonicalize().ok()
+        } else {
+            None
+        };
+        Self {
+            path: path.to_path_buf(),
+            resolved,
+            mtime_nanos,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    format: u32,
+    appgrep_version: String,
+    created_secs: u64,
+    stamps: Vec<PathStamp>,
+    apps: Vec<Application>,
+}
+
+impl CacheEntry {
+    fn is_valid(&self, stamps: &[PathStamp]) -> bool {
+        self.format == CACHE_FORMAT
+            && self.appgrep_version == env!("CARGO_PKG_VERSION")
+            && self.stamps == stamps
+    }
+}
+
+/// State of a provider's cache entry, as reported by `appgrep doctor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The entry matches the watched paths and would be used.
+    Fresh { entries: usize, age: Duration },
+    /// An entry exists but something it depends on has changed.
+    Stale,
+    /// Nothing has been cached yet.
+    Missing,
+    /// The provider declares nothing to watch and is always queried live.
+    Uncacheable,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryCache {
+    dir: PathBuf,
+    refresh: bool,
+}
+
+impl DiscoveryCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            refresh: false,
+        }
+    }
+
+    /// Ignore existing entries and rewrite them from fresh results.
+    pub fn refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// `$XDG_CACHE_HOME/appgrep` (default `~/.cache/appgrep`).
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|d| d.join("appgrep"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, provider: &dyn AppProvider) -> PathBuf {
+        self.dir.join(format!("{}.json", provider.cache_key()))
+    }
+
+    /// Current stamps of a provider's watched paths, or `None` if it cannot be cached.
+    fn stamps(provider: &dyn AppProvider) -> Option<Vec<PathStamp>> {
+        let paths = provider.watch_paths();
+        // With nothing on disk to watch, a cached result could never be invalidated
+        if !paths.iter().any(|p| p.exists()) {
+            return None;
+        }
+        Some(paths.iter().map(|p| PathStamp::of(p)).collect())
+    }
+
+    fn read_entry(&self, provider: &dyn AppProvider) -> Option<CacheEntry> {
+        let content = fs::read_to_string(self.entry_path(provider)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn write_entry(&self, provider: &dyn AppProvider, entry: &CacheEntry) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(provider);
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        let json = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+        fs::write(&tmp, json)?;
+        // Rename so concurrent readers never see a partially written entry
+        fs::rename(&tmp, &path)
+    }
+
+    /// Return the provider's cached applications if still valid, otherwise discover and store.
+    pub fn get_or_discover(
+        &self,
+        provider: &dyn AppProvider,
+    ) -> Result<Vec<Application>, ProviderError> {
+        let stamps = match Self::stamps(provider) {
+            Some(s) => s,
+            None => return provider.discover(),
+        };
+
+        if !self.refresh {
+            if let Some(entry) = self.read_entry(provider) {
+                if entry.is_valid(&stamps) {
+                    return Ok(entry.apps);
+                }
+            }
+        }
+
+        let apps = provider.discover()?;
+        let entry = CacheEntry {
+            format: CACHE_FORMAT,
+            appgrep_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_secs: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            stamps,
+            apps,
+        };
+        if let Err(e) = self.write_entry(provider, &entry) {
+            eprintln!(
+                "appgrep: warning: could not write cache for '{}': {}",
+                provider.name(),
+                e
+            );
+        }
+
+        Ok(entry.apps)
+    }
+
+    pub fn status(&self, provider: &dyn AppProvider) -> CacheStatus {
+        let stamps = match Self::stamps(provider) {
+            Some(s) => s,
+            None => return CacheStatus::Uncacheable,
+        };
+
+        match self.read_entry(provider) {
+            Some(entry) if entry.is_valid(&stamps) => {
+                let created = UNIX_EPOCH + Duration::from_secs(entry.created_secs);
+                CacheStatus::Fresh {
+                    entries: entry.apps.len(),
+                    age: SystemTime::now().duration_since(created).unwrap_or_default(),
+                }
+            }
+            Some(_) => CacheStatus::Stale,
+            None => CacheStatus::Missing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppSource;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    struct FakeProvider {
+        watched: Vec<PathBuf>,
+        calls: AtomicUsize,
+    }
+
+    impl FakeProvider {
+        fn new(watched: Vec<PathBuf>) -> Self {
+            Self {
+                watched,
+                calls: AtomicUsize::new(0),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl AppProvider for FakeProvider {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![Application {
+                name: "tool".to_string(),
+                exec_command: "/usr/bin/tool".to_string(),
+                source: AppSource::Standalone,
+                location: "/usr/bin/tool".to_string(),
+                ..Default::default()
+            }])
+        }
+
+        fn watch_paths(&self) -> Vec<PathBuf> {
+            self.watched.clone()
+        }
+    }
+
+    #[test]
+    fn test_reuses_fresh_entry() {
+        let tmp = TempDir::new().unwrap();
+        let watched = tmp.path().join("db");
+        fs::write(&watched, "v1").unwrap();
+        let cache = DiscoveryCache::new(tmp.path().join("cache"));
+        let provider = FakeProvider::new(vec![watched]);
+
+        assert_eq!(cache.status(&provider), CacheStatus::Missing);
+        let first = cache.get_or_discover(&provider).unwrap();
+        let second = cache.get_or_discover(&provider).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(provider.calls(), 1);
+        assert!(tmp.path().join("cache/fake.json").is_file());
+        assert!(matches!(
+            cache.status(&provider),
+            CacheStatus::Fresh { entries: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalidated_by_mtime_change() {
+        let tmp = TempDir::new().unwrap();
+        let watched = tmp.path().join("db");
+        fs::write(&watched, "v1").unwrap();
+        let cache = DiscoveryCache::new(tmp.path().join("cache"));
+        let provider = FakeProvider::new(vec![watched.clone()]);
+
+        cache.get_or_discover(&provider).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&watched)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(cache.status(&provider), CacheStatus::Stale);
+        cache.get_or_discover(&provider).unwrap();
+        assert_eq!(provider.calls(), 2);
+    }
+
+    #[test]
+    fn test_invalidated_when_watched_path_appears() {
+        let tmp = TempDir::new().unwrap();
+        let existing = tmp.path().join("db");
+        let missing = tmp.path().join("later");
+        fs::write(&existing, "v1").unwrap();
+        let cache = DiscoveryCache::new(tmp.path().join("cache"));
+        let provider = FakeProvider::new(vec![existing, missing.clone()]);
+
+        cache.get_or_discover(&provider).unwrap();
+        fs::create_dir(&missing).unwrap();
+        cache.get_or_discover(&provider).unwrap();
+        assert_eq!(provider.calls(), 2);
+    }
+
+    #[test]
+    fn test_refresh_ignores_entry() {
+        let tmp = TempDir::new().unwrap();
+        let watched = tmp.path().join("db");
+        fs::write(&watched, "v1").unwrap();
+        let provider = FakeProvider::new(vec![watched]);
+
+        DiscoveryCache::new(tmp.path().join("cache"))
+            .get_or_discover(&provider)
+            .unwrap();
+        DiscoveryCache::new(tmp.path().join("cache"))
+            .refresh(true)
+            .get_or_discover(&provider)
+            .unwrap();
+        assert_eq!(provider.calls(), 2);
+    }
+
+    #[test]
+    fn test_uncacheable_without_watched_paths() {
+        let tmp = TempDir::new().unwrap();
+        let cache = DiscoveryCache::new(tmp.path().join("cache"));
+        let provider = FakeProvider::new(vec![tmp.path().join("nope")]);
+
+        cache.get_or_discover(&provider).unwrap();
+        cache.get_or_discover(&provider).unwrap();
+        assert_eq!(provider.calls(), 2);
+        assert_eq!(cache.status(&provider), CacheStatus::Uncacheable);
+        assert!(!tmp.path().join("cache").exists());
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_rewritten() {
+        let tmp = TempDir::new().unwrap();
+        let watched = tmp.path().join("db");
+        fs::write(&watched, "v1").unwrap();
+        let cache_dir = tmp.path().join("cache");
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join("fake.json"), "{ not json").unwrap();
+
+        let cache = DiscoveryCache::new(cache_dir);
+        let provider = FakeProvider::new(vec![watched]);
+        assert_eq!(cache.status(&provider), CacheStatus::Missing);
+        assert_eq!(cache.get_or_discover(&provider).unwrap().len(), 1);
+        cache.get_or_discover(&provider).unwrap();
+        assert_eq!(provider.calls(), 1);
+    }
+}
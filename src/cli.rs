@@ -1,25 +1,24 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use std::time::Duration;
 
-use crate::app::AppSource;
-use crate::output::OutputFormat;
+use clap::{Parser, Subcommand};
+
+use appgrep::{AppSource, OutputFormat, PermissionFilter};
 
 /// Parse a source string into an AppSource.
 fn parse_source(s: &str) -> Result<AppSource, String> {
-    match s.to_lowercase().as_str() {
-        "desktop" => Ok(AppSource::Desktop),
-        "flatpak" => Ok(AppSource::Flatpak),
-        "snap" => Ok(AppSource::Snap),
-        "standalone" => Ok(AppSource::Standalone),
-        "cargo" => Ok(AppSource::Cargo),
-        "npm" => Ok(AppSource::Npm),
-        "dpkg" => Ok(AppSource::Dpkg),
-        "rpm" => Ok(AppSource::Rpm),
-        "pacman" => Ok(AppSource::Pacman),
-        "brew" => Ok(AppSource::Brew),
-        _ => Err(format!(
-            "invalid source '{}': expected desktop, flatpak, snap, standalone, cargo, npm, dpkg, rpm, pacman, or brew",
-            s
-        )),
+    s.parse()
+}
+
+/// Parse a sandbox permission filter such as `filesystem=home`.
+fn parse_filter(s: &str) -> Result<PermissionFilter, String> {
+    s.parse()
+}
+
+/// Parse a timeout given in seconds.
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs > 0.0 => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("invalid timeout '{}': expected a positive number of seconds", s)),
     }
 }
 
@@ -38,6 +37,10 @@ pub struct Cli {
     #[arg(short, long, value_parser = parse_source)]
     pub source: Vec<AppSource>,
 
+    /// Only list flatpak apps granted a sandbox permission, e.g. filesystem=home (repeatable)
+    #[arg(long, value_name = "KIND=VALUE", value_parser = parse_filter)]
+    pub filter: Vec<PermissionFilter>,
+
     /// Disable colored output
     #[arg(long)]
     pub no_color: bool,
@@ -46,6 +49,34 @@ pub struct Cli {
     #[arg(long)]
     pub stats: bool,
 
+    /// Report every executable shipped by dpkg/rpm/pacman packages (always on for has/path)
+    #[arg(long)]
+    pub all_binaries: bool,
+
+    /// Also list desktop entries that are normally hidden, with the reason
+    #[arg(long, visible_alias = "all")]
+    pub show_hidden: bool,
+
+    /// Locale for translated names, e.g. de_DE (default: from $LC_ALL/$LC_MESSAGES/$LANG)
+    #[arg(long, value_name = "LOCALE")]
+    pub locale: Option<String>,
+
+    /// Ignore cached results and rebuild the cache
+    #[arg(long, conflicts_with = "no_cache")]
+    pub refresh: bool,
+
+    /// Neither read nor write the discovery cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Neither rank by nor record launch history, for reproducible results
+    #[arg(long)]
+    pub no_history: bool,
+
+    /// Seconds each provider may take before its results are dropped (overrides the config)
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    pub timeout: Option<Duration>,
+
     #[command(subcommand)]
     pub command: Command,
 }
@@ -77,6 +108,22 @@ pub enum Command {
     Run {
         /// Application name
         name: String,
+
+        /// Start terminal applications without a terminal, like graphical ones
+        #[arg(long)]
+        no_terminal: bool,
+
+        /// Run in the foreground and exit with the application's exit code
+        #[arg(long)]
+        wait: bool,
+
+        /// Run one of the desktop entry's actions, as listed by `info`
+        #[arg(long, value_name = "ACTION")]
+        action: Option<String>,
+
+        /// Files or URLs to open, given after `--`
+        #[arg(last = true, value_name = "FILE_OR_URL")]
+        targets: Vec<String>,
     },
 
     /// Print exec command for an application
@@ -88,6 +135,18 @@ pub enum Command {
     /// Show system diagnostic: provider status, app counts, warnings
     Doctor,
 
+    /// Show or clear the launch history used for ranking
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
     /// Generate shell completion script
     Completions {
         /// Shell to generate completions for
@@ -95,24 +154,16 @@ pub enum Command {
     },
 }
 
-impl ValueEnum for OutputFormat {
-    fn value_variants<'a>() -> &'a [Self] {
-        &[
-            OutputFormat::Table,
-            OutputFormat::Json,
-            OutputFormat::Tsv,
-            OutputFormat::Names,
-            OutputFormat::Exec,
-        ]
-    }
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the effective configuration and where each value came from
+    Show,
+}
 
-    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
-        match self {
-            OutputFormat::Table => Some(clap::builder::PossibleValue::new("table")),
-            OutputFormat::Json => Some(clap::builder::PossibleValue::new("json")),
-            OutputFormat::Tsv => Some(clap::builder::PossibleValue::new("tsv")),
-            OutputFormat::Names => Some(clap::builder::PossibleValue::new("names")),
-            OutputFormat::Exec => Some(clap::builder::PossibleValue::new("exec")),
-        }
-    }
+#[derive(Subcommand, Debug)]
+pub enum HistoryAction {
+    /// List launched applications, most frecent first (default)
+    Show,
+    /// Forget every recorded launch
+    Clear,
 }
@@ -0,0 +1,572 @@
+//! User and system configuration (`config.toml`).
+//!
+//! Files are read from `$XDG_CONFIG_DIRS/appgrep/config.toml` (default `/etc/xdg`) and
+//! then `$XDG_CONFIG_HOME/appgrep/config.toml`, or `$APPGREP_CONFIG` if set. List
+//! settings accumulate across files; `sources.priority` from a later file replaces an
+//! earlier one. Every value remembers which file it came from for `appgrep config show`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::app::{AppSource, Application};
+use crate::exec;
+use crate::provider::process::DEFAULT_TIMEOUT;
+use crate::search::SearchField;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("cannot read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid config {}: {message}", path.display())]
+    Invalid { path: PathBuf, message: String },
+}
+
+/// Where a configuration value was set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A configuration value together with its origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub origin: Origin,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T, origin: Origin) -> Self {
+        Self { value, origin }
+    }
+}
+
+/// Rules hiding applications from every command.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    /// Globs matched case-insensitively against the application name.
+    pub names: Vec<Setting<Pattern>>,
+    /// Globs matched against the exec command or the program path it starts with.
+    pub exec: Vec<Setting<Pattern>>,
+    pub sources: Vec<Setting<AppSource>>,
+}
+
+impl IgnoreRules {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.exec.is_empty() && self.sources.is_empty()
+    }
+
+    pub fn matches(&self, app: &Application) -> bool {
+        let case_insensitive = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        if self
+            .names
+            .iter()
+            .any(|p| p.value.matches_with(&app.name, case_insensitive))
+        {
+            return true;
+        }
+
+        let program = app.exec_command.split_whitespace().next().unwrap_or("");
+        if self
+            .exec
+            .iter()
+            .any(|p| p.value.matches(&app.exec_command) || p.value.matches(program))
+        {
+            return true;
+        }
+
+        self.sources.iter().any(|s| s.value == app.source)
+    }
+}
+
+/// How long each provider may spend discovering before its results are dropped.
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    pub default: Setting<Duration>,
+    /// Overrides keyed by provider name.
+    pub providers: BTreeMap<String, Setting<Duration>>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            default: Setting::new(DEFAULT_TIMEOUT, Origin::Default),
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn get(&self, provider: &str) -> Duration {
+        self.providers
+            .get(provider)
+            .unwrap_or(&self.default)
+            .value
+    }
+}
+
+/// The effective configuration after merging all files over the defaults.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Files that were read, lowest precedence first.
+    pub files: Vec<PathBuf>,
+    /// Directories scanned by the standalone provider in addition to its defaults.
+    pub standalone_dirs: Vec<Setting<PathBuf>>,
+    /// Directories scanned for .desktop files in addition to the XDG ones.
+    pub desktop_dirs: Vec<Setting<PathBuf>>,
+    /// Deduplication order, highest priority first. Always lists every source.
+    pub priority: Setting<Vec<AppSource>>,
+    /// Providers that only run when their source is requested with `--source`.
+    pub disabled: Vec<Setting<String>>,
+    pub ignore: IgnoreRules,
+    pub timeouts: Timeouts,
+    /// Terminal emulator command that `run` appends terminal applications' commands to.
+    pub terminal: Option<Setting<Vec<String>>>,
+    /// Whether `run` places applications in transient systemd scopes.
+    pub scope: Setting<bool>,
+    /// Multipliers for search matches in each field. Always lists every field.
+    pub search_weights: BTreeMap<SearchField, Setting<f64>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            standalone_dirs: Vec::new(),
+            desktop_dirs: Vec::new(),
+            priority: Setting::new(AppSource::ALL.to_vec(), Origin::Default),
+            disabled: Vec::new(),
+            ignore: IgnoreRules::default(),
+            timeouts: Timeouts::default(),
+            terminal: None,
+            scope: Setting::new(true, Origin::Default),
+            search_weights: SearchField::ALL
+                .iter()
+                .map(|&f| (f, Setting::new(f.default_weight(), Origin::Default)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    standalone: RawStandalone,
+    desktop: RawDesktop,
+    sources: RawSources,
+    ignore: RawIgnore,
+    /// Seconds, as `default` or per provider name.
+    timeouts: BTreeMap<String, f64>,
+    run: RawRun,
+    search: RawSearch,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawSearch {
+    weights: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawRun {
+    terminal: Option<String>,
+    scope: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawStandalone {
+    scan_dirs: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawDesktop {
+    app_dirs: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawSources {
+    priority: Option<Vec<String>>,
+    disabled: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawIgnore {
+    names: Vec<String>,
+    exec: Vec<String>,
+    sources: Vec<String>,
+}
+
+impl Config {
+    /// Config file locations, lowest precedence first.
+    pub fn search_paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+
+        let config_dirs = std::env::var("XDG_CONFIG_DIRS")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| "/etc/xdg".to_string());
+        // XDG_CONFIG_DIRS is ordered most important first
+        for dir in config_dirs.split(':').rev().filter(|d| !d.is_empty()) {
+            paths.push(PathBuf::from(dir).join("appgrep/config.toml"));
+        }
+
+        if let Some(path) = std::env::var_os("APPGREP_CONFIG").filter(|p| !p.is_empty()) {
+            paths.push(PathBuf::from(path));
+        } else if let Some(dir) = dirs::config_dir() {
+            paths.push(dir.join("appgrep/config.toml"));
+        }
+
+        paths
+    }
+
+    /// Load and merge every existing config file.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        for path in Self::search_paths() {
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(source) => return Err(ConfigError::Io { path, source }),
+            };
+            config.merge(&content, &path)?;
+        }
+        Ok(config)
+    }
+
+    /// Merge one file's contents over the current values.
+    pub fn merge(&mut self, content: &str, path: &Path) -> Result<(), ConfigError> {
+        let invalid = |message: String| ConfigError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        };
+        let raw: RawConfig =
+            toml::from_str(content).map_err(|e| invalid(e.to_string().trim().to_string()))?;
+        let origin = Origin::File(path.to_path_buf());
+
+        for dir in raw.standalone.scan_dirs {
+            self.standalone_dirs
+                .push(Setting::new(expand_home(&dir), origin.clone()));
+        }
+        for dir in raw.desktop.app_dirs {
+            self.desktop_dirs
+                .push(Setting::new(expand_home(&dir), origin.clone()));
+        }
+
+        if let Some(priority) = raw.sources.priority {
+            let mut order: Vec<AppSource> = Vec::new();
+            for name in &priority {
+                let source: AppSource = name.parse().map_err(invalid)?;
+                if !order.contains(&source) {
+                    order.push(source);
+                }
+            }
+            // Sources left out keep their default relative order after the listed ones
+            for source in AppSource::ALL {
+                if !order.contains(source) {
+                    order.push(source.clone());
+                }
+            }
+            self.priority = Setting::new(order, origin.clone());
+        }
+
+        for name in raw.sources.disabled {
+            let source: AppSource = name.parse().map_err(invalid)?;
+            self.disabled
+                .push(Setting::new(source.to_string(), origin.clone()));
+        }
+
+        for glob in raw.ignore.names {
+            let pattern = Pattern::new(&glob)
+                .map_err(|e| invalid(format!("ignore.names '{}': {}", glob, e)))?;
+            self.ignore.names.push(Setting::new(pattern, origin.clone()));
+        }
+        for glob in raw.ignore.exec {
+            let pattern = Pattern::new(&expand_home(&glob).to_string_lossy())
+                .map_err(|e| invalid(format!("ignore.exec '{}': {}", glob, e)))?;
+            self.ignore.exec.push(Setting::new(pattern, origin.clone()));
+        }
+        for name in raw.ignore.sources {
+            let source: AppSource = name.parse().map_err(invalid)?;
+            self.ignore.sources.push(Setting::new(source, origin.clone()));
+        }
+
+        for (name, secs) in raw.timeouts {
+            if !secs.is_finite() || secs <= 0.0 {
+                return Err(invalid(format!(
+                    "timeouts.{}: expected a positive number of seconds",
+                    name
+                )));
+            }
+            let timeout = Setting::new(Duration::from_secs_f64(secs), origin.clone());
+            if name == "default" {
+                self.timeouts.default = timeout;
+            } else {
+                let source: AppSource = name.parse().map_err(invalid)?;
+                self.timeouts.providers.insert(source.to_string(), timeout);
+            }
+        }
+
+        if let Some(terminal) = raw.run.terminal {
+            let argv = exec::split(&terminal)
+                .map_err(|e| invalid(format!("run.terminal '{}': {}", terminal, e)))?;
+            self.terminal = Some(Setting::new(argv, origin.clone()));
+        }
+        if let Some(scope) = raw.run.scope {
+            self.scope = Setting::new(scope, origin.clone());
+        }
+
+        for (name, weight) in raw.search.weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(invalid(format!(
+                    "search.weights.{}: expected a non-negative number",
+                    name
+                )));
+            }
+            let field: SearchField = name
+                .parse()
+                .map_err(|e| invalid(format!("search.weights: {}", e)))?;
+            self.search_weights
+                .insert(field, Setting::new(weight, origin.clone()));
+        }
+
+        self.files.push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Expand a leading `~/` to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_app(name: &str, exec: &str, source: AppSource) -> Application {
+        Application::new(name, exec, source)
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+        assert!(config.files.is_empty());
+        assert_eq!(config.priority.value, AppSource::ALL);
+        assert_eq!(config.priority.origin, Origin::Default);
+        assert!(config.ignore.is_empty());
+    }
+
+    #[test]
+    fn test_merge_full_file() {
+        let content = r#"
+[standalone]
+scan_dirs = ["/srv/tools/bin"]
+
+[desktop]
+app_dirs = ["/srv/share/applications"]
+
+[sources]
+priority = ["flatpak", "desktop"]
+disabled = ["npm"]
+
+[ignore]
+names = ["*-debug"]
+exec = ["/opt/legacy/*"]
+sources = ["brew"]
+"#;
+        let path = Path::new("/etc/xdg/appgrep/config.toml");
+        let mut config = Config::default();
+        config.merge(content, path).unwrap();
+
+        assert_eq!(config.files, vec![path.to_path_buf()]);
+        assert_eq!(
+            config.standalone_dirs[0].value,
+            PathBuf::from("/srv/tools/bin")
+        );
+        assert_eq!(config.standalone_dirs[0].origin, Origin::File(path.to_path_buf()));
+        assert_eq!(
+            config.desktop_dirs[0].value,
+            PathBuf::from("/srv/share/applications")
+        );
+        assert_eq!(
+            &config.priority.value[..3],
+            &[AppSource::Flatpak, AppSource::Desktop, AppSource::Snap]
+        );
+        assert_eq!(config.priority.value.len(), AppSource::ALL.len());
+        assert_eq!(config.disabled[0].value, "npm");
+
+        let ignore = &config.ignore;
+        assert!(ignore.matches(&make_app("GIMP-Debug", "/usr/bin/gimp", AppSource::Desktop)));
+        assert!(ignore.matches(&make_app("old", "/opt/legacy/old --flag", AppSource::Standalone)));
+        assert!(ignore.matches(&make_app("jq", "/home/linuxbrew/bin/jq", AppSource::Brew)));
+        assert!(!ignore.matches(&make_app("gimp", "/usr/bin/gimp", AppSource::Desktop)));
+    }
+
+    #[test]
+    fn test_later_files_accumulate_and_override() {
+        let mut config = Config::default();
+        let system = Path::new("/etc/xdg/appgrep/config.toml");
+        let user = Path::new("/home/me/.config/appgrep/config.toml");
+        config
+            .merge(
+                "[standalone]\nscan_dirs = [\"/srv/a\"]\n[sources]\npriority = [\"snap\"]\n",
+                system,
+            )
+            .unwrap();
+        config
+            .merge(
+                "[standalone]\nscan_dirs = [\"/srv/b\"]\n[sources]\npriority = [\"nix\"]\n",
+                user,
+            )
+            .unwrap();
+
+        let dirs: Vec<&PathBuf> = config.standalone_dirs.iter().map(|d| &d.value).collect();
+        assert_eq!(dirs, vec![&PathBuf::from("/srv/a"), &PathBuf::from("/srv/b")]);
+        assert_eq!(config.priority.value[0], AppSource::Nix);
+        assert_eq!(config.priority.origin, Origin::File(user.to_path_buf()));
+    }
+
+    #[test]
+    fn test_merge_rejects_invalid_source() {
+        let mut config = Config::default();
+        let err = config
+            .merge("[sources]\ndisabled = [\"not a source\"]\n", Path::new("c.toml"))
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid source 'not a source'"));
+    }
+
+    #[test]
+    fn test_merge_accepts_plugin_sources() {
+        let mut config = Config::default();
+        config
+            .merge(
+                "[sources]\npriority = [\"acme\", \"desktop\"]\ndisabled = [\"acme\"]\n",
+                Path::new("c.toml"),
+            )
+            .unwrap();
+        assert_eq!(config.priority.value[0], AppSource::Custom("acme".to_string()));
+        assert_eq!(config.priority.value.len(), AppSource::ALL.len() + 1);
+        assert_eq!(config.disabled[0].value, "acme");
+    }
+
+    #[test]
+    fn test_merge_rejects_unknown_key() {
+        let mut config = Config::default();
+        let err = config
+            .merge("[standalone]\nscan_dir = [\"/x\"]\n", Path::new("c.toml"))
+            .unwrap_err();
+        assert!(err.to_string().contains("c.toml"));
+    }
+
+    #[test]
+    fn test_merge_timeouts() {
+        let mut config = Config::default();
+        assert_eq!(config.timeouts.get("snap"), DEFAULT_TIMEOUT);
+        config
+            .merge(
+                "[timeouts]\ndefault = 5\nsnap = 0.5\nacme = 30\n",
+                Path::new("c.toml"),
+            )
+            .unwrap();
+        assert_eq!(config.timeouts.get("snap"), Duration::from_millis(500));
+        assert_eq!(config.timeouts.get("acme"), Duration::from_secs(30));
+        assert_eq!(config.timeouts.get("brew"), Duration::from_secs(5));
+
+        let err = config
+            .merge("[timeouts]\nbrew = 0\n", Path::new("c.toml"))
+            .unwrap_err();
+        assert!(err.to_string().contains("timeouts.brew"));
+        assert!(config
+            .merge("[timeouts]\n\"not a source\" = 1\n", Path::new("c.toml"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_merge_run_terminal() {
+        let mut config = Config::default();
+        assert!(config.terminal.is_none());
+        config
+            .merge("[run]\nterminal = \"gnome-terminal --\"\n", Path::new("c.toml"))
+            .unwrap();
+        assert_eq!(config.terminal.unwrap().value, vec!["gnome-terminal", "--"]);
+
+        let err = Config::default()
+            .merge("[run]\nterminal = \"\"\n", Path::new("c.toml"))
+            .unwrap_err();
+        assert!(err.to_string().contains("run.terminal"));
+    }
+
+    #[test]
+    fn test_merge_run_scope() {
+        let mut config = Config::default();
+        assert!(config.scope.value);
+        config
+            .merge("[run]\nscope = false\n", Path::new("c.toml"))
+            .unwrap();
+        assert!(!config.scope.value);
+        assert_eq!(config.scope.origin, Origin::File(PathBuf::from("c.toml")));
+    }
+
+    #[test]
+    fn test_merge_search_weights() {
+        let mut config = Config::default();
+        assert_eq!(config.search_weights[&SearchField::Name].value, 1.0);
+        config
+            .merge(
+                "[search.weights]\ndescription = 0\nkeywords = 1.5\n",
+                Path::new("c.toml"),
+            )
+            .unwrap();
+        assert_eq!(config.search_weights[&SearchField::Description].value, 0.0);
+        assert_eq!(config.search_weights[&SearchField::Keywords].value, 1.5);
+        assert_eq!(config.search_weights[&SearchField::Name].origin, Origin::Default);
+
+        let err = Config::default()
+            .merge("[search.weights]\ntitle = 1\n", Path::new("c.toml"))
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown search field 'title'"));
+        let err = Config::default()
+            .merge("[search.weights]\nname = -1\n", Path::new("c.toml"))
+            .unwrap_err();
+        assert!(err.to_string().contains("search.weights.name"));
+    }
+
+    #[test]
+    fn test_expand_home() {
+        assert_eq!(expand_home("/abs/path"), PathBuf::from("/abs/path"));
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(expand_home("~/tools"), home.join("tools"));
+        }
+    }
+}
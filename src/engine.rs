@@ -1,116 +1,507 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use rayon::prelude::*;
+use serde::Serialize;
 
 use crate::app::{AppSource, Application};
+use crate::cache::DiscoveryCache;
+use crate::config::{Config, IgnoreRules, Timeouts};
+use crate::history::LaunchHistory;
+#[cfg(feature = "brew")]
 use crate::provider::brew::BrewProvider;
+#[cfg(feature = "cargo")]
 use crate::provider::cargo::CargoProvider;
-use crate::provider::desktop::DesktopProvider;
+#[cfg(feature = "desktop")]
+use crate::provider::desktop::{DesktopProvider, Locale, ShowContext};
+#[cfg(feature = "rpm")]
 use crate::provider::dnf::RpmProvider;
+#[cfg(feature = "dpkg")]
 use crate::provider::dpkg::DpkgProvider;
+#[cfg(feature = "flatpak")]
 use crate::provider::flatpak::FlatpakProvider;
+#[cfg(feature = "nix")]
+use crate::provider::nix::NixProvider;
+#[cfg(feature = "npm")]
 use crate::provider::npm::NpmProvider;
+#[cfg(feature = "pacman")]
 use crate::provider::pacman::PacmanProvider;
+#[cfg(feature = "pipx")]
+use crate::provider::pipx::PipxProvider;
+#[cfg(feature = "plugins")]
+use crate::provider::plugin::PluginProvider;
+#[cfg(feature = "snap")]
 use crate::provider::snap::SnapProvider;
+#[cfg(feature = "standalone")]
 use crate::provider::standalone::StandaloneProvider;
-use crate::provider::AppProvider;
+#[cfg(feature = "uv")]
+use crate::provider::uv::UvProvider;
+use crate::provider::{process, AppProvider, BinaryMode, ProviderError};
+use crate::search::{self, SearchField, SearchMatch};
 
 pub struct DiscoveryEngine {
-    providers: Vec<Box<dyn AppProvider>>,
+    providers: Vec<Arc<dyn AppProvider>>,
+    cache: Option<DiscoveryCache>,
+    history: Option<LaunchHistory>,
+    priority: Vec<AppSource>,
+    disabled: HashSet<String>,
+    ignore: IgnoreRules,
+    timeouts: Timeouts,
+    timeout_override: Option<Duration>,
+    search_weights: BTreeMap<SearchField, f64>,
+    warnings: Mutex<Vec<ProviderWarning>>,
+}
+
+/// A provider whose results are missing from a discovery.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderWarning {
+    pub provider: String,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WarningKind {
+    /// The provider did not finish within its timeout.
+    Timeout,
+    /// The provider reported an error.
+    Error,
+}
+
+impl fmt::Display for ProviderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            WarningKind::Timeout => write!(f, "provider '{}' {}", self.provider, self.message),
+            WarningKind::Error => write!(f, "provider '{}' failed: {}", self.provider, self.message),
+        }
+    }
+}
+
+/// Chooses the providers and options of a [`DiscoveryEngine`].
+pub struct DiscoveryEngineBuilder {
+    config: Config,
+    binary_mode: BinaryMode,
+    show_hidden: bool,
+    locale: Option<String>,
+    builtin_providers: bool,
+    plugins: bool,
+    excluded: HashSet<String>,
+    extra: Vec<Arc<dyn AppProvider>>,
+    cache: Option<DiscoveryCache>,
+    history: Option<LaunchHistory>,
+    timeout: Option<Duration>,
+}
+
+impl Default for DiscoveryEngineBuilder {
+    fn default() -> Self {
+        Self {
+            config: Config::default(),
+            binary_mode: BinaryMode::default(),
+            show_hidden: false,
+            locale: None,
+            builtin_providers: true,
+            plugins: true,
+            excluded: HashSet::new(),
+            extra: Vec::new(),
+            cache: None,
+            history: None,
+            timeout: None,
+        }
+    }
+}
+
+impl DiscoveryEngineBuilder {
+    /// Apply scan directories, priorities, disabled providers and ignores from `config`.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Which executables the system package providers report.
+    pub fn binary_mode(mut self, binary_mode: BinaryMode) -> Self {
+        self.binary_mode = binary_mode;
+        self
+    }
+
+    /// Also list desktop entries that would not be shown, such as `NoDisplay=true` ones
+    /// or those whose `TryExec` program is missing, with the reason.
+    pub fn show_hidden(mut self, show_hidden: bool) -> Self {
+        self.show_hidden = show_hidden;
+        self
+    }
+
+    /// Translate desktop entries for `locale`, such as `de_DE.UTF-8`, instead of the one
+    /// from `$LC_ALL`, `$LC_MESSAGES` or `$LANG`.
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Include the providers compiled into this build (default: true).
+    pub fn builtin_providers(mut self, enabled: bool) -> Self {
+        self.builtin_providers = enabled;
+        self
+    }
+
+    /// Load plugins from the `providers.d` directories (default: true).
+    pub fn plugins(mut self, enabled: bool) -> Self {
+        self.plugins = enabled;
+        self
+    }
+
+    /// Leave out the provider with this name.
+    pub fn exclude(mut self, name: &str) -> Self {
+        self.excluded.insert(name.to_string());
+        self
+    }
+
+    /// Add a provider after the built-in ones.
+    pub fn provider<P: AppProvider + 'static>(mut self, provider: P) -> Self {
+        self.extra.push(Arc::new(provider));
+        self
+    }
+
+    /// Serve provider results from `cache` while their watched paths are unchanged.
+    pub fn cache(mut self, cache: DiscoveryCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Rank applications launched often and recently higher in searches and lookups.
+    pub fn history(mut self, history: LaunchHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Give every provider this timeout, overriding the configured ones.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> DiscoveryEngine {
+        let mut providers: Vec<Arc<dyn AppProvider>> = Vec::new();
+        if self.builtin_providers {
+            providers.extend(builtin_providers(
+                &self.config,
+                self.binary_mode,
+                self.show_hidden,
+                self.locale.as_deref(),
+            ));
+        }
+        #[cfg(not(feature = "plugins"))]
+        let _ = self.plugins;
+        #[cfg(feature = "plugins")]
+        if self.plugins {
+            for plugin in PluginProvider::load_all(&PluginProvider::plugin_dirs()) {
+                providers.push(Arc::new(plugin));
+            }
+        }
+        providers.extend(self.extra);
+        providers.retain(|p| !self.excluded.contains(p.name()));
+
+        DiscoveryEngine {
+            providers,
+            cache: self.cache,
+            history: self.history,
+            priority: self.config.priority.value.clone(),
+            disabled: self.config.disabled.iter().map(|d| d.value.clone()).collect(),
+            ignore: self.config.ignore,
+            timeouts: self.config.timeouts,
+            timeout_override: self.timeout,
+            search_weights: self
+                .config
+                .search_weights
+                .iter()
+                .map(|(field, weight)| (*field, weight.value))
+                .collect(),
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// Every provider compiled into this build, in default priority order.
+#[allow(unused_variables)]
+fn builtin_providers(
+    config: &Config,
+    binary_mode: BinaryMode,
+    show_hidden: bool,
+    locale: Option<&str>,
+) -> Vec<Arc<dyn AppProvider>> {
+    // Every provider that reads desktop entries translates them for the same locale
+    #[cfg(feature = "desktop")]
+    let context = {
+        let mut context = ShowContext::current();
+        if let Some(locale) = locale {
+            context.locale = Locale::parse(locale);
+        }
+        context
+    };
+
+    vec![
+        #[cfg(feature = "desktop")]
+        Arc::new({
+            let dirs = config.desktop_dirs.iter().map(|d| d.value.clone()).collect();
+            DesktopProvider::new()
+                .with_extra_dirs(dirs)
+                .with_show_hidden(show_hidden)
+                .with_context(context.clone())
+        }),
+        #[cfg(feature = "flatpak")]
+        Arc::new(FlatpakProvider::new().with_context(context.clone())),
+        #[cfg(feature = "snap")]
+        Arc::new(SnapProvider::new()),
+        #[cfg(feature = "pipx")]
+        Arc::new(PipxProvider::new()),
+        #[cfg(feature = "uv")]
+        Arc::new(UvProvider::new()),
+        #[cfg(feature = "standalone")]
+        Arc::new({
+            let dirs = config.standalone_dirs.iter().map(|d| d.value.clone()).collect();
+            StandaloneProvider::new()
+                .with_extra_dirs(dirs)
+                .with_context(context.clone())
+        }),
+        #[cfg(feature = "cargo")]
+        Arc::new(CargoProvider::new()),
+        #[cfg(feature = "npm")]
+        Arc::new(NpmProvider::new()),
+        #[cfg(feature = "dpkg")]
+        Arc::new(DpkgProvider::new().with_binary_mode(binary_mode)),
+        #[cfg(feature = "rpm")]
+        Arc::new(RpmProvider::new().with_binary_mode(binary_mode)),
+        #[cfg(feature = "pacman")]
+        Arc::new(PacmanProvider::new().with_binary_mode(binary_mode)),
+        #[cfg(feature = "brew")]
+        Arc::new(BrewProvider::new()),
+        #[cfg(feature = "nix")]
+        Arc::new(NixProvider::new().with_context(context.clone())),
+    ]
+}
+
+impl Default for DiscoveryEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DiscoveryEngine {
+    /// An engine with every compiled-in provider and plugin, default options and no cache.
     pub fn new() -> Self {
-        let providers: Vec<Box<dyn AppProvider>> = vec![
-            Box::new(DesktopProvider::new()),
-            Box::new(FlatpakProvider::new()),
-            Box::new(SnapProvider::new()),
-            Box::new(StandaloneProvider::new()),
-            Box::new(CargoProvider::new()),
-            Box::new(NpmProvider::new()),
-            Box::new(DpkgProvider::new()),
-            Box::new(RpmProvider::new()),
-            Box::new(PacmanProvider::new()),
-            Box::new(BrewProvider::new()),
-        ];
-        Self { providers }
+        Self::builder().build()
+    }
+
+    pub fn builder() -> DiscoveryEngineBuilder {
+        DiscoveryEngineBuilder::default()
+    }
+
+    pub fn cache(&self) -> Option<&DiscoveryCache> {
+        self.cache.as_ref()
+    }
+
+    pub fn history(&self) -> Option<&LaunchHistory> {
+        self.history.as_ref()
+    }
+
+    /// Factor to scale `app`'s match score by for its launch history.
+    fn boost(&self, app: &Application, now: SystemTime) -> f64 {
+        self.history.as_ref().map_or(1.0, |h| h.boost(app, now))
     }
 
     /// Get a reference to all registered providers.
-    pub fn providers(&self) -> &[Box<dyn AppProvider>] {
+    pub fn providers(&self) -> &[Arc<dyn AppProvider>] {
         &self.providers
     }
 
-    /// Discover all applications from all available providers in parallel.
+    /// Whether a provider is turned off by configuration.
+    pub fn is_disabled(&self, provider: &str) -> bool {
+        self.disabled.contains(provider)
+    }
+
+    /// Discover all applications from all enabled, available providers in parallel.
     pub fn discover_all(&self) -> Vec<Application> {
-        let results: Vec<Vec<Application>> = self
+        self.discover_with(|p| !self.is_disabled(p.name()))
+    }
+
+    /// Discover applications filtered by source types.
+    pub fn discover_filtered(&self, sources: &[AppSource]) -> Vec<Application> {
+        // Disabled providers still run when their source is asked for explicitly
+        let requested: HashSet<String> = sources.iter().map(|s| s.to_string()).collect();
+        let all = self.discover_with(|p| {
+            !self.is_disabled(p.name()) || requested.contains(p.name())
+        });
+        all.into_iter()
+            .filter(|app| sources.contains(&app.source))
+            .collect()
+    }
+
+    /// How long `provider` may take before its results are dropped.
+    pub fn timeout_for(&self, provider: &str) -> Duration {
+        self.timeout_override
+            .unwrap_or_else(|| self.timeouts.get(provider))
+    }
+
+    /// Providers that failed or timed out during the most recent discovery.
+    pub fn warnings(&self) -> Vec<ProviderWarning> {
+        self.warnings.lock().map(|w| w.clone()).unwrap_or_default()
+    }
+
+    /// Run a single provider live, bypassing the cache. `Ok(None)` if it is unavailable.
+    pub fn discover_provider(
+        &self,
+        provider: &Arc<dyn AppProvider>,
+    ) -> Result<Option<Vec<Application>>, ProviderError> {
+        self.run_provider(provider, None)
+    }
+
+    /// Run `provider` on its own thread and give up on it once its timeout expires.
+    fn run_provider(
+        &self,
+        provider: &Arc<dyn AppProvider>,
+        cache: Option<DiscoveryCache>,
+    ) -> Result<Option<Vec<Application>>, ProviderError> {
+        let timeout = self.timeout_for(provider.name());
+        let worker = Arc::clone(provider);
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name(format!("appgrep-{}", provider.name()))
+            .spawn(move || {
+                let result = process::with_deadline(timeout, || {
+                    if !worker.is_available() {
+                        return Ok(None);
+                    }
+                    match &cache {
+                        Some(cache) => cache.get_or_discover(worker.as_ref()),
+                        None => worker.discover(),
+                    }
+                    .map(Some)
+                });
+                let _ = tx.send(result);
+            })?;
+
+        // Subprocesses are killed at the deadline; a provider stuck elsewhere is abandoned
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(ProviderError::Timeout(timeout)),
+            Err(RecvTimeoutError::Disconnected) => Err(ProviderError::ParseError(format!(
+                "provider '{}' panicked",
+                provider.name()
+            ))),
+        }
+    }
+
+    fn discover_with<F>(&self, enabled: F) -> Vec<Application>
+    where
+        F: Fn(&dyn AppProvider) -> bool + Sync,
+    {
+        let outcomes: Vec<Result<Vec<Application>, ProviderWarning>> = self
             .providers
             .par_iter()
-            .filter(|p| p.is_available())
-            .map(|p| match p.discover() {
-                Ok(apps) => apps,
-                Err(e) => {
-                    eprintln!("appgrep: warning: provider '{}' failed: {}", p.name(), e);
-                    Vec::new()
-                }
+            .filter(|p| enabled(p.as_ref()))
+            .map(|p| match self.run_provider(p, self.cache.clone()) {
+                Ok(apps) => Ok(apps.unwrap_or_default()),
+                Err(ProviderError::Timeout(timeout)) => Err(ProviderWarning {
+                    provider: p.name().to_string(),
+                    kind: WarningKind::Timeout,
+                    message: ProviderError::Timeout(timeout).to_string(),
+                }),
+                Err(e) => Err(ProviderWarning {
+                    provider: p.name().to_string(),
+                    kind: WarningKind::Error,
+                    message: e.to_string(),
+                }),
             })
             .collect();
 
-        let all_apps: Vec<Application> = results.into_iter().flatten().collect();
-        let mut deduped = Self::deduplicate(all_apps);
+        let mut results = Vec::new();
+        let mut warnings = Vec::new();
+        for outcome in outcomes {
+            match outcome {
+                Ok(apps) => results.push(apps),
+                Err(warning) => warnings.push(warning),
+            }
+        }
+        if let Ok(mut w) = self.warnings.lock() {
+            *w = warnings;
+        }
+
+        let all_apps: Vec<Application> = results
+            .into_iter()
+            .flatten()
+            .filter(|app| !self.ignore.matches(app))
+            .collect();
+        let mut deduped = Self::deduplicate(all_apps, &self.priority);
         deduped.sort();
         deduped
     }
 
-    /// Discover applications filtered by source types.
-    pub fn discover_filtered(&self, sources: &[AppSource]) -> Vec<Application> {
-        let all = self.discover_all();
-        all.into_iter()
-            .filter(|app| sources.contains(&app.source))
+    /// Fuzzy search applications, best match first.
+    pub fn search(&self, query: &str, apps: &[Application]) -> Vec<Application> {
+        self.search_matches(query, apps)
+            .into_iter()
+            .map(|m| m.app)
             .collect()
     }
 
-    /// Fuzzy search applications by name and description.
-    pub fn search(&self, query: &str, apps: &[Application]) -> Vec<Application> {
+    /// Fuzzy search applications by name, generic name, keywords, exec basename,
+    /// categories and description, weighting each field by the configured weights and
+    /// boosting frequently launched applications. Results carry their score and the field
+    /// that matched best.
+    pub fn search_matches(&self, query: &str, apps: &[Application]) -> Vec<SearchMatch> {
         let matcher = SkimMatcherV2::default();
-        let mut scored: Vec<(i64, &Application)> = apps
+        let now = SystemTime::now();
+        let mut matches: Vec<SearchMatch> = apps
             .iter()
             .filter_map(|app| {
-                let name_score = matcher.fuzzy_match(&app.name, query).unwrap_or(0);
-                let desc_score = app
-                    .description
-                    .as_ref()
-                    .and_then(|d| matcher.fuzzy_match(d, query))
-                    .unwrap_or(0);
-                let score = name_score.max(desc_score);
-                if score > 0 {
-                    Some((score, app))
-                } else {
-                    None
-                }
+                search::score(&matcher, app, query, &self.search_weights).map(
+                    |(score, matched_field)| SearchMatch {
+                        app: app.clone(),
+                        score: (score as f64 * self.boost(app, now)).round() as i64,
+                        matched_field,
+                    },
+                )
             })
             .collect();
 
-        scored.sort_by(|a, b| b.0.cmp(&a.0));
-        scored.into_iter().map(|(_, app)| app.clone()).collect()
+        matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+        matches
     }
 
     /// Find an application by name: exact case-insensitive match first, then fuzzy best.
+    /// Ties go to the application launched most often and most recently.
     pub fn find_by_name(&self, name: &str, apps: &[Application]) -> Option<Application> {
         let lower = name.to_lowercase();
+        let now = SystemTime::now();
 
         // Exact case-insensitive match
-        if let Some(app) = apps.iter().find(|a| a.name.to_lowercase() == lower) {
+        let mut best: Option<(f64, &Application)> = None;
+        for app in apps
+            .iter()
+            .filter(|a| a.names().any(|n| n.to_lowercase() == lower))
+        {
+            let boost = self.boost(app, now);
+            match &best {
+                Some((best_boost, _)) if boost <= *best_boost => {}
+                _ => best = Some((boost, app)),
+            }
+        }
+        if let Some((_, app)) = best {
             return Some(app.clone());
         }
 
         // Fuzzy best match
         let matcher = SkimMatcherV2::default();
-        let mut best: Option<(i64, &Application)> = None;
+        let mut best: Option<(f64, &Application)> = None;
         for app in apps {
-            if let Some(score) = matcher.fuzzy_match(&app.name, name) {
+            if let Some(score) = app.names().filter_map(|n| matcher.fuzzy_match(n, name)).max() {
+                let score = score as f64 * self.boost(app, now);
                 match &best {
                     Some((best_score, _)) if score <= *best_score => {}
                     _ => best = Some((score, app)),
@@ -122,8 +513,15 @@ impl DiscoveryEngine {
     }
 
     /// Deduplicate applications by normalized exec command.
-    /// When duplicates exist: prefer higher-priority source, then more metadata.
-    fn deduplicate(apps: Vec<Application>) -> Vec<Application> {
+    /// When duplicates exist: prefer the source earlier in `priority`, then more metadata.
+    fn deduplicate(apps: Vec<Application>, priority: &[AppSource]) -> Vec<Application> {
+        let rank = |source: &AppSource| {
+            priority
+                .iter()
+                .position(|s| s == source)
+                .unwrap_or(usize::MAX)
+        };
+
         let mut groups: HashMap<String, Vec<Application>> = HashMap::new();
 
         for app in apps {
@@ -134,20 +532,47 @@ impl DiscoveryEngine {
         groups
             .into_values()
             .map(|mut group| {
+                // Entries listed only for --show-hidden never shadow visible ones
                 group.sort_by(|a, b| {
-                    a.source
-                        .priority()
-                        .cmp(&b.source.priority())
+                    (a.hidden_reason.is_some(), rank(&a.source))
+                        .cmp(&(b.hidden_reason.is_some(), rank(&b.source)))
                         .then_with(|| b.metadata_richness().cmp(&a.metadata_richness()))
                 });
-                group.into_iter().next().unwrap()
+                let mut group = group.into_iter();
+                let mut best = group.next().unwrap();
+                // Shadowed duplicates, like a flatpak's exported desktop entry, fill in
+                // details the preferred one lacks
+                for other in group {
+                    if best.permissions.is_none() {
+                        best.permissions = other.permissions;
+                    }
+                    for (key, value) in other.metadata {
+                        best.metadata.entry(key).or_insert(value);
+                    }
+                }
+                best
             })
             .collect()
     }
 }
 
 /// Normalize an exec command for deduplication comparison.
+///
+/// `flatpak run` commands are keyed by application ID, since they all start the same
+/// program. A leading `env VAR=value` is skipped, as in snap desktop entries.
 fn normalize_exec(exec: &str) -> String {
+    let exec = strip_env_prefix(exec);
+    let mut args = exec.split_whitespace();
+    if args
+        .next()
+        .is_some_and(|program| program == "flatpak" || program.ends_with("/flatpak"))
+        && args.next() == Some("run")
+    {
+        if let Some(app_id) = args.find(|a| !a.starts_with('-')) {
+            return format!("flatpak:{}", app_id.to_lowercase());
+        }
+    }
+
     let trimmed = exec.trim();
     // Strip quotes around the path
     let unquoted = trimmed
@@ -160,21 +585,30 @@ fn normalize_exec(exec: &str) -> String {
     unquoted.to_lowercase()
 }
 
+/// `exec` without a leading `env` and its variable assignments.
+fn strip_env_prefix(exec: &str) -> &str {
+    let mut rest = exec.trim_start();
+    let program = rest.split_whitespace().next().unwrap_or("");
+    if program != "env" && !program.ends_with("/env") {
+        return rest;
+    }
+    rest = rest[program.len()..].trim_start();
+    while let Some(arg) = rest.split_whitespace().next() {
+        if !arg.contains('=') && !arg.starts_with('-') {
+            break;
+        }
+        rest = rest[arg.len()..].trim_start();
+    }
+    rest
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::app::{AppSource, Application};
 
     fn make_app(name: &str, exec: &str, source: AppSource) -> Application {
-        Application {
-            name: name.to_string(),
-            exec_command: exec.to_string(),
-            source,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
-            description: None,
-        }
+        Application::new(name, exec, source)
     }
 
     fn make_app_with_desc(
@@ -187,11 +621,86 @@ mod tests {
             name: name.to_string(),
             exec_command: exec.to_string(),
             source,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
             description: desc.map(|s| s.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns one app after sleeping for `delay`.
+    struct SleepyProvider {
+        name: &'static str,
+        delay: Duration,
+    }
+
+    impl AppProvider for SleepyProvider {
+        fn name(&self) -> &str {
+            self.name
         }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+            thread::sleep(self.delay);
+            let exec = format!("/usr/bin/{}", self.name);
+            Ok(vec![make_app(self.name, &exec, AppSource::Custom(self.name.to_string()))])
+        }
+    }
+
+    fn sleepy_engine(timeout: Duration) -> DiscoveryEngine {
+        DiscoveryEngine::builder()
+            .builtin_providers(false)
+            .plugins(false)
+            .provider(SleepyProvider {
+                name: "fast",
+                delay: Duration::ZERO,
+            })
+            .provider(SleepyProvider {
+                name: "slow",
+                delay: Duration::from_secs(5),
+            })
+            .timeout(timeout)
+            .build()
+    }
+
+    #[test]
+    fn test_timed_out_provider_is_reported_and_others_kept() {
+        let engine = sleepy_engine(Duration::from_millis(200));
+        let start = std::time::Instant::now();
+        let apps = engine.discover_all();
+        assert!(start.elapsed() < Duration::from_secs(4));
+
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "fast");
+        let warnings = engine.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].provider, "slow");
+        assert_eq!(warnings[0].kind, WarningKind::Timeout);
+        assert_eq!(warnings[0].to_string(), "provider 'slow' timed out after 200ms");
+    }
+
+    #[test]
+    fn test_timeout_for_uses_config_then_override() {
+        let mut config = Config::default();
+        config
+            .merge("[timeouts]\nslow = 2\n", std::path::Path::new("c.toml"))
+            .unwrap();
+        let engine = DiscoveryEngine::builder()
+            .builtin_providers(false)
+            .plugins(false)
+            .config(config.clone())
+            .build();
+        assert_eq!(engine.timeout_for("slow"), Duration::from_secs(2));
+        assert_eq!(engine.timeout_for("fast"), process::DEFAULT_TIMEOUT);
+
+        let engine = DiscoveryEngine::builder()
+            .builtin_providers(false)
+            .plugins(false)
+            .config(config)
+            .timeout(Duration::from_secs(1))
+            .build();
+        assert_eq!(engine.timeout_for("slow"), Duration::from_secs(1));
     }
 
     #[test]
@@ -200,7 +709,7 @@ mod tests {
             make_app("Firefox", "/usr/bin/firefox", AppSource::Standalone),
             make_app("Firefox", "/usr/bin/firefox", AppSource::Desktop),
         ];
-        let deduped = DiscoveryEngine::deduplicate(apps);
+        let deduped = DiscoveryEngine::deduplicate(apps, AppSource::ALL);
         assert_eq!(deduped.len(), 1);
         assert_eq!(deduped[0].source, AppSource::Desktop);
     }
@@ -216,21 +725,65 @@ mod tests {
                 Some("Web Browser"),
             ),
         ];
-        let deduped = DiscoveryEngine::deduplicate(apps);
+        let deduped = DiscoveryEngine::deduplicate(apps, AppSource::ALL);
         assert_eq!(deduped.len(), 1);
         assert!(deduped[0].description.is_some());
     }
 
+    #[test]
+    fn test_deduplicate_fills_in_details_from_duplicates() {
+        let desktop = make_app(
+            "Firefox",
+            "/usr/bin/flatpak run --branch=stable org.mozilla.firefox @@u %u @@",
+            AppSource::Desktop,
+        );
+        let mut flatpak = make_app(
+            "Firefox",
+            "/usr/bin/flatpak run --branch=stable org.mozilla.firefox @@u %u @@",
+            AppSource::Flatpak,
+        );
+        flatpak.permissions = Some(Default::default());
+        flatpak
+            .metadata
+            .insert("origin".to_string(), "flathub".to_string());
+        let deduped = DiscoveryEngine::deduplicate(vec![desktop, flatpak], AppSource::ALL);
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].source, AppSource::Desktop);
+        assert!(deduped[0].permissions.is_some());
+        assert_eq!(deduped[0].metadata["origin"], "flathub");
+    }
+
     #[test]
     fn test_deduplicate_different_apps_kept() {
         let apps = vec![
             make_app("Firefox", "/usr/bin/firefox", AppSource::Desktop),
             make_app("GIMP", "/usr/bin/gimp", AppSource::Desktop),
         ];
-        let deduped = DiscoveryEngine::deduplicate(apps);
+        let deduped = DiscoveryEngine::deduplicate(apps, AppSource::ALL);
         assert_eq!(deduped.len(), 2);
     }
 
+    #[test]
+    fn test_deduplicate_follows_configured_priority() {
+        let apps = vec![
+            make_app("Firefox", "/usr/bin/firefox", AppSource::Flatpak),
+            make_app("Firefox", "/usr/bin/firefox", AppSource::Desktop),
+        ];
+        let priority = [AppSource::Flatpak, AppSource::Desktop];
+        let deduped = DiscoveryEngine::deduplicate(apps, &priority);
+        assert_eq!(deduped[0].source, AppSource::Flatpak);
+    }
+
+    #[test]
+    fn test_deduplicate_prefers_visible_entries() {
+        let mut hidden = make_app("Htop", "/usr/bin/htop", AppSource::Desktop);
+        hidden.hidden_reason = Some("NoDisplay=true".to_string());
+        let apps = vec![hidden, make_app("htop", "/usr/bin/htop", AppSource::Dpkg)];
+        let deduped = DiscoveryEngine::deduplicate(apps, AppSource::ALL);
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].source, AppSource::Dpkg);
+    }
+
     #[test]
     fn test_find_by_name_exact() {
         let apps = vec![
@@ -243,6 +796,18 @@ mod tests {
         assert_eq!(found.unwrap().name, "Firefox");
     }
 
+    #[test]
+    fn test_find_and_search_by_untranslated_name() {
+        let mut files = make_app("Dateien", "/usr/bin/nautilus", AppSource::Desktop);
+        files.untranslated_name = Some("Files".to_string());
+        let apps = vec![files, make_app("GIMP", "/usr/bin/gimp", AppSource::Desktop)];
+        let engine = DiscoveryEngine::new();
+        assert_eq!(engine.find_by_name("files", &apps).unwrap().name, "Dateien");
+        assert_eq!(engine.find_by_name("dateien", &apps).unwrap().name, "Dateien");
+        assert_eq!(engine.search("Files", &apps)[0].name, "Dateien");
+        assert_eq!(engine.search("Dateien", &apps)[0].name, "Dateien");
+    }
+
     #[test]
     fn test_find_by_name_fuzzy() {
         let apps = vec![
@@ -277,6 +842,73 @@ mod tests {
         assert_eq!(results[0].name, "Firefox");
     }
 
+    #[test]
+    fn test_search_matches_generic_name_and_keywords() {
+        let mut firefox = make_app("Firefox", "/usr/bin/firefox", AppSource::Desktop);
+        firefox.generic_name = Some("Web Browser".to_string());
+        let mut thunderbird = make_app("Thunderbird", "/usr/bin/thunderbird", AppSource::Desktop);
+        thunderbird.keywords = vec!["mail".to_string(), "browser".to_string()];
+        let apps = vec![
+            thunderbird,
+            make_app("GIMP", "/usr/bin/gimp", AppSource::Desktop),
+            firefox,
+        ];
+        let engine = DiscoveryEngine::new();
+        let results = engine.search_matches("browser", &apps);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].app.name, "Firefox");
+        assert_eq!(results[0].matched_field, SearchField::GenericName);
+        assert_eq!(results[1].app.name, "Thunderbird");
+        assert_eq!(results[1].matched_field, SearchField::Keywords);
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn test_search_uses_configured_weights() {
+        let mut firefox = make_app("Firefox", "/usr/bin/firefox", AppSource::Desktop);
+        firefox.generic_name = Some("Web Browser".to_string());
+        let mut thunderbird = make_app("Thunderbird", "/usr/bin/thunderbird", AppSource::Desktop);
+        thunderbird.keywords = vec!["browser".to_string()];
+        let apps = vec![firefox, thunderbird];
+
+        let mut config = Config::default();
+        config
+            .merge("[search.weights]\nkeywords = 2\n", std::path::Path::new("c.toml"))
+            .unwrap();
+        let engine = DiscoveryEngine::builder()
+            .builtin_providers(false)
+            .plugins(false)
+            .config(config)
+            .build();
+        assert_eq!(engine.search("browser", &apps)[0].name, "Thunderbird");
+    }
+
+    #[test]
+    fn test_history_ranks_launched_apps_first() {
+        let apps = vec![
+            make_app("code", "/usr/bin/code", AppSource::Dpkg),
+            make_app("Code", "/usr/share/code/code", AppSource::Desktop),
+            make_app("codium", "/usr/bin/codium", AppSource::Dpkg),
+        ];
+        let engine = DiscoveryEngine::new();
+        assert_eq!(engine.find_by_name("code", &apps).unwrap().source, AppSource::Dpkg);
+
+        let mut history = LaunchHistory::load(std::path::PathBuf::from("/nonexistent/h.json"));
+        for _ in 0..5 {
+            history.record(&apps[1], SystemTime::now());
+        }
+        let engine = DiscoveryEngine::builder()
+            .builtin_providers(false)
+            .plugins(false)
+            .history(history)
+            .build();
+        assert_eq!(engine.find_by_name("code", &apps).unwrap().source, AppSource::Desktop);
+        assert_eq!(engine.find_by_name("cod", &apps).unwrap().source, AppSource::Desktop);
+        let results = engine.search_matches("code", &apps);
+        assert_eq!(results[0].app.source, AppSource::Desktop);
+        assert!(results[0].score > results[1].score);
+    }
+
     #[test]
     fn test_normalize_exec() {
         assert_eq!(normalize_exec("/usr/bin/firefox"), "/usr/bin/firefox");
@@ -285,5 +917,15 @@ mod tests {
             "/path/with spaces/app"
         );
         assert_eq!(normalize_exec("  /usr/bin/app  "), "/usr/bin/app");
+        assert_eq!(
+            normalize_exec("/usr/bin/flatpak run --branch=stable --command=gimp org.gimp.GIMP @@ %F @@"),
+            "flatpak:org.gimp.gimp"
+        );
+        assert_eq!(normalize_exec("flatpak run org.gimp.GIMP"), "flatpak:org.gimp.gimp");
+        assert_eq!(
+            normalize_exec("env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/firefox_firefox.desktop /snap/bin/firefox %u"),
+            "/snap/bin/firefox"
+        );
+        assert_eq!(normalize_exec("/usr/bin/env -i BAR=1 /usr/bin/app"), "/usr/bin/app");
     }
 }
@@ -0,0 +1,524 @@
+//! The desktop entry `Exec` key: quoting, escaping and field codes.
+//!
+//! An Exec value is first unescaped as a desktop entry string (`\s`, `\n`, `\t`, `\r`,
+//! `\\`), then split into arguments. Arguments containing reserved characters are
+//! enclosed in double quotes, inside which `"`, `` ` ``, `$` and `\` are escaped with a
+//! backslash. Like most launchers we also accept single quotes and backslash escapes
+//! outside quotes, which many entries in the wild rely on.
+//!
+//! Field codes (`%f`, `%U`, `%i`, ...) are kept verbatim by [`split`] and substituted by
+//! [`expand`] when the application is launched.
+
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use crate::app::Application;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ExecError {
+    #[error("empty command")]
+    Empty,
+
+    #[error("unterminated {0} quote")]
+    UnterminatedQuote(char),
+
+    #[error("trailing backslash")]
+    TrailingBackslash,
+}
+
+/// Undo the escapes of a desktop entry string value.
+pub fn unescape_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            // Not a string escape; leave it for the Exec quoting rules
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split an (already unescaped) Exec value into arguments, leaving field codes in place.
+pub fn split(exec: &str) -> Result<Vec<String>, ExecError> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    // Distinguishes an empty quoted argument ("") from no argument at all
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(e @ ('"' | '`' | '$' | '\\')) => current.push(e),
+                            Some(other) => {
+                                current.push('\\');
+                                current.push(other);
+                            }
+                            None => return Err(ExecError::UnterminatedQuote('"')),
+                        },
+                        Some(other) => current.push(other),
+                        None => return Err(ExecError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(other) => current.push(other),
+                        None => return Err(ExecError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(e) => current.push(e),
+                    None => return Err(ExecError::TrailingBackslash),
+                }
+            }
+            other => {
+                in_arg = true;
+                current.push(other);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    if args.is_empty() {
+        return Err(ExecError::Empty);
+    }
+    Ok(args)
+}
+
+/// Values substituted for field codes.
+#[derive(Debug, Clone, Default)]
+pub struct FieldCodes<'a> {
+    /// `%c`: the application's (translated) name.
+    pub name: &'a str,
+    /// `%i`: expands to `--icon <icon>` when set.
+    pub icon: Option<&'a str>,
+    /// `%k`: path of the desktop entry the command came from.
+    pub desktop_file: Option<&'a str>,
+    /// Files and URLs for `%f`, `%F`, `%u` and `%U`.
+    pub targets: &'a [String],
+}
+
+impl<'a> FieldCodes<'a> {
+    pub fn for_app(app: &'a Application) -> Self {
+        Self {
+            name: &app.name,
+            icon: app.icon.as_deref(),
+            desktop_file: app
+                .location
+                .ends_with(".desktop")
+                .then_some(app.location.as_str()),
+            targets: &[],
+        }
+    }
+
+    pub fn with_targets(mut self, targets: &'a [String]) -> Self {
+        self.targets = targets;
+        self
+    }
+}
+
+/// How a command line takes files or URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TargetCode {
+    /// `%f`: one local file per invocation.
+    File,
+    /// `%F`: all local files at once.
+    Files,
+    /// `%u`: one URL per invocation.
+    Url,
+    /// `%U`: all URLs at once.
+    Urls,
+}
+
+impl TargetCode {
+    fn parse(code: char) -> Option<Self> {
+        match code {
+            'f' => Some(TargetCode::File),
+            'F' => Some(TargetCode::Files),
+            'u' => Some(TargetCode::Url),
+            'U' => Some(TargetCode::Urls),
+            _ => None,
+        }
+    }
+
+    fn is_list(self) -> bool {
+        matches!(self, TargetCode::Files | TargetCode::Urls)
+    }
+
+    /// The form a target is passed in: file codes get local paths, URL codes anything.
+    fn convert(self, target: &str) -> String {
+        match self {
+            TargetCode::File | TargetCode::Files => {
+                file_url_to_path(target).unwrap_or_else(|| target.to_string())
+            }
+            TargetCode::Url | TargetCode::Urls => target.to_string(),
+        }
+    }
+}
+
+/// The first file or URL field code in `argv`, if any.
+fn target_code(argv: &[String]) -> Option<TargetCode> {
+    argv.iter().find_map(|arg| {
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c == '%' {
+                match chars.next() {
+                    Some('%') | None => {}
+                    Some(code) => {
+                        if let Some(target) = TargetCode::parse(code) {
+                            return Some(target);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })
+}
+
+/// Whether `argv` has a field code to pass files or URLs through.
+pub fn accepts_targets(argv: &[String]) -> bool {
+    target_code(argv).is_some()
+}
+
+/// Substitute field codes in `argv`, giving the commands to run.
+///
+/// There is one command, unless several targets are given to a `%f` or `%u` command line,
+/// which is then repeated once per target. Deprecated codes are removed, and an argument
+/// consisting of only a field code that expands to nothing is dropped rather than passed
+/// as an empty string. Targets are ignored if `argv` has no file or URL code.
+pub fn expand(argv: &[String], codes: &FieldCodes) -> Vec<Vec<String>> {
+    match target_code(argv) {
+        Some(code) if !code.is_list() && codes.targets.len() > 1 => codes
+            .targets
+            .iter()
+            .map(|target| expand_one(argv, codes, code, std::slice::from_ref(target)))
+            .collect(),
+        Some(code) => vec![expand_one(argv, codes, code, codes.targets)],
+        None => vec![expand_one(argv, codes, TargetCode::Files, &[])],
+    }
+}
+
+fn expand_one(
+    argv: &[String],
+    codes: &FieldCodes,
+    code: TargetCode,
+    targets: &[String],
+) -> Vec<String> {
+    let targets: Vec<String> = targets.iter().map(|t| code.convert(t)).collect();
+    let mut out = Vec::with_capacity(argv.len() + targets.len());
+    for arg in argv {
+        match arg.as_str() {
+            "%i" => {
+                if let Some(icon) = codes.icon {
+                    out.push("--icon".to_string());
+                    out.push(icon.to_string());
+                }
+            }
+            "%f" | "%F" | "%u" | "%U" => out.extend(targets.iter().cloned()),
+            "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+            "%k" if codes.desktop_file.is_none() => {}
+            _ => out.push(expand_inline(arg, codes, targets.first().map(String::as_str))),
+        }
+    }
+    out
+}
+
+/// Expand the field codes embedded in a single argument, e.g. `--class=%c`.
+fn expand_inline(arg: &str, codes: &FieldCodes, target: Option<&str>) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some('c') => out.push_str(codes.name),
+            Some('k') => out.push_str(codes.desktop_file.unwrap_or("")),
+            Some('f' | 'u') => out.push_str(target.unwrap_or("")),
+            // %i and the list codes cannot be part of a larger argument
+            Some(_) => {}
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// Commands that open `targets` with `app`.
+///
+/// Command lines from desktop entries take targets through their field codes; any other
+/// command gets them appended.
+pub fn launch_commands(app: &Application, targets: &[String]) -> Vec<Vec<String>> {
+    if app.exec_args.is_empty() {
+        let mut argv = app.argv();
+        argv.extend(targets.iter().cloned());
+        return vec![argv];
+    }
+    expand(&app.exec_args, &FieldCodes::for_app(app).with_targets(targets))
+}
+
+/// The local path of a `file://` URL.
+fn file_url_to_path(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// Quote `arg` for display if it contains characters a shell would interpret.
+pub fn quote(arg: &str) -> Cow<'_, str> {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    if !arg.is_empty() && !arg.contains(RESERVED) {
+        return Cow::Borrowed(arg);
+    }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Join arguments into a single command line for display.
+pub fn join(argv: &[String]) -> String {
+    argv.iter()
+        .map(|a| quote(a))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unescape_value() {
+        assert_eq!(unescape_value(r"a\sb\tc"), "a b\tc");
+        assert_eq!(unescape_value(r#""\\$HOME""#), r#""\$HOME""#);
+        assert_eq!(unescape_value(r"\x"), r"\x");
+    }
+
+    #[test]
+    fn test_split_plain() {
+        assert_eq!(split("/usr/bin/app --flag %u").unwrap(), args(&["/usr/bin/app", "--flag", "%u"]));
+        assert_eq!(split("  app   a  ").unwrap(), args(&["app", "a"]));
+    }
+
+    #[test]
+    fn test_split_quoted_path() {
+        assert_eq!(
+            split(r#""/opt/My App/app" --flag"#).unwrap(),
+            args(&["/opt/My App/app", "--flag"])
+        );
+        assert_eq!(split(r#"app "" x"#).unwrap(), args(&["app", "", "x"]));
+        assert_eq!(split(r#"app --opt="a b""#).unwrap(), args(&["app", "--opt=a b"]));
+    }
+
+    #[test]
+    fn test_split_escapes_inside_quotes() {
+        assert_eq!(
+            split(r#"sh -c "echo \"\$HOME\" \`x\` \\ \n""#).unwrap(),
+            args(&["sh", "-c", r#"echo "$HOME" `x` \ \n"#])
+        );
+    }
+
+    #[test]
+    fn test_split_env_prefix_and_single_quotes() {
+        assert_eq!(
+            split("env FOO=bar GDK_BACKEND=x11 /usr/bin/app").unwrap(),
+            args(&["env", "FOO=bar", "GDK_BACKEND=x11", "/usr/bin/app"])
+        );
+        assert_eq!(split(r"sh -c 'a  b' x\ y").unwrap(), args(&["sh", "-c", "a  b", "x y"]));
+    }
+
+    #[test]
+    fn test_split_errors() {
+        assert_eq!(split(r#"app "unterminated"#), Err(ExecError::UnterminatedQuote('"')));
+        assert_eq!(split("app 'x"), Err(ExecError::UnterminatedQuote('\'')));
+        assert_eq!(split(r"app \"), Err(ExecError::TrailingBackslash));
+        assert_eq!(split("   "), Err(ExecError::Empty));
+    }
+
+    #[test]
+    fn test_expand_field_codes() {
+        let codes = FieldCodes {
+            name: "My App",
+            icon: Some("myapp"),
+            desktop_file: Some("/usr/share/applications/myapp.desktop"),
+            targets: &[],
+        };
+        let argv = args(&["app", "%i", "--class=%c", "%k", "%U", "--rate=100%%", "%d"]);
+        assert_eq!(
+            expand(&argv, &codes),
+            vec![args(&[
+                "app",
+                "--icon",
+                "myapp",
+                "--class=My App",
+                "/usr/share/applications/myapp.desktop",
+                "--rate=100%",
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_expand_without_icon_or_desktop_file() {
+        let codes = FieldCodes {
+            name: "x",
+            ..Default::default()
+        };
+        assert_eq!(
+            expand(&args(&["app", "%i", "%k", "%f"]), &codes),
+            vec![args(&["app"])]
+        );
+    }
+
+    #[test]
+    fn test_expand_list_codes_take_all_targets() {
+        let targets = args(&["a.pdf", "file:///tmp/My%20Report.pdf"]);
+        let codes = FieldCodes::default().with_targets(&targets);
+        assert_eq!(
+            expand(&args(&["viewer", "%F", "--new"]), &codes),
+            vec![args(&["viewer", "a.pdf", "/tmp/My Report.pdf", "--new"])]
+        );
+        assert_eq!(
+            expand(&args(&["browser", "%U"]), &codes),
+            vec![args(&["browser", "a.pdf", "file:///tmp/My%20Report.pdf"])]
+        );
+    }
+
+    #[test]
+    fn test_expand_single_codes_repeat_the_command() {
+        let targets = args(&["a.txt", "b.txt"]);
+        let codes = FieldCodes::default().with_targets(&targets);
+        assert_eq!(
+            expand(&args(&["editor", "--file=%f"]), &codes),
+            vec![args(&["editor", "--file=a.txt"]), args(&["editor", "--file=b.txt"])]
+        );
+        assert_eq!(
+            expand(&args(&["open", "%u"]), &codes),
+            vec![args(&["open", "a.txt"]), args(&["open", "b.txt"])]
+        );
+    }
+
+    #[test]
+    fn test_expand_ignores_targets_without_codes() {
+        let targets = args(&["a.txt"]);
+        let codes = FieldCodes::default().with_targets(&targets);
+        assert!(!accepts_targets(&args(&["app", "--rate=100%%"])));
+        assert!(accepts_targets(&args(&["app", "--file=%f"])));
+        assert_eq!(expand(&args(&["app"]), &codes), vec![args(&["app"])]);
+    }
+
+    #[test]
+    fn test_file_url_to_path() {
+        assert_eq!(file_url_to_path("file:///tmp/a%20b"), Some("/tmp/a b".to_string()));
+        assert_eq!(file_url_to_path("file://localhost/x"), Some("/x".to_string()));
+        assert_eq!(file_url_to_path("file://host/x"), None);
+        assert_eq!(file_url_to_path("https://example.com"), None);
+        assert_eq!(file_url_to_path("file:///100%"), Some("/100%".to_string()));
+    }
+
+    #[test]
+    fn test_launch_commands_append_for_plain_commands() {
+        let targets = args(&["report.pdf"]);
+        let app = Application {
+            name: "zathura".to_string(),
+            exec_command: "/usr/bin/zathura".to_string(),
+            source: crate::app::AppSource::Dpkg,
+            location: "/usr/bin/zathura".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            launch_commands(&app, &targets),
+            vec![args(&["/usr/bin/zathura", "report.pdf"])]
+        );
+
+        let app = Application {
+            exec_args: args(&["zathura", "%U"]),
+            ..app
+        };
+        assert_eq!(launch_commands(&app, &targets), vec![args(&["zathura", "report.pdf"])]);
+    }
+
+    #[test]
+    fn test_quote_and_join() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("My App"), r#""My App""#);
+        assert_eq!(quote(r#"a"$b"#), r#""a\"\$b""#);
+        assert_eq!(quote(""), r#""""#);
+        let argv = args(&["/opt/My App/app", "--flag"]);
+        assert_eq!(join(&argv), r#""/opt/My App/app" --flag"#);
+        assert_eq!(split(&join(&argv)).unwrap(), argv);
+    }
+}
@@ -0,0 +1,254 @@
+//! Launch history used to rank frequently and recently used applications first.
+//!
+//! Every `appgrep run` adds to a per-application counter in
+//! `$XDG_STATE_HOME/appgrep/history.json`. An application's frecency is its launch
+//! count weighted by how long ago it was last launched, and scales search scores by up
+//! to [`MAX_BOOST`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{AppSource, Application};
+
+/// Bumped whenever the on-disk layout changes.
+const HISTORY_FORMAT: u32 = 1;
+
+/// Largest factor a search score is multiplied by for a frequently used application.
+pub const MAX_BOOST: f64 = 2.0;
+
+/// Frecency at which an application gets the full boost, e.g. five launches this week.
+const FULL_BOOST_FRECENCY: f64 = 500.0;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Launches of one application.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub name: String,
+    pub source: AppSource,
+    pub location: String,
+    pub count: u32,
+    pub last_used_secs: u64,
+}
+
+impl HistoryEntry {
+    fn is_for(&self, app: &Application) -> bool {
+        self.source == app.source && self.location == app.location
+    }
+
+    /// Launch count weighted by the age of the last launch.
+    pub fn frecency(&self, now: SystemTime) -> f64 {
+        let now_secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let age = now_secs.saturating_sub(self.last_used_secs);
+        let weight = match age {
+            a if a < 4 * DAY => 100.0,
+            a if a < 14 * DAY => 70.0,
+            a if a < 31 * DAY => 50.0,
+            a if a < 90 * DAY => 30.0,
+            _ => 10.0,
+        };
+        self.count as f64 * weight
+    }
+
+    /// Time since the last launch.
+    pub fn age(&self, now: SystemTime) -> Duration {
+        let last = UNIX_EPOCH + Duration::from_secs(self.last_used_secs);
+        now.duration_since(last).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    format: u32,
+    entries: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LaunchHistory {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl LaunchHistory {
+    /// `$XDG_STATE_HOME/appgrep/history.json` (default `~/.local/state/appgrep/history.json`).
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::state_dir().map(|d| d.join("appgrep").join("history.json"))
+    }
+
+    /// Read the history at `path`; a missing, unreadable or outdated file is empty.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<HistoryFile>(&content).ok())
+            .filter(|file| file.format == HISTORY_FORMAT)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries, most frecent first.
+    pub fn entries(&self, now: SystemTime) -> Vec<&HistoryEntry> {
+        let mut entries: Vec<&HistoryEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+        entries
+    }
+
+    pub fn entry(&self, app: &Application) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.is_for(app))
+    }
+
+    /// Count a launch of `app` at `now`.
+    pub fn record(&mut self, app: &Application, now: SystemTime) {
+        let now_secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        match self.entries.iter_mut().find(|e| e.is_for(app)) {
+            Some(entry) => {
+                entry.name = app.name.clone();
+                entry.count = entry.count.saturating_add(1);
+                entry.last_used_secs = now_secs;
+            }
+            None => self.entries.push(HistoryEntry {
+                name: app.name.clone(),
+                source: app.source.clone(),
+                location: app.location.clone(),
+                count: 1,
+                last_used_secs: now_secs,
+            }),
+        }
+    }
+
+    /// Factor between 1 and [`MAX_BOOST`] to scale `app`'s search score by.
+    pub fn boost(&self, app: &Application, now: SystemTime) -> f64 {
+        let frecency = self.entry(app).map_or(0.0, |e| e.frecency(now));
+        1.0 + (MAX_BOOST - 1.0) * (frecency / FULL_BOOST_FRECENCY).min(1.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Drop entries for applications that were uninstalled, i.e. whose location is a
+    /// path that no longer exists. Locations that are not paths, like flatpak app IDs,
+    /// are kept.
+    pub fn prune_missing(&mut self) {
+        self.entries.retain(|entry| {
+            let location = Path::new(&entry.location);
+            !location.is_absolute() || location.exists()
+        });
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = HistoryFile {
+            format: HISTORY_FORMAT,
+            entries: self.entries.clone(),
+        };
+        let tmp = self
+            .path
+            .with_extension(format!("json.{}.tmp", std::process::id()));
+        let json = serde_json::to_string_pretty(&file).map_err(std::io::Error::other)?;
+        fs::write(&tmp, json)?;
+        // Rename so a concurrent `run` never reads a partially written file
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_app(name: &str, location: &str) -> Application {
+        Application {
+            name: name.to_string(),
+            exec_command: location.to_string(),
+            source: AppSource::Desktop,
+            location: location.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn at_day(day: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000 * DAY + day * DAY)
+    }
+
+    #[test]
+    fn test_record_and_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("state/history.json");
+        let firefox = make_app("Firefox", "/usr/share/applications/firefox.desktop");
+
+        let mut history = LaunchHistory::load(path.clone());
+        assert!(history.entries(at_day(0)).is_empty());
+        history.record(&firefox, at_day(0));
+        history.record(&firefox, at_day(1));
+        history.save().unwrap();
+
+        let history = LaunchHistory::load(path);
+        let entry = history.entry(&firefox).unwrap();
+        assert_eq!(entry.count, 2);
+        assert_eq!(entry.age(at_day(3)), Duration::from_secs(2 * DAY));
+    }
+
+    #[test]
+    fn test_frecency_decays_with_age() {
+        let mut history = LaunchHistory::load(PathBuf::from("/nonexistent/history.json"));
+        let app = make_app("Firefox", "/usr/bin/firefox");
+        for _ in 0..3 {
+            history.record(&app, at_day(0));
+        }
+        let entry = history.entry(&app).unwrap();
+        assert_eq!(entry.frecency(at_day(1)), 300.0);
+        assert_eq!(entry.frecency(at_day(20)), 150.0);
+        assert_eq!(entry.frecency(at_day(365)), 30.0);
+    }
+
+    #[test]
+    fn test_boost_is_bounded() {
+        let mut history = LaunchHistory::load(PathBuf::from("/nonexistent/history.json"));
+        let used = make_app("Firefox", "/usr/bin/firefox");
+        let unused = make_app("Falkon", "/usr/bin/falkon");
+        assert_eq!(history.boost(&used, at_day(0)), 1.0);
+        for _ in 0..50 {
+            history.record(&used, at_day(0));
+        }
+        assert_eq!(history.boost(&used, at_day(0)), MAX_BOOST);
+        assert_eq!(history.boost(&unused, at_day(0)), 1.0);
+    }
+
+    #[test]
+    fn test_prune_missing() {
+        let tmp = TempDir::new().unwrap();
+        let entry = tmp.path().join("kept.desktop");
+        fs::write(&entry, "").unwrap();
+        let kept = make_app("Kept", entry.to_str().unwrap());
+        let removed = make_app("Removed", tmp.path().join("gone.desktop").to_str().unwrap());
+        let mut flatpak = make_app("Flatpak", "org.mozilla.firefox");
+        flatpak.source = AppSource::Flatpak;
+
+        let mut history = LaunchHistory::load(tmp.path().join("history.json"));
+        for app in [&kept, &removed, &flatpak] {
+            history.record(app, at_day(0));
+        }
+        history.prune_missing();
+        assert!(history.entry(&kept).is_some());
+        assert!(history.entry(&removed).is_none());
+        assert!(history.entry(&flatpak).is_some());
+    }
+
+    #[test]
+    fn test_corrupt_file_is_empty() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("history.json");
+        fs::write(&path, "not json").unwrap();
+        assert!(LaunchHistory::load(path).entries(at_day(0)).is_empty());
+    }
+}
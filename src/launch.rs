@@ -0,0 +1,408 @@
+//! Starting applications for `appgrep run`.
+
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, IsTerminal};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::app::Application;
+use crate::exec;
+use crate::provider::on_path;
+
+#[derive(Error, Debug)]
+pub enum LaunchError {
+    #[error("'{0}' has an empty exec command")]
+    EmptyCommand(String),
+
+    #[error(
+        "'{0}' runs in a terminal, but no terminal emulator was found; set run.terminal in \
+         the config or $TERMINAL, or pass --no-terminal"
+    )]
+    NoTerminal(String),
+
+    #[error("'{0}' has no action '{1}' (available: {2})")]
+    UnknownAction(String, String, String),
+}
+
+/// How `run` starts applications.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    /// Start terminal applications like graphical ones.
+    pub no_terminal: bool,
+    /// Terminal emulator command line to use instead of detecting one.
+    pub terminal: Option<Vec<String>>,
+    /// Run in the foreground and wait for the application to exit.
+    pub wait: bool,
+    /// Place each launch in a transient systemd scope when a user manager is running.
+    pub scope: bool,
+}
+
+/// A command ready to start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub argv: Vec<String>,
+    /// Run in the foreground on the current terminal rather than detached.
+    pub attach: bool,
+}
+
+impl Launch {
+    /// Start the command. Attached commands are waited for and their status returned.
+    ///
+    /// Detached commands run in a new session as an orphan of a double fork, so closing
+    /// the terminal appgrep ran in does not take them down and init reaps them.
+    pub fn spawn(&self) -> io::Result<Option<ExitStatus>> {
+        let mut cmd = Command::new(&self.argv[0]);
+        cmd.args(&self.argv[1..]);
+        if self.attach {
+            return cmd.status().map(Some);
+        }
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // SAFETY: only async-signal-safe calls between fork and exec. The intermediate
+        // child exits at once; exec errors still reach us through the grandchild's copy
+        // of the standard library's error pipe.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                match libc::fork() {
+                    -1 => Err(io::Error::last_os_error()),
+                    0 => Ok(()),
+                    _ => libc::_exit(0),
+                }
+            });
+        }
+        // Reap the intermediate child; the application itself is reparented.
+        cmd.spawn()?.wait()?;
+        Ok(None)
+    }
+}
+
+/// The commands that start `app` with `targets`.
+///
+/// Terminal applications take over the current terminal when there is one, and are
+/// otherwise wrapped in a terminal emulator. With `options.scope`, each command runs in
+/// its own `app-appgrep-<id>-<random>.scope` via `systemd-run` when that is possible.
+pub fn plan(
+    app: &Application,
+    targets: &[String],
+    options: &LaunchOptions,
+) -> Result<Vec<Launch>, LaunchError> {
+    let commands = exec::launch_commands(app, targets);
+    if commands.iter().any(|argv| argv.is_empty()) {
+        return Err(LaunchError::EmptyCommand(app.name.clone()));
+    }
+
+    let mut launches: Vec<Launch> = if !app.terminal || options.no_terminal {
+        commands
+            .into_iter()
+            .map(|argv| Launch {
+                argv,
+                attach: options.wait,
+            })
+            .collect()
+    } else if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        commands
+            .into_iter()
+            .map(|argv| Launch { argv, attach: true })
+            .collect()
+    } else {
+        let terminal = find_terminal(options.terminal.as_deref())
+            .ok_or_else(|| LaunchError::NoTerminal(app.name.clone()))?;
+        commands
+            .into_iter()
+            .map(|argv| Launch {
+                argv: terminal.iter().cloned().chain(argv).collect(),
+                attach: options.wait,
+            })
+            .collect()
+    };
+
+    if options.scope && scope_available() {
+        let id = app_id(app);
+        for (i, launch) in launches.iter_mut().enumerate() {
+            let unit = scope_unit(&id, &format!("{}{}", random_suffix(), i));
+            launch.argv = scope_command(&unit)
+                .into_iter()
+                .chain(launch.argv.drain(..))
+                .collect();
+        }
+    }
+    Ok(launches)
+}
+
+/// `app` with its command replaced by that of the desktop action `id`.
+pub fn action_app(app: &Application, id: &str) -> Result<Application, LaunchError> {
+    let action = app.action(id).ok_or_else(|| {
+        let available = if app.actions.is_empty() {
+            "none".to_string()
+        } else {
+            app.actions
+                .iter()
+                .map(|a| a.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        LaunchError::UnknownAction(app.name.clone(), id.to_string(), available)
+    })?;
+    Ok(Application {
+        exec_command: exec::join(&action.exec_args),
+        exec_args: action.exec_args.clone(),
+        ..app.clone()
+    })
+}
+
+/// Whether `systemd-run --user` can start scopes: it is installed and the user's
+/// service manager is reachable over the session bus.
+pub fn scope_available() -> bool {
+    systemd_user_manager(
+        env::var_os("XDG_RUNTIME_DIR").as_deref().map(Path::new),
+        &env::var_os("PATH").unwrap_or_default(),
+    )
+}
+
+fn systemd_user_manager(runtime_dir: Option<&Path>, path: &OsStr) -> bool {
+    runtime_dir.is_some_and(|dir| dir.join("bus").exists()) && on_path("systemd-run", path)
+}
+
+/// The command line that runs a command in the transient scope `unit`.
+fn scope_command(unit: &str) -> Vec<String> {
+    [
+        "systemd-run",
+        "--user",
+        "--scope",
+        "--quiet",
+        "--collect",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .chain([format!("--unit={}", unit), "--".to_string()])
+    .collect()
+}
+
+/// The application ID used in scope names: the desktop file ID without its extension,
+/// or the application name for other sources.
+fn app_id(app: &Application) -> String {
+    app.metadata
+        .get("desktop_id")
+        .map(String::as_str)
+        .or_else(|| Path::new(&app.location).file_name().and_then(|n| n.to_str()))
+        .and_then(|id| id.strip_suffix(".desktop"))
+        .map(str::to_string)
+        .unwrap_or_else(|| app.name.clone())
+}
+
+/// A scope name following the XDG systemd integration guidelines,
+/// `app-<launcher>-<ApplicationID>-<RANDOM>.scope`.
+fn scope_unit(app_id: &str, random: &str) -> String {
+    format!("app-appgrep-{}-{}.scope", escape_unit_name(app_id), random)
+}
+
+fn random_suffix() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    format!("{:x}{:x}", std::process::id(), nanos)
+}
+
+/// Escape a string for use in a unit name like `systemd-escape` does, including
+/// dashes, which separate the parts of the name.
+fn escape_unit_name(s: &str) -> String {
+    let mut escaped = String::new();
+    for (i, byte) in s.bytes().enumerate() {
+        let plain = byte.is_ascii_alphanumeric() || byte == b':' || byte == b'_' || byte == b'.';
+        if plain && !(i == 0 && byte == b'.') {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+/// The terminal emulator command line that a command to run is appended to.
+///
+/// In order: the configured one, `$TERMINAL -e`, `x-terminal-emulator -e`, and
+/// `xdg-terminal-exec`.
+pub fn find_terminal(configured: Option<&[String]>) -> Option<Vec<String>> {
+    detect_terminal(
+        configured,
+        env::var("TERMINAL").ok().as_deref(),
+        &env::var_os("PATH").unwrap_or_default(),
+    )
+}
+
+fn detect_terminal(
+    configured: Option<&[String]>,
+    terminal_env: Option<&str>,
+    path: &OsStr,
+) -> Option<Vec<String>> {
+    if let Some(terminal) = configured {
+        return Some(terminal.to_vec());
+    }
+    if let Some(terminal) = terminal_env.filter(|t| !t.is_empty()) {
+        return Some(vec![terminal.to_string(), "-e".to_string()]);
+    }
+    if on_path("x-terminal-emulator", path) {
+        return Some(vec!["x-terminal-emulator".to_string(), "-e".to_string()]);
+    }
+    if on_path("xdg-terminal-exec", path) {
+        return Some(vec!["xdg-terminal-exec".to_string()]);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn write_executable(dir: &Path, name: &str) {
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_detect_terminal_order() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().as_os_str();
+        assert_eq!(detect_terminal(None, None, path), None);
+
+        write_executable(tmp.path(), "xdg-terminal-exec");
+        assert_eq!(detect_terminal(None, None, path), Some(args(&["xdg-terminal-exec"])));
+
+        write_executable(tmp.path(), "x-terminal-emulator");
+        assert_eq!(
+            detect_terminal(None, None, path),
+            Some(args(&["x-terminal-emulator", "-e"]))
+        );
+        assert_eq!(
+            detect_terminal(None, Some("alacritty"), path),
+            Some(args(&["alacritty", "-e"]))
+        );
+
+        let configured = args(&["gnome-terminal", "--"]);
+        assert_eq!(
+            detect_terminal(Some(&configured), Some("alacritty"), path),
+            Some(configured.clone())
+        );
+    }
+
+    /// An application from `/usr/share/applications` named `name`, running `exec`.
+    fn desktop_app(name: &str, exec: &[&str]) -> Application {
+        Application {
+            name: name.to_string(),
+            exec_command: exec[0].to_string(),
+            location: format!("/usr/share/applications/{}.desktop", exec[0]),
+            exec_args: args(exec),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_graphical_and_no_terminal() {
+        let mut app = desktop_app("htop", &["htop"]);
+        let options = LaunchOptions::default();
+        let launches = plan(&app, &[], &options).unwrap();
+        assert_eq!(launches, vec![Launch { argv: args(&["htop"]), attach: false }]);
+
+        app.terminal = true;
+        let options = LaunchOptions {
+            no_terminal: true,
+            ..Default::default()
+        };
+        assert!(!plan(&app, &[], &options).unwrap()[0].attach);
+    }
+
+    #[test]
+    fn test_plan_wait_attaches() {
+        let app = desktop_app("Firefox", &["firefox", "%u"]);
+        let options = LaunchOptions {
+            wait: true,
+            ..Default::default()
+        };
+        let launches = plan(&app, &args(&["a", "b"]), &options).unwrap();
+        assert_eq!(launches.len(), 2);
+        assert!(launches.iter().all(|l| l.attach));
+        assert_eq!(app_id(&app), "firefox");
+        let mut app = app;
+        app.metadata
+            .insert("desktop_id".to_string(), "kde4-okular.desktop".to_string());
+        assert_eq!(app_id(&app), "kde4-okular");
+    }
+
+    #[test]
+    fn test_action_app() {
+        let mut app = desktop_app("Firefox", &["firefox", "%u"]);
+        let err = action_app(&app, "new-window").unwrap_err();
+        assert_eq!(err.to_string(), "'Firefox' has no action 'new-window' (available: none)");
+
+        app.actions.push(crate::app::DesktopAction {
+            id: "new-window".to_string(),
+            name: "New Window".to_string(),
+            icon: None,
+            exec_args: args(&["firefox", "--new-window", "%u"]),
+        });
+        let action = action_app(&app, "new-window").unwrap();
+        assert_eq!(action.exec_args, args(&["firefox", "--new-window", "%u"]));
+        assert_eq!(action.location, app.location);
+        assert!(action_app(&app, "quit")
+            .unwrap_err()
+            .to_string()
+            .contains("available: new-window"));
+    }
+
+    #[test]
+    fn test_scope_unit_name() {
+        assert_eq!(
+            scope_unit("org.gnome.Terminal", "1a2b"),
+            "app-appgrep-org.gnome.Terminal-1a2b.scope"
+        );
+        assert_eq!(escape_unit_name("my-app"), "my\\x2dapp");
+        assert_eq!(escape_unit_name("Some App"), "Some\\x20App");
+        assert_eq!(escape_unit_name(".hidden"), "\\x2ehidden");
+    }
+
+    #[test]
+    fn test_systemd_user_manager_needs_bus_and_systemd_run() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().as_os_str();
+        write_executable(tmp.path(), "systemd-run");
+        assert!(!systemd_user_manager(None, path));
+        assert!(!systemd_user_manager(Some(tmp.path()), path));
+
+        fs::write(tmp.path().join("bus"), "").unwrap();
+        assert!(systemd_user_manager(Some(tmp.path()), path));
+        assert!(!systemd_user_manager(Some(tmp.path()), OsStr::new("")));
+    }
+
+    #[test]
+    fn test_spawn_detached_reports_exec_errors() {
+        let launch = Launch {
+            argv: args(&["/nonexistent/appgrep-test-binary"]),
+            attach: false,
+        };
+        assert!(launch.spawn().is_err());
+        let launch = Launch {
+            argv: args(&["true"]),
+            attach: false,
+        };
+        assert!(launch.spawn().unwrap().is_none());
+    }
+}
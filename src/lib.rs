@@ -0,0 +1,38 @@
+//! Discover installed applications on Linux from desktop entries, app stores, language
+//! package managers and system packages.
+//!
+//! ```no_run
+//! use appgrep::{DiscoveryEngine, Formatter, OutputFormat};
+//!
+//! let engine = DiscoveryEngine::builder().build();
+//! let apps = engine.discover_all();
+//! let matches = engine.search("fire", &apps);
+//! Formatter::new(OutputFormat::Names, true)
+//!     .format_list(&matches, &mut std::io::stdout())
+//!     .unwrap();
+//! ```
+//!
+//! Providers are compiled in through cargo features named after their source (`desktop`,
+//! `flatpak`, `dpkg`, ...), all enabled by default, plus `plugins` for `providers.d`
+//! executables. Custom providers implement [`AppProvider`] and are added with
+//! [`DiscoveryEngineBuilder::provider`].
+
+pub mod app;
+pub mod cache;
+pub mod config;
+pub mod engine;
+pub mod error;
+pub mod exec;
+pub mod history;
+pub mod launch;
+pub mod output;
+pub mod provider;
+pub mod sandbox;
+pub mod search;
+
+pub use app::{AppSource, Application, DesktopAction};
+pub use engine::{DiscoveryEngine, DiscoveryEngineBuilder, ProviderWarning, WarningKind};
+pub use output::{Formatter, OutputFormat};
+pub use provider::{AppProvider, BinaryMode, ProviderError};
+pub use sandbox::{PermissionFilter, SandboxPermissions};
+pub use search::{SearchField, SearchMatch};
@@ -1,9 +1,4 @@
-mod app;
 mod cli;
-mod engine;
-mod error;
-mod output;
-mod provider;
 
 use std::collections::HashMap;
 
@@ -11,12 +6,17 @@ use anyhow::Result;
 use clap::{CommandFactory, Parser};
 use clap_complete::generate;
 
-use app::AppSource;
-use cli::{Cli, Command};
-use engine::DiscoveryEngine;
-use output::{Formatter, OutputFormat};
+use appgrep::cache::{CacheStatus, DiscoveryCache};
+use appgrep::config::Config;
+use appgrep::exec;
+use appgrep::history::LaunchHistory;
+use appgrep::launch::{self, LaunchOptions};
+use appgrep::{
+    AppSource, Application, BinaryMode, DiscoveryEngine, Formatter, OutputFormat, ProviderWarning,
+};
+use cli::{Cli, Command, ConfigAction, HistoryAction};
 
-fn print_stats(apps: &[app::Application], format: OutputFormat, w: &mut dyn std::io::Write) -> Result<()> {
+fn print_stats(apps: &[Application], format: OutputFormat, w: &mut dyn std::io::Write) -> Result<()> {
     let mut counts: HashMap<AppSource, usize> = HashMap::new();
     for app in apps {
         *counts.entry(app.source.clone()).or_insert(0) += 1;
@@ -31,18 +31,14 @@ fn print_stats(apps: &[app::Application], format: OutputFormat, w: &mut dyn std:
         let json = serde_json::to_string_pretty(&stats_obj)?;
         writeln!(w, "{}", json)?;
     } else {
-        let sources = [
-            AppSource::Desktop,
-            AppSource::Flatpak,
-            AppSource::Snap,
-            AppSource::Standalone,
-            AppSource::Cargo,
-            AppSource::Npm,
-            AppSource::Dpkg,
-            AppSource::Rpm,
-            AppSource::Pacman,
-            AppSource::Brew,
-        ];
+        let mut sources = AppSource::ALL.to_vec();
+        let mut custom: Vec<AppSource> = counts
+            .keys()
+            .filter(|s| matches!(s, AppSource::Custom(_)))
+            .cloned()
+            .collect();
+        custom.sort_by_key(|s| s.to_string());
+        sources.extend(custom);
         let parts: Vec<String> = sources
             .iter()
             .map(|s| format!("{} {}", counts.get(s).unwrap_or(&0), s))
@@ -54,79 +50,380 @@ fn print_stats(apps: &[app::Application], format: OutputFormat, w: &mut dyn std:
     Ok(())
 }
 
+/// Discover applications, reporting providers that failed or timed out on stderr unless
+/// `json_output` is set, in which case the JSON document printed carries them instead.
+fn discover(
+    engine: &DiscoveryEngine,
+    sources: &[AppSource],
+    json_output: bool,
+) -> Vec<Application> {
+    let apps = if sources.is_empty() {
+        engine.discover_all()
+    } else {
+        engine.discover_filtered(sources)
+    };
+    if !json_output {
+        print_warnings(&engine.warnings());
+    }
+    apps
+}
+
+/// Report providers whose results are missing.
+fn print_warnings(warnings: &[ProviderWarning]) {
+    for warning in warnings {
+        eprintln!("appgrep: warning: {}", warning);
+    }
+}
+
+/// Print the effective configuration with the origin of each value.
+fn print_config(config: &Config) {
+    println!("Config files:");
+    for path in Config::search_paths() {
+        let state = if config.files.contains(&path) {
+            "loaded"
+        } else {
+            "not found"
+        };
+        println!("  {:<48} {}", path.display(), state);
+    }
+
+    println!("\nstandalone.scan_dirs:");
+    #[cfg(feature = "standalone")]
+    for dir in appgrep::provider::standalone::StandaloneProvider::default_scan_dirs() {
+        println!("  {:<48} default", dir.display());
+    }
+    for dir in &config.standalone_dirs {
+        println!("  {:<48} {}", dir.value.display(), dir.origin);
+    }
+
+    println!("\ndesktop.app_dirs:");
+    #[cfg(feature = "desktop")]
+    for dir in appgrep::provider::desktop::DesktopProvider::default_app_dirs() {
+        println!("  {:<48} default", dir.display());
+    }
+    for dir in &config.desktop_dirs {
+        println!("  {:<48} {}", dir.value.display(), dir.origin);
+    }
+
+    println!("\nsources.priority ({}):", config.priority.origin);
+    let order: Vec<String> = config.priority.value.iter().map(|s| s.to_string()).collect();
+    println!("  {}", order.join(" "));
+
+    println!("\nsources.disabled:");
+    if config.disabled.is_empty() {
+        println!("  (none)");
+    }
+    for name in &config.disabled {
+        println!("  {:<48} {}", name.value, name.origin);
+    }
+
+    println!("\nplugins:");
+    #[cfg(feature = "plugins")]
+    {
+        use appgrep::provider::plugin::PluginProvider;
+        use appgrep::AppProvider;
+
+        let plugins = PluginProvider::load_all(&PluginProvider::plugin_dirs());
+        if plugins.is_empty() {
+            println!("  (none)");
+        }
+        for plugin in &plugins {
+            println!("  {:<48} {}", plugin.name(), plugin.path().display());
+        }
+    }
+    #[cfg(not(feature = "plugins"))]
+    println!("  (not compiled in)");
+
+    println!("\ntimeouts:");
+    println!(
+        "  {:<48} {}",
+        format!("default {:?}", config.timeouts.default.value),
+        config.timeouts.default.origin
+    );
+    for (name, timeout) in &config.timeouts.providers {
+        println!(
+            "  {:<48} {}",
+            format!("{} {:?}", name, timeout.value),
+            timeout.origin
+        );
+    }
+
+    println!("\nrun.terminal:");
+    match &config.terminal {
+        Some(terminal) => {
+            println!("  {:<48} {}", exec::join(&terminal.value), terminal.origin)
+        }
+        None => println!("  (detect)"),
+    }
+    println!("\nrun.scope:");
+    println!("  {:<48} {}", config.scope.value, config.scope.origin);
+
+    println!("\nsearch.weights:");
+    for (field, weight) in &config.search_weights {
+        println!(
+            "  {:<48} {}",
+            format!("{} {}", field, weight.value),
+            weight.origin
+        );
+    }
+
+    println!("\nignore:");
+    if config.ignore.is_empty() {
+        println!("  (none)");
+    }
+    for pattern in &config.ignore.names {
+        println!("  {:<48} {}", format!("name {}", pattern.value), pattern.origin);
+    }
+    for pattern in &config.ignore.exec {
+        println!("  {:<48} {}", format!("exec {}", pattern.value), pattern.origin);
+    }
+    for source in &config.ignore.sources {
+        println!("  {:<48} {}", format!("source {}", source.value), source.origin);
+    }
+}
+
+/// Render a duration as a short human-readable string such as `3m`.
+fn format_duration(age: std::time::Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}d", secs / 86400)
+    }
+}
+
+/// Print recorded launches, most frecent first.
+fn print_history(history: &LaunchHistory, format: OutputFormat) -> Result<()> {
+    let now = std::time::SystemTime::now();
+    let entries = history.entries(now);
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("No launches recorded in {}", history.path().display());
+    }
+    for entry in entries {
+        println!(
+            "{:<32} {:<10} {:>5} launches   last {} ago",
+            entry.name,
+            entry.source,
+            entry.count,
+            format_duration(entry.age(now))
+        );
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let engine = DiscoveryEngine::new();
-    let formatter = Formatter::new(cli.format, cli.no_color);
+
+    // Only the commands that use the config load it, so a broken config file cannot
+    // get in the way of the others
+    match &cli.command {
+        Command::History { action } => {
+            let history = match LaunchHistory::default_path() {
+                Some(path) => LaunchHistory::load(path),
+                None => {
+                    eprintln!("appgrep: no state directory for the launch history");
+                    std::process::exit(1);
+                }
+            };
+            match action.as_ref().unwrap_or(&HistoryAction::Show) {
+                HistoryAction::Show => print_history(&history, cli.format)?,
+                HistoryAction::Clear => {
+                    let mut history = history;
+                    history.clear();
+                    history.save()?;
+                    eprintln!("Cleared launch history in {}", history.path().display());
+                }
+            }
+        }
+        Command::Config { action } => match action {
+            ConfigAction::Show => print_config(&Config::load()?),
+        },
+        Command::Completions { shell } => {
+            let mut cmd = Cli::command();
+            generate(*shell, &mut cmd, "appgrep", &mut std::io::stdout());
+        }
+        _ => return run_discovery(cli),
+    }
+
+    Ok(())
+}
+
+fn run_discovery(cli: Cli) -> Result<()> {
+    let config = Config::load()?;
+
+    // Lookups by command name need every packaged binary, not just one per package
+    let binary_mode = if cli.all_binaries
+        || matches!(cli.command, Command::Has { .. } | Command::Path { .. })
+    {
+        BinaryMode::All
+    } else {
+        BinaryMode::Primary
+    };
+    let mut builder = DiscoveryEngine::builder()
+        .config(config.clone())
+        .binary_mode(binary_mode)
+        .show_hidden(cli.show_hidden);
+    if let Some(timeout) = cli.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(locale) = &cli.locale {
+        builder = builder.locale(locale);
+    }
+    if !cli.no_cache {
+        if let Some(dir) = DiscoveryCache::default_dir() {
+            builder = builder.cache(DiscoveryCache::new(dir).refresh(cli.refresh));
+        }
+    }
+    let history = if cli.no_history {
+        None
+    } else {
+        LaunchHistory::default_path().map(LaunchHistory::load)
+    };
+    if let Some(history) = &history {
+        builder = builder.history(history.clone());
+    }
+    let engine = builder.build();
+
+    // Plugin sources only exist while the plugin is installed
+    for source in &cli.source {
+        if let AppSource::Custom(name) = source {
+            if !engine.providers().iter().any(|p| p.name() == name) {
+                eprintln!(
+                    "error: invalid value '{}' for '--source <SOURCE>': no built-in source or installed plugin has this name",
+                    name
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+    let json_output = cli.format == OutputFormat::Json;
+    let formatter = || Formatter::new(cli.format, cli.no_color).with_warnings(engine.warnings());
 
     match cli.command {
         Command::List => {
-            let apps = if cli.source.is_empty() {
-                engine.discover_all()
-            } else {
-                engine.discover_filtered(&cli.source)
-            };
-            formatter.format_list(&apps, &mut std::io::stdout())?;
+            let mut apps = discover(&engine, &cli.source, json_output);
+            apps.retain(|app| cli.filter.iter().all(|f| f.matches(app)));
+            formatter().format_list(&apps, &mut std::io::stdout())?;
             if cli.stats {
                 print_stats(&apps, cli.format, &mut std::io::stderr())?;
             }
         }
         Command::Info { name } => {
-            let apps = engine.discover_all();
+            let apps = discover(&engine, &[], json_output);
             match engine.find_by_name(&name, &apps) {
                 Some(app) => {
-                    formatter.format_info(&app, &mut std::io::stdout())?;
+                    formatter().format_info(&app, &mut std::io::stdout())?;
                 }
                 None => {
+                    // No JSON document is printed to carry the warnings
+                    if json_output {
+                        print_warnings(&engine.warnings());
+                    }
                     eprintln!("Application '{}' not found", name);
                     std::process::exit(1);
                 }
             }
         }
         Command::Search { query } => {
-            let apps = engine.discover_all();
-            let results = engine.search(&query, &apps);
-            formatter.format_list(&results, &mut std::io::stdout())?;
+            let mut apps = discover(&engine, &[], json_output);
+            apps.retain(|app| cli.filter.iter().all(|f| f.matches(app)));
+            let matches = engine.search_matches(&query, &apps);
+            formatter().format_search(&matches, &mut std::io::stdout())?;
             if cli.stats {
+                let results: Vec<Application> = matches.into_iter().map(|m| m.app).collect();
                 print_stats(&results, cli.format, &mut std::io::stderr())?;
             }
         }
         Command::Has { name } => {
-            let apps = engine.discover_all();
+            let apps = discover(&engine, &[], json_output);
             match engine.find_by_name(&name, &apps) {
                 Some(app) => {
-                    formatter.format_has(&app, true, &mut std::io::stdout())?;
+                    formatter().format_has(&app, true, &mut std::io::stdout())?;
                     std::process::exit(0);
                 }
                 None => {
-                    formatter.format_has_not_found(&name, &mut std::io::stdout())?;
+                    formatter().format_has_not_found(&name, &mut std::io::stdout())?;
                     std::process::exit(1);
                 }
             }
         }
-        Command::Run { name } => {
-            let apps = engine.discover_all();
+        Command::Run {
+            name,
+            no_terminal,
+            wait,
+            action,
+            targets,
+        } => {
+            let apps = discover(&engine, &[], false);
             match engine.find_by_name(&name, &apps) {
                 Some(app) => {
-                    eprintln!("Running: {}", app.exec_command);
-                    let parts: Vec<&str> = app.exec_command.split_whitespace().collect();
-                    if parts.is_empty() {
-                        eprintln!("Empty exec command");
-                        std::process::exit(1);
+                    let launched = app.clone();
+                    let app = match action {
+                        Some(id) => match launch::action_app(&app, &id) {
+                            Ok(app) => app,
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                std::process::exit(1);
+                            }
+                        },
+                        None => app,
+                    };
+                    if !targets.is_empty()
+                        && !app.exec_args.is_empty()
+                        && !exec::accepts_targets(&app.exec_args)
+                    {
+                        eprintln!(
+                            "appgrep: warning: '{}' does not take files or URLs; ignoring them",
+                            app.name
+                        );
                     }
-                    let mut cmd = std::process::Command::new(parts[0]);
-                    if parts.len() > 1 {
-                        cmd.args(&parts[1..]);
-                    }
-                    cmd.stdin(std::process::Stdio::null())
-                        .stdout(std::process::Stdio::null())
-                        .stderr(std::process::Stdio::null());
-                    match cmd.spawn() {
-                        Ok(_) => {}
+                    let options = LaunchOptions {
+                        no_terminal,
+                        terminal: config.terminal.as_ref().map(|t| t.value.clone()),
+                        wait,
+                        scope: config.scope.value,
+                    };
+                    let launches = match launch::plan(&app, &targets, &options) {
+                        Ok(launches) => launches,
                         Err(e) => {
-                            eprintln!("Failed to launch '{}': {}", app.name, e);
+                            eprintln!("{}", e);
                             std::process::exit(1);
                         }
+                    };
+                    // Only launches that started count towards the history
+                    let mut history = history.as_ref();
+                    for launch in launches {
+                        eprintln!("Running: {}", exec::join(&launch.argv));
+                        let result = launch.spawn();
+                        if let Some(loaded) = history.filter(|_| result.is_ok()) {
+                            // Reload so launches recorded since startup are kept
+                            let mut loaded = LaunchHistory::load(loaded.path().to_path_buf());
+                            loaded.record(&launched, std::time::SystemTime::now());
+                            loaded.prune_missing();
+                            if let Err(e) = loaded.save() {
+                                eprintln!("appgrep: warning: could not record launch history: {}", e);
+                            }
+                            // Once per run, however many files were opened
+                            history = None;
+                        }
+                        match result {
+                            Ok(Some(status)) if !status.success() => {
+                                std::process::exit(status.code().unwrap_or(1));
+                            }
+                            Ok(_) => {}
+                            Err(e) => {
+                                eprintln!("Failed to launch '{}': {}", app.name, e);
+                                std::process::exit(1);
+                            }
+                        }
                     }
                 }
                 None => {
@@ -136,7 +433,7 @@ fn main() -> Result<()> {
             }
         }
         Command::Path { name } => {
-            let apps = engine.discover_all();
+            let apps = discover(&engine, &[], false);
             match engine.find_by_name(&name, &apps) {
                 Some(app) => {
                     println!("{}", app.exec_command);
@@ -153,9 +450,14 @@ fn main() -> Result<()> {
 
             let mut total = 0;
             for provider in engine.providers() {
-                if provider.is_available() {
-                    match provider.discover() {
-                        Ok(apps) => {
+                if engine.is_disabled(provider.name()) {
+                    println!(
+                        "  \u{2717} {:<14} disabled in config",
+                        provider.name()
+                    );
+                } else {
+                    match engine.discover_provider(provider) {
+                        Ok(Some(apps)) => {
                             let count = apps.len();
                             total += count;
                             let preview: Vec<&str> = apps
@@ -175,6 +477,12 @@ fn main() -> Result<()> {
                                 preview_str
                             );
                         }
+                        Ok(None) => {
+                            println!(
+                                "  \u{2717} {:<14} unavailable",
+                                provider.name()
+                            );
+                        }
                         Err(e) => {
                             println!(
                                 "  \u{2717} {:<14} error: {}",
@@ -183,19 +491,35 @@ fn main() -> Result<()> {
                             );
                         }
                     }
-                } else {
-                    println!(
-                        "  \u{2717} {:<14} unavailable",
-                        provider.name()
-                    );
                 }
             }
 
             println!("\nTotal: {} apps (before dedup)", total);
+
+            println!("\nCache:");
+            match engine.cache() {
+                Some(cache) => {
+                    println!("  directory      {}", cache.dir().display());
+                    for provider in engine.providers() {
+                        if !provider.is_available() {
+                            continue;
+                        }
+                        let status = match cache.status(provider.as_ref()) {
+                            CacheStatus::Fresh { entries, age } => {
+                                format!("fresh, {} apps, {} old", entries, format_duration(age))
+                            }
+                            CacheStatus::Stale => "stale".to_string(),
+                            CacheStatus::Missing => "not cached".to_string(),
+                            CacheStatus::Uncacheable => "not cacheable".to_string(),
+                        };
+                        println!("  {:<14} {}", provider.name(), status);
+                    }
+                }
+                None => println!("  disabled"),
+            }
         }
-        Command::Completions { shell } => {
-            let mut cmd = Cli::command();
-            generate(shell, &mut cmd, "appgrep", &mut std::io::stdout());
+        Command::History { .. } | Command::Config { .. } | Command::Completions { .. } => {
+            unreachable!("handled in main")
         }
     }
 
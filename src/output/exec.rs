@@ -16,15 +16,7 @@ mod tests {
     use crate::app::{AppSource, Application};
 
     fn make_app(name: &str, exec: &str) -> Application {
-        Application {
-            name: name.to_string(),
-            exec_command: exec.to_string(),
-            source: AppSource::Desktop,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
-            description: None,
-        }
+        Application::new(name, exec, AppSource::Desktop)
     }
 
     #[test]
@@ -1,19 +1,68 @@
+use serde::Serialize;
+
 use crate::app::Application;
+use crate::engine::ProviderWarning;
+use crate::search::SearchMatch;
+
+/// A list together with the providers whose results are missing from it.
+#[derive(Serialize)]
+struct Listing<'a, T> {
+    apps: &'a [T],
+    warnings: &'a [ProviderWarning],
+}
+
+/// An object with a `warnings` field added.
+#[derive(Serialize)]
+struct WithWarnings<'a, T> {
+    #[serde(flatten)]
+    item: &'a T,
+    warnings: &'a [ProviderWarning],
+}
 
 pub fn format_json_list(
     apps: &[Application],
     w: &mut dyn std::io::Write,
 ) -> anyhow::Result<()> {
-    let json = serde_json::to_string_pretty(apps)?;
-    writeln!(w, "{}", json)?;
-    Ok(())
+    format_json_listing(apps, &[], w)
+}
+
+pub fn format_json_matches(
+    matches: &[SearchMatch],
+    w: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    format_json_listing(matches, &[], w)
 }
 
 pub fn format_json_single(
     app: &Application,
     w: &mut dyn std::io::Write,
 ) -> anyhow::Result<()> {
-    let json = serde_json::to_string_pretty(app)?;
+    format_json_object(app, &[], w)
+}
+
+/// `{"apps": [...], "warnings": [...]}`, with `warnings` empty unless some providers'
+/// results are missing.
+pub fn format_json_listing<T: Serialize>(
+    items: &[T],
+    warnings: &[ProviderWarning],
+    w: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(&Listing {
+        apps: items,
+        warnings,
+    })?;
+    writeln!(w, "{}", json)?;
+    Ok(())
+}
+
+/// `item`, which must serialize to a JSON object, with a `warnings` field that is
+/// empty unless some providers' results are missing.
+pub fn format_json_object<T: Serialize>(
+    item: &T,
+    warnings: &[ProviderWarning],
+    w: &mut dyn std::io::Write,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(&WithWarnings { item, warnings })?;
     writeln!(w, "{}", json)?;
     Ok(())
 }
@@ -32,6 +81,7 @@ mod tests {
             icon: Some(name.to_lowercase()),
             categories: vec!["Utility".to_string()],
             description: Some(format!("{} application", name)),
+            ..Default::default()
         }
     }
 
@@ -42,8 +92,9 @@ mod tests {
         format_json_list(&apps, &mut buf).unwrap();
         let output = String::from_utf8(buf).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
-        assert!(parsed.is_array());
-        assert_eq!(parsed.as_array().unwrap().len(), 0);
+        assert!(parsed["apps"].is_array());
+        assert_eq!(parsed["apps"].as_array().unwrap().len(), 0);
+        assert_eq!(parsed["warnings"], serde_json::json!([]));
     }
 
     #[test]
@@ -53,9 +104,9 @@ mod tests {
         format_json_list(&apps, &mut buf).unwrap();
         let output = String::from_utf8(buf).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
-        assert!(parsed.is_array());
-        assert_eq!(parsed.as_array().unwrap().len(), 1);
-        assert_eq!(parsed[0]["name"], "Firefox");
+        assert!(parsed["apps"].is_array());
+        assert_eq!(parsed["apps"].as_array().unwrap().len(), 1);
+        assert_eq!(parsed["apps"][0]["name"], "Firefox");
     }
 
     #[test]
@@ -65,7 +116,20 @@ mod tests {
         format_json_list(&apps, &mut buf).unwrap();
         let output = String::from_utf8(buf).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
-        assert_eq!(parsed.as_array().unwrap().len(), 3);
+        assert_eq!(parsed["apps"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_json_custom_source_round_trip() {
+        let mut app = make_app("Deployer");
+        app.source = AppSource::Custom("acme".to_string());
+        let mut buf = Vec::new();
+        format_json_list(&[app.clone()], &mut buf).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(parsed["apps"][0]["source"], "acme");
+
+        let back: Vec<Application> = serde_json::from_value(parsed["apps"].clone()).unwrap();
+        assert_eq!(back[0], app);
     }
 
     #[test]
@@ -75,7 +139,7 @@ mod tests {
         format_json_list(&apps, &mut buf).unwrap();
         let output = String::from_utf8(buf).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
-        let app = &parsed[0];
+        let app = &parsed["apps"][0];
         assert!(app.get("name").is_some());
         assert!(app.get("exec_command").is_some());
         assert!(app.get("source").is_some());
@@ -94,5 +158,42 @@ mod tests {
         let parsed: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
         assert_eq!(parsed["name"], "Firefox");
         assert_eq!(parsed["source"], "desktop");
+        assert_eq!(parsed["warnings"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_json_warnings() {
+        let warnings = vec![ProviderWarning {
+            provider: "snap".to_string(),
+            kind: crate::engine::WarningKind::Timeout,
+            message: "timed out after 3s".to_string(),
+        }];
+        let mut buf = Vec::new();
+        format_json_listing(&[make_app("Firefox")], &warnings, &mut buf).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(parsed["apps"][0]["name"], "Firefox");
+        assert_eq!(parsed["warnings"][0]["provider"], "snap");
+        assert_eq!(parsed["warnings"][0]["kind"], "timeout");
+
+        let mut buf = Vec::new();
+        format_json_object(&make_app("Firefox"), &warnings, &mut buf).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(parsed["name"], "Firefox");
+        assert_eq!(parsed["warnings"][0]["message"], "timed out after 3s");
+    }
+
+    #[test]
+    fn test_json_matches_include_score_and_field() {
+        let matches = vec![SearchMatch {
+            app: make_app("Firefox"),
+            score: 120,
+            matched_field: crate::search::SearchField::GenericName,
+        }];
+        let mut buf = Vec::new();
+        format_json_matches(&matches, &mut buf).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(parsed["apps"][0]["name"], "Firefox");
+        assert_eq!(parsed["apps"][0]["score"], 120);
+        assert_eq!(parsed["apps"][0]["matched_field"], "generic_name");
     }
 }
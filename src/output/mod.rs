@@ -4,7 +4,11 @@ pub mod names;
 pub mod table;
 pub mod tsv;
 
+use clap::ValueEnum;
+
 use crate::app::Application;
+use crate::engine::ProviderWarning;
+use crate::search::SearchMatch;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputFormat {
@@ -15,14 +19,48 @@ pub enum OutputFormat {
     Exec,
 }
 
+impl ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            OutputFormat::Table,
+            OutputFormat::Json,
+            OutputFormat::Tsv,
+            OutputFormat::Names,
+            OutputFormat::Exec,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            OutputFormat::Table => Some(clap::builder::PossibleValue::new("table")),
+            OutputFormat::Json => Some(clap::builder::PossibleValue::new("json")),
+            OutputFormat::Tsv => Some(clap::builder::PossibleValue::new("tsv")),
+            OutputFormat::Names => Some(clap::builder::PossibleValue::new("names")),
+            OutputFormat::Exec => Some(clap::builder::PossibleValue::new("exec")),
+        }
+    }
+}
+
 pub struct Formatter {
     format: OutputFormat,
     no_color: bool,
+    warnings: Vec<ProviderWarning>,
 }
 
 impl Formatter {
     pub fn new(format: OutputFormat, no_color: bool) -> Self {
-        Self { format, no_color }
+        Self {
+            format,
+            no_color,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Include `warnings` about the discovery being formatted in JSON output. Other
+    /// formats leave reporting them to the caller.
+    pub fn with_warnings(mut self, warnings: Vec<ProviderWarning>) -> Self {
+        self.warnings = warnings;
+        self
     }
 
     pub fn format_list(
@@ -32,20 +70,33 @@ impl Formatter {
     ) -> anyhow::Result<()> {
         match self.format {
             OutputFormat::Table => table::format_table(apps, w, self.no_color),
-            OutputFormat::Json => json::format_json_list(apps, w),
+            OutputFormat::Json => json::format_json_listing(apps, &self.warnings, w),
             OutputFormat::Tsv => tsv::format_tsv(apps, w),
             OutputFormat::Names => names::format_names(apps, w),
             OutputFormat::Exec => exec::format_exec(apps, w),
         }
     }
 
+    /// Like `format_list`, but JSON output also carries each match's score and field.
+    pub fn format_search(
+        &self,
+        matches: &[SearchMatch],
+        w: &mut dyn std::io::Write,
+    ) -> anyhow::Result<()> {
+        if self.format == OutputFormat::Json {
+            return json::format_json_listing(matches, &self.warnings, w);
+        }
+        let apps: Vec<Application> = matches.iter().map(|m| m.app.clone()).collect();
+        self.format_list(&apps, w)
+    }
+
     pub fn format_info(
         &self,
         app: &Application,
         w: &mut dyn std::io::Write,
     ) -> anyhow::Result<()> {
         match self.format {
-            OutputFormat::Json => json::format_json_single(app, w),
+            OutputFormat::Json => json::format_json_object(app, &self.warnings, w),
             _ => {
                 writeln!(w, "Name:        {}", app.name)?;
                 writeln!(w, "Exec:        {}", app.exec_command)?;
@@ -70,6 +121,49 @@ impl Formatter {
                     "Description: {}",
                     app.description.as_deref().unwrap_or("-")
                 )?;
+                if let Some(generic_name) = &app.generic_name {
+                    writeln!(w, "Generic:     {}", generic_name)?;
+                }
+                if !app.keywords.is_empty() {
+                    writeln!(w, "Keywords:    {}", app.keywords.join(", "))?;
+                }
+                if app.terminal {
+                    writeln!(w, "Terminal:    yes")?;
+                }
+                if let Some(reason) = &app.hidden_reason {
+                    writeln!(w, "Hidden:      {}", reason)?;
+                }
+                for (i, action) in app.actions.iter().enumerate() {
+                    let label = if i == 0 { "Actions:" } else { "" };
+                    writeln!(w, "{:<13}{} ({})", label, action.id, action.name)?;
+                }
+                if let Some(permissions) = &app.permissions {
+                    let list = |items: &[String]| {
+                        if items.is_empty() {
+                            "-".to_string()
+                        } else {
+                            items.join(", ")
+                        }
+                    };
+                    writeln!(w, "Filesystems: {}", list(&permissions.filesystems))?;
+                    writeln!(w, "Devices:     {}", list(&permissions.devices))?;
+                    writeln!(w, "Sockets:     {}", list(&permissions.sockets))?;
+                    writeln!(w, "Shared:      {}", list(&permissions.shared))?;
+                    let buses = [
+                        ("Session bus:", &permissions.session_bus),
+                        ("System bus:", &permissions.system_bus),
+                    ];
+                    for (label, policy) in buses {
+                        let names: Vec<String> = policy
+                            .iter()
+                            .map(|(name, access)| format!("{} ({})", name, access))
+                            .collect();
+                        writeln!(w, "{:<13}{}", label, list(&names))?;
+                    }
+                }
+                for (key, value) in &app.metadata {
+                    writeln!(w, "{:<13}{}", format!("{}:", metadata_label(key)), value)?;
+                }
                 Ok(())
             }
         }
@@ -87,7 +181,7 @@ impl Formatter {
                 "name": app.name,
                 "source": app.source,
             });
-            writeln!(w, "{}", serde_json::to_string_pretty(&obj)?)?;
+            json::format_json_object(&obj, &self.warnings, w)?;
         }
         // Silent for non-JSON formats (exit code only)
         Ok(())
@@ -103,12 +197,22 @@ impl Formatter {
                 "found": false,
                 "name": name,
             });
-            writeln!(w, "{}", serde_json::to_string_pretty(&obj)?)?;
+            json::format_json_object(&obj, &self.warnings, w)?;
         }
         Ok(())
     }
 }
 
+/// Turn a metadata key like `installed_size` into a label like `Installed size`.
+fn metadata_label(key: &str) -> String {
+    let spaced = key.replace('_', " ");
+    let mut chars = spaced.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,6 +227,7 @@ mod tests {
             icon: Some("icon".to_string()),
             categories: vec!["Utility".to_string()],
             description: Some(format!("{} application", name)),
+            ..Default::default()
         }
     }
 
@@ -131,10 +236,7 @@ mod tests {
             name: name.to_string(),
             exec_command: format!("/usr/bin/{}", name.to_lowercase()),
             source: AppSource::Standalone,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
-            description: None,
+            ..Default::default()
         }
     }
 
@@ -165,6 +267,77 @@ mod tests {
         assert!(output.contains("Description: -"));
     }
 
+    #[test]
+    fn test_format_info_plain_metadata() {
+        let formatter = Formatter::new(OutputFormat::Table, true);
+        let mut app = make_minimal_app("black");
+        app.metadata.insert("package".to_string(), "black".to_string());
+        app.metadata.insert("version".to_string(), "24.1.0".to_string());
+        let mut buf = Vec::new();
+        formatter.format_info(&app, &mut buf).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("Package:     black"));
+        assert!(output.contains("Version:     24.1.0"));
+    }
+
+    #[test]
+    fn test_format_info_plain_actions() {
+        let formatter = Formatter::new(OutputFormat::Table, true);
+        let mut app = make_app("Firefox");
+        for (id, name) in [("new-window", "New Window"), ("private", "Private Window")] {
+            app.actions.push(crate::app::DesktopAction {
+                id: id.to_string(),
+                name: name.to_string(),
+                icon: None,
+                exec_args: vec!["firefox".to_string()],
+            });
+        }
+        let mut buf = Vec::new();
+        formatter.format_info(&app, &mut buf).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("Actions:     new-window (New Window)\n"));
+        assert!(output.contains("\n             private (Private Window)\n"));
+    }
+
+    #[test]
+    fn test_format_info_plain_hidden_reason() {
+        let formatter = Formatter::new(OutputFormat::Table, true);
+        let mut app = make_app("Firefox");
+        let mut buf = Vec::new();
+        formatter.format_info(&app, &mut buf).unwrap();
+        assert!(!String::from_utf8(buf).unwrap().contains("Hidden:"));
+
+        app.hidden_reason = Some("TryExec=firefox not found".to_string());
+        let mut buf = Vec::new();
+        formatter.format_info(&app, &mut buf).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("Hidden:      TryExec=firefox not found"));
+    }
+
+    #[test]
+    fn test_format_info_plain_permissions() {
+        let formatter = Formatter::new(OutputFormat::Table, true);
+        let mut app = make_app("Editor");
+        app.permissions = Some(crate::sandbox::SandboxPermissions {
+            filesystems: vec!["home".to_string(), "xdg-download:ro".to_string()],
+            session_bus: [("org.freedesktop.secrets".to_string(), "talk".to_string())].into(),
+            ..Default::default()
+        });
+        let mut buf = Vec::new();
+        formatter.format_info(&app, &mut buf).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("Filesystems: home, xdg-download:ro\n"));
+        assert!(output.contains("Devices:     -\n"));
+        assert!(output.contains("Session bus: org.freedesktop.secrets (talk)\n"));
+        assert!(output.contains("System bus:  -\n"));
+    }
+
+    #[test]
+    fn test_metadata_label() {
+        assert_eq!(metadata_label("version"), "Version");
+        assert_eq!(metadata_label("installed_size"), "Installed size");
+    }
+
     #[test]
     fn test_format_info_json() {
         let formatter = Formatter::new(OutputFormat::Json, false);
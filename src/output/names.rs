@@ -16,15 +16,8 @@ mod tests {
     use crate::app::{AppSource, Application};
 
     fn make_app(name: &str) -> Application {
-        Application {
-            name: name.to_string(),
-            exec_command: format!("/usr/bin/{}", name.to_lowercase()),
-            source: AppSource::Desktop,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
-            description: None,
-        }
+        let exec = format!("/usr/bin/{}", name.to_lowercase());
+        Application::new(name, exec, AppSource::Desktop)
     }
 
     #[test]
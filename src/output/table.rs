@@ -10,7 +10,13 @@ pub fn format_table(
 ) -> anyhow::Result<()> {
     let mut table = Table::new();
     table.set_content_arrangement(ContentArrangement::Dynamic);
-    table.set_header(vec!["Name", "Exec", "Source", "Description"]);
+    // Only listings with --show-hidden have anything to put in this column
+    let show_hidden = apps.iter().any(|a| a.hidden_reason.is_some());
+    let mut header = vec!["Name", "Exec", "Source", "Description"];
+    if show_hidden {
+        header.push("Hidden");
+    }
+    table.set_header(header);
 
     for app in apps {
         let source_str = app.source.to_string();
@@ -21,6 +27,8 @@ pub fn format_table(
                 AppSource::Desktop => source_str.green().to_string(),
                 AppSource::Flatpak => source_str.blue().to_string(),
                 AppSource::Snap => source_str.yellow().to_string(),
+                AppSource::Pipx => source_str.bright_green().to_string(),
+                AppSource::Uv => source_str.bright_magenta().to_string(),
                 AppSource::Standalone => source_str.cyan().to_string(),
                 AppSource::Cargo => source_str.magenta().to_string(),
                 AppSource::Npm => source_str.red().to_string(),
@@ -28,15 +36,21 @@ pub fn format_table(
                 AppSource::Rpm => source_str.bright_red().to_string(),
                 AppSource::Pacman => source_str.bright_cyan().to_string(),
                 AppSource::Brew => source_str.bright_yellow().to_string(),
+                AppSource::Nix => source_str.bright_blue().to_string(),
+                AppSource::Custom(_) => source_str.bright_white().to_string(),
             }
         };
 
-        table.add_row(vec![
+        let mut row = vec![
             Cell::new(&app.name),
             Cell::new(&app.exec_command),
             Cell::new(source_display),
             Cell::new(app.description.as_deref().unwrap_or("")),
-        ]);
+        ];
+        if show_hidden {
+            row.push(Cell::new(app.hidden_reason.as_deref().unwrap_or("")));
+        }
+        table.add_row(row);
     }
 
     writeln!(w, "{}", table)?;
@@ -52,10 +66,8 @@ mod tests {
             name: name.to_string(),
             exec_command: format!("/usr/bin/{}", name.to_lowercase()),
             source,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
             description: Some(format!("{} app", name)),
+            ..Default::default()
         }
     }
 
@@ -113,4 +125,19 @@ mod tests {
         assert!(output.contains("mytool"));
         assert!(output.contains("curl"));
     }
+
+    #[test]
+    fn test_table_hidden_column_only_when_needed() {
+        let mut apps = vec![make_app("Firefox", AppSource::Desktop)];
+        let mut buf = Vec::new();
+        format_table(&apps, &mut buf, true).unwrap();
+        assert!(!String::from_utf8(buf).unwrap().contains("Hidden"));
+
+        apps[0].hidden_reason = Some("NoDisplay=true".to_string());
+        let mut buf = Vec::new();
+        format_table(&apps, &mut buf, true).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.contains("Hidden"));
+        assert!(output.contains("NoDisplay=true"));
+    }
 }
@@ -28,10 +28,8 @@ mod tests {
             name: name.to_string(),
             exec_command: format!("/usr/bin/{}", name.to_lowercase()),
             source: AppSource::Desktop,
-            location: String::new(),
-            icon: None,
-            categories: Vec::new(),
             description: Some(format!("{} app", name)),
+            ..Default::default()
         }
     }
 
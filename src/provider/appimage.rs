@@ -0,0 +1,1268 @@
+//! Reading the desktop entry, icon and AppStream metadata embedded in an AppImage
+//! without running it.
+//!
+//! Type 1 AppImages are ISO 9660 images with Rock Ridge names whose system area holds
+//! the ELF runtime. Type 2 AppImages are an ELF runtime followed by a squashfs
+//! filesystem starting where the ELF section headers end.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::provider::appstream::{parse_appstream, AppStream};
+
+/// Largest embedded file read. Desktop entries, icons and AppStream files are far
+/// smaller; the limit only guards against corrupt images.
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Symbolic links followed while resolving one path.
+const MAX_SYMLINKS: usize = 8;
+
+/// Directories AppStream metadata is installed in, current name first.
+const APPSTREAM_DIRS: &[&str] = &["usr/share/metainfo", "usr/share/appdata"];
+
+/// What an AppImage says about itself.
+#[derive(Debug, Default)]
+pub struct AppImage {
+    /// 1 (ISO 9660) or 2 (squashfs).
+    pub image_type: u8,
+    /// File name and content of the desktop entry in the image root.
+    pub desktop_entry: Option<(String, String)>,
+    /// `.DirIcon`, a PNG or SVG image.
+    pub icon: Option<Vec<u8>>,
+    pub appstream: Option<AppStream>,
+}
+
+impl AppImage {
+    /// Read the AppImage at `path`. `Ok(None)` if the file is not an AppImage.
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let file = File::open(path)?;
+        let mut head = [0u8; 64];
+        let len = file.read_at(&mut head, 0)?;
+        if len < head.len() || &head[..4] != b"\x7fELF" {
+            return Ok(None);
+        }
+
+        let image_type = match &head[8..11] {
+            b"AI\x01" => 1,
+            b"AI\x02" => 2,
+            // Early AppImages lack the magic bytes
+            _ if Iso9660::detect(&file) => 1,
+            _ if elf_end(&head).is_some_and(|end| SquashFs::detect(&file, end)) => 2,
+            _ => return Ok(None),
+        };
+        let fs: Box<dyn Filesystem> = if image_type == 1 {
+            Box::new(Iso9660::open(file)?)
+        } else {
+            let offset = elf_end(&head).ok_or_else(|| invalid("truncated ELF header"))?;
+            Box::new(SquashFs::open(file, offset)?)
+        };
+
+        let mut image = AppImage {
+            image_type,
+            ..Default::default()
+        };
+        let root = fs.root()?;
+        let mut desktop_files: Vec<Entry> = fs
+            .read_dir(&root)?
+            .into_iter()
+            .filter(|e| e.name.ends_with(".desktop"))
+            .collect();
+        desktop_files.sort_by(|a, b| a.name.cmp(&b.name));
+        if let Some(entry) = desktop_files.first() {
+            if let Some(content) = read_path(fs.as_ref(), &entry.name)? {
+                let content = String::from_utf8_lossy(&content).to_string();
+                image.desktop_entry = Some((entry.name.clone(), content));
+            }
+        }
+        image.icon = read_path(fs.as_ref(), ".DirIcon")?;
+
+        let desktop_id = image
+            .desktop_entry
+            .as_ref()
+            .map(|(name, _)| name.trim_end_matches(".desktop").to_string());
+        image.appstream = read_appstream(fs.as_ref(), desktop_id.as_deref())?;
+        Ok(Some(image))
+    }
+}
+
+/// The AppStream file in the image, preferring the component for `desktop_id`.
+fn read_appstream(fs: &dyn Filesystem, desktop_id: Option<&str>) -> io::Result<Option<AppStream>> {
+    let mut found = Vec::new();
+    for dir in APPSTREAM_DIRS {
+        let dir = match lookup(fs, dir)? {
+            Some(d) if d.kind == Kind::Dir => d,
+            _ => continue,
+        };
+        let mut files: Vec<Entry> = fs
+            .read_dir(&dir)?
+            .into_iter()
+            .filter(|e| e.name.ends_with(".xml") && e.kind == Kind::File)
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        for file in files {
+            let content = fs.read_file(&file)?;
+            if let Some(appstream) = parse_appstream(&String::from_utf8_lossy(&content)) {
+                found.push(appstream);
+            }
+        }
+    }
+    let preferred = desktop_id.and_then(|id| {
+        found.iter().position(|a| {
+            a.id.as_deref()
+                .is_some_and(|a| a == id || a.trim_end_matches(".desktop") == id)
+        })
+    });
+    Ok(match preferred {
+        Some(i) => Some(found.swap_remove(i)),
+        None => found.into_iter().next(),
+    })
+}
+
+/// Where the ELF file in `head` ends: after its section header table.
+fn elf_end(head: &[u8]) -> Option<u64> {
+    let big_endian = head[5] == 2;
+    let u16_at = |off: usize| {
+        let bytes = [head[off], head[off + 1]];
+        if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let (shoff, shentsize, shnum) = match head[4] {
+        1 => {
+            let bytes = head[0x20..0x24].try_into().ok()?;
+            let shoff = if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            };
+            (shoff as u64, u16_at(0x2e), u16_at(0x30))
+        }
+        2 => {
+            let bytes = head[0x28..0x30].try_into().ok()?;
+            let shoff = if big_endian {
+                u64::from_be_bytes(bytes)
+            } else {
+                u64::from_le_bytes(bytes)
+            };
+            (shoff, u16_at(0x3a), u16_at(0x3c))
+        }
+        _ => return None,
+    };
+    shoff.checked_add(shentsize as u64 * shnum as u64)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn u16_at(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+fn u32_at(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(buf[off..off + 8].try_into().unwrap())
+}
+
+/// `base + offset`, failing for offsets past the end of any file.
+fn add_offset(base: u64, offset: u64) -> io::Result<u64> {
+    base.checked_add(offset)
+        .ok_or_else(|| invalid("offset out of range"))
+}
+
+/// Read `len` bytes at `pos`, failing on a short read.
+fn read_exact(file: &File, pos: u64, len: u64) -> io::Result<Vec<u8>> {
+    if len > MAX_FILE_SIZE {
+        return Err(invalid("embedded file too large"));
+    }
+    let mut buf = vec![0; len as usize];
+    file.read_exact_at(&mut buf, pos)?;
+    Ok(buf)
+}
+
+/// Collects decompressed output, failing as soon as it grows past `max` bytes.
+struct BoundedWriter {
+    out: Vec<u8>,
+    max: usize,
+}
+
+impl Write for BoundedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.max - self.out.len() {
+            return Err(invalid("decompressed block too large"));
+        }
+        self.out.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Dir,
+    File,
+    Symlink(String),
+    Other,
+}
+
+/// A directory entry. `location` and `size` are filesystem specific.
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    kind: Kind,
+    location: u64,
+    size: u64,
+}
+
+/// The read-only view of an image's filesystem needed to find files in it.
+trait Filesystem {
+    fn root(&self) -> io::Result<Entry>;
+    fn read_dir(&self, dir: &Entry) -> io::Result<Vec<Entry>>;
+    fn read_file(&self, file: &Entry) -> io::Result<Vec<u8>>;
+}
+
+/// The entry at `path`, relative to the image root, following symbolic links. Absolute
+/// link targets are resolved inside the image.
+fn lookup(fs: &dyn Filesystem, path: &str) -> io::Result<Option<Entry>> {
+    let mut ancestors = vec![fs.root()?];
+    let mut components: VecDeque<String> = path.split('/').map(str::to_string).collect();
+    let mut links = 0;
+    while let Some(component) = components.pop_front() {
+        match component.as_str() {
+            "" | "." => continue,
+            ".." => {
+                if ancestors.len() > 1 {
+                    ancestors.pop();
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let dir = ancestors.last().expect("the root is never popped");
+        if dir.kind != Kind::Dir {
+            return Ok(None);
+        }
+        let entry = match fs.read_dir(dir)?.into_iter().find(|e| e.name == component) {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        if let Kind::Symlink(target) = &entry.kind {
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Ok(None);
+            }
+            if target.starts_with('/') {
+                ancestors.truncate(1);
+            }
+            for part in target.split('/').rev() {
+                components.push_front(part.to_string());
+            }
+            continue;
+        }
+        ancestors.push(entry);
+    }
+    Ok(ancestors.pop())
+}
+
+/// The content of the regular file at `path`, if there is one.
+fn read_path(fs: &dyn Filesystem, path: &str) -> io::Result<Option<Vec<u8>>> {
+    match lookup(fs, path)? {
+        Some(entry) if entry.kind == Kind::File => fs.read_file(&entry).map(Some),
+        _ => Ok(None),
+    }
+}
+
+const SQUASHFS_MAGIC: &[u8] = b"hsqs";
+const METADATA_SIZE: usize = 8192;
+const NO_FRAGMENT: u32 = 0xffff_ffff;
+/// Set in a data block size when the block is stored uncompressed.
+const UNCOMPRESSED_BLOCK: u32 = 1 << 24;
+
+/// A squashfs 4.0 filesystem, compressed with gzip, xz or zstd.
+struct SquashFs {
+    file: File,
+    /// Where the filesystem starts in the file; its offsets are relative to this.
+    offset: u64,
+    block_size: u32,
+    compressor: u16,
+    root_inode: u64,
+    inode_table: u64,
+    directory_table: u64,
+    fragment_table: u64,
+    /// Metadata blocks already read, by position.
+    metadata: RefCell<HashMap<u64, MetadataBlock>>,
+}
+
+/// A decompressed metadata block and the position of the block after it.
+type MetadataBlock = (Rc<Vec<u8>>, u64);
+
+enum Inode {
+    Dir {
+        block: u64,
+        offset: usize,
+        size: usize,
+    },
+    File {
+        blocks_start: u64,
+        size: u64,
+        fragment: u32,
+        fragment_offset: u32,
+        block_sizes: Vec<u32>,
+    },
+    Symlink(String),
+    Other,
+}
+
+impl SquashFs {
+    fn detect(file: &File, offset: u64) -> bool {
+        let mut magic = [0u8; 4];
+        file.read_exact_at(&mut magic, offset).is_ok() && magic == SQUASHFS_MAGIC
+    }
+
+    fn open(file: File, offset: u64) -> io::Result<Self> {
+        let sb = read_exact(&file, offset, 96)?;
+        if &sb[..4] != SQUASHFS_MAGIC || u16_at(&sb, 28) != 4 {
+            return Err(invalid("not a squashfs 4.0 filesystem"));
+        }
+        let block_size = u32_at(&sb, 12);
+        if !block_size.is_power_of_two() || block_size > 1024 * 1024 {
+            return Err(invalid("invalid squashfs block size"));
+        }
+        let compressor = u16_at(&sb, 20);
+        if !matches!(compressor, 1 | 4 | 6) {
+            let name = match compressor {
+                2 => "lzma",
+                3 => "lzo",
+                5 => "lz4",
+                _ => "unknown",
+            };
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported squashfs compression: {}", name),
+            ));
+        }
+        Ok(Self {
+            file,
+            offset,
+            block_size,
+            compressor,
+            root_inode: u64_at(&sb, 32),
+            inode_table: u64_at(&sb, 64),
+            directory_table: u64_at(&sb, 72),
+            fragment_table: u64_at(&sb, 80),
+            metadata: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn read_at(&self, pos: u64, len: u64) -> io::Result<Vec<u8>> {
+        read_exact(&self.file, add_offset(self.offset, pos)?, len)
+    }
+
+    fn decompress(&self, data: &[u8], max: usize) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        match self.compressor {
+            1 => {
+                flate2::read::ZlibDecoder::new(data)
+                    .take(max as u64)
+                    .read_to_end(&mut out)?;
+            }
+            4 => {
+                let mut writer = BoundedWriter { out, max };
+                lzma_rs::xz_decompress(&mut io::BufReader::new(data), &mut writer)
+                    .map_err(|e| invalid(&e.to_string()))?;
+                out = writer.out;
+            }
+            _ => {
+                let mut source = data;
+                ruzstd::StreamingDecoder::new(&mut source)
+                    .map_err(|e| invalid(&e.to_string()))?
+                    .take(max as u64)
+                    .read_to_end(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// The metadata block at `pos`, decompressed, and where the next one starts.
+    fn metadata_block(&self, pos: u64) -> io::Result<MetadataBlock> {
+        if let Some(block) = self.metadata.borrow().get(&pos) {
+            return Ok(block.clone());
+        }
+        let header = u16_at(&self.read_at(pos, 2)?, 0);
+        let size = (header & 0x7fff) as u64;
+        let start = add_offset(pos, 2)?;
+        let raw = self.read_at(start, size)?;
+        let data = if header & 0x8000 != 0 {
+            raw
+        } else {
+            self.decompress(&raw, METADATA_SIZE)?
+        };
+        let block = (Rc::new(data), add_offset(start, size)?);
+        self.metadata.borrow_mut().insert(pos, block.clone());
+        Ok(block)
+    }
+
+    /// `len` bytes of metadata starting `offset` bytes into the block at `pos`.
+    fn read_metadata(&self, mut pos: u64, mut offset: usize, len: usize) -> io::Result<Vec<u8>> {
+        if len as u64 > MAX_FILE_SIZE {
+            return Err(invalid("metadata too large"));
+        }
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let (data, next) = self.metadata_block(pos)?;
+            let take = (len - out.len()).min(data.len().saturating_sub(offset));
+            if take == 0 {
+                return Err(invalid("truncated squashfs metadata"));
+            }
+            out.extend_from_slice(&data[offset..offset + take]);
+            offset = 0;
+            pos = next;
+        }
+        Ok(out)
+    }
+
+    fn inode(&self, reference: u64) -> io::Result<Inode> {
+        let pos = add_offset(self.inode_table, reference >> 16)?;
+        let offset = (reference & 0xffff) as usize;
+        let read = |len: usize| self.read_metadata(pos, offset, len);
+
+        let inode = match u16_at(&read(2)?, 0) {
+            1 => {
+                let b = read(32)?;
+                Inode::Dir {
+                    block: u32_at(&b, 16) as u64,
+                    size: u16_at(&b, 24) as usize,
+                    offset: u16_at(&b, 26) as usize,
+                }
+            }
+            8 => {
+                let b = read(40)?;
+                Inode::Dir {
+                    size: u32_at(&b, 20) as usize,
+                    block: u32_at(&b, 24) as u64,
+                    offset: u16_at(&b, 34) as usize,
+                }
+            }
+            kind @ (2 | 9) => {
+                let (header_len, blocks_start, size, fragment, fragment_offset) = if kind == 2 {
+                    let b = read(32)?;
+                    (
+                        32,
+                        u32_at(&b, 16) as u64,
+                        u32_at(&b, 28) as u64,
+                        u32_at(&b, 20),
+                        u32_at(&b, 24),
+                    )
+                } else {
+                    let b = read(56)?;
+                    (
+                        56,
+                        u64_at(&b, 16),
+                        u64_at(&b, 24),
+                        u32_at(&b, 44),
+                        u32_at(&b, 48),
+                    )
+                };
+                if size > MAX_FILE_SIZE {
+                    return Err(invalid("embedded file too large"));
+                }
+                let block_size = self.block_size as u64;
+                let count = if fragment == NO_FRAGMENT {
+                    size.div_ceil(block_size)
+                } else {
+                    size / block_size
+                } as usize;
+                let b = read(header_len + 4 * count)?;
+                Inode::File {
+                    blocks_start,
+                    size,
+                    fragment,
+                    fragment_offset,
+                    block_sizes: (0..count).map(|i| u32_at(&b, header_len + 4 * i)).collect(),
+                }
+            }
+            3 | 10 => {
+                let target_len = u32_at(&read(24)?, 20) as usize;
+                let b = read(24 + target_len)?;
+                Inode::Symlink(String::from_utf8_lossy(&b[24..]).to_string())
+            }
+            _ => Inode::Other,
+        };
+        Ok(inode)
+    }
+
+    /// The fragment block with index `index`, decompressed.
+    fn fragment(&self, index: u32) -> io::Result<Vec<u8>> {
+        let pos = add_offset(self.fragment_table, 8 * (index as u64 / 512))?;
+        let table = self.read_at(pos, 8)?;
+        let entry = self.read_metadata(u64_at(&table, 0), (index as usize % 512) * 16, 16)?;
+        self.data_block(u64_at(&entry, 0), u32_at(&entry, 8))
+    }
+
+    fn data_block(&self, pos: u64, size: u32) -> io::Result<Vec<u8>> {
+        let raw = self.read_at(pos, (size & !UNCOMPRESSED_BLOCK) as u64)?;
+        if size & UNCOMPRESSED_BLOCK != 0 {
+            Ok(raw)
+        } else {
+            self.decompress(&raw, self.block_size as usize)
+        }
+    }
+}
+
+impl Filesystem for SquashFs {
+    fn root(&self) -> io::Result<Entry> {
+        Ok(Entry {
+            name: String::new(),
+            kind: Kind::Dir,
+            location: self.root_inode,
+            size: 0,
+        })
+    }
+
+    fn read_dir(&self, dir: &Entry) -> io::Result<Vec<Entry>> {
+        let (block, offset, size) = match self.inode(dir.location)? {
+            Inode::Dir {
+                block,
+                offset,
+                size,
+            } => (block, offset, size),
+            _ => return Err(invalid("not a directory")),
+        };
+        // The size counts three bytes for the implicit "." and ".." entries
+        let len = size.saturating_sub(3);
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self.read_metadata(add_offset(self.directory_table, block)?, offset, len)?;
+
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos + 12 <= data.len() {
+            let count = u32_at(&data, pos) as usize + 1;
+            let start = u32_at(&data, pos + 4) as u64;
+            pos += 12;
+            for _ in 0..count {
+                if pos + 8 > data.len() {
+                    return Err(invalid("truncated squashfs directory"));
+                }
+                let inode_offset = u16_at(&data, pos) as u64;
+                let kind = u16_at(&data, pos + 4);
+                let name_len = u16_at(&data, pos + 6) as usize + 1;
+                let name = data
+                    .get(pos + 8..pos + 8 + name_len)
+                    .ok_or_else(|| invalid("truncated squashfs directory"))?;
+                pos += 8 + name_len;
+
+                let location = (start << 16) | inode_offset;
+                let kind = match kind {
+                    1 | 8 => Kind::Dir,
+                    2 | 9 => Kind::File,
+                    3 | 10 => match self.inode(location)? {
+                        Inode::Symlink(target) => Kind::Symlink(target),
+                        _ => Kind::Other,
+                    },
+                    _ => Kind::Other,
+                };
+                entries.push(Entry {
+                    name: String::from_utf8_lossy(name).to_string(),
+                    kind,
+                    location,
+                    size: 0,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read_file(&self, file: &Entry) -> io::Result<Vec<u8>> {
+        let (mut pos, size, fragment, fragment_offset, block_sizes) =
+            match self.inode(file.location)? {
+                Inode::File {
+                    blocks_start,
+                    size,
+                    fragment,
+                    fragment_offset,
+                    block_sizes,
+                } => (blocks_start, size, fragment, fragment_offset, block_sizes),
+                _ => return Err(invalid("not a regular file")),
+            };
+
+        let mut out = Vec::with_capacity(size as usize);
+        for block_size in block_sizes {
+            let on_disk = block_size & !UNCOMPRESSED_BLOCK;
+            if on_disk == 0 {
+                // A sparse block
+                out.resize(out.len() + self.block_size as usize, 0);
+                continue;
+            }
+            out.extend(self.data_block(pos, block_size)?);
+            pos = add_offset(pos, on_disk as u64)?;
+        }
+        if fragment != NO_FRAGMENT {
+            let tail = (size as usize).saturating_sub(out.len());
+            let block = self.fragment(fragment)?;
+            let start = fragment_offset as usize;
+            let data = block
+                .get(start..start + tail)
+                .ok_or_else(|| invalid("truncated squashfs fragment"))?;
+            out.extend_from_slice(data);
+        }
+        out.truncate(size as usize);
+        Ok(out)
+    }
+}
+
+const SECTOR: u64 = 2048;
+
+/// An ISO 9660 filesystem, with Rock Ridge names and symbolic links when present.
+struct Iso9660 {
+    file: File,
+    root: Entry,
+}
+
+impl Iso9660 {
+    fn detect(file: &File) -> bool {
+        let mut magic = [0u8; 5];
+        file.read_exact_at(&mut magic, 16 * SECTOR + 1).is_ok() && &magic == b"CD001"
+    }
+
+    fn open(file: File) -> io::Result<Self> {
+        let pvd = read_exact(&file, 16 * SECTOR, SECTOR)?;
+        if pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+            return Err(invalid("no ISO 9660 primary volume descriptor"));
+        }
+        let root = parse_record(&pvd[156..190]).ok_or_else(|| invalid("invalid ISO 9660 root"))?;
+        Ok(Self { file, root })
+    }
+}
+
+impl Filesystem for Iso9660 {
+    fn root(&self) -> io::Result<Entry> {
+        Ok(self.root.clone())
+    }
+
+    fn read_dir(&self, dir: &Entry) -> io::Result<Vec<Entry>> {
+        let data = read_exact(&self.file, dir.location * SECTOR, dir.size)?;
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let len = data[pos] as usize;
+            if len == 0 {
+                // Records do not cross sectors; the rest of this one is padding
+                pos = (pos / SECTOR as usize + 1) * SECTOR as usize;
+                continue;
+            }
+            let record = data
+                .get(pos..pos + len)
+                .ok_or_else(|| invalid("truncated ISO 9660 directory"))?;
+            if let Some(entry) = parse_record(record) {
+                if entry.name != "." && entry.name != ".." {
+                    entries.push(entry);
+                }
+            }
+            pos += len;
+        }
+        Ok(entries)
+    }
+
+    fn read_file(&self, file: &Entry) -> io::Result<Vec<u8>> {
+        read_exact(&self.file, file.location * SECTOR, file.size)
+    }
+}
+
+/// A directory record, named by its Rock Ridge `NM` entry when it has one.
+fn parse_record(record: &[u8]) -> Option<Entry> {
+    if record.len() < 34 {
+        return None;
+    }
+    let location = u32_at(record, 2) as u64;
+    let size = u32_at(record, 10) as u64;
+    let is_dir = record[25] & 2 != 0;
+    let name_len = record[32] as usize;
+    let iso_name = record.get(33..33 + name_len)?;
+    // A padding byte keeps the system use area at an even offset
+    let system_use = record
+        .get(33 + name_len + (1 - name_len % 2)..)
+        .unwrap_or_default();
+    let (rr_name, symlink) = parse_rock_ridge(system_use);
+
+    let name = match (rr_name, iso_name) {
+        (_, [0]) => ".".to_string(),
+        (_, [1]) => "..".to_string(),
+        (Some(name), _) => name,
+        (None, _) => {
+            let name = String::from_utf8_lossy(iso_name);
+            let name = name.split(';').next().unwrap_or_default();
+            name.trim_end_matches('.').to_lowercase()
+        }
+    };
+    let kind = match symlink {
+        Some(target) => Kind::Symlink(target),
+        None if is_dir => Kind::Dir,
+        None => Kind::File,
+    };
+    Some(Entry {
+        name,
+        kind,
+        location,
+        size,
+    })
+}
+
+/// The alternate name (`NM`) and symbolic link target (`SL`) in a system use area.
+fn parse_rock_ridge(system_use: &[u8]) -> (Option<String>, Option<String>) {
+    let mut name: Option<String> = None;
+    let mut components: Option<Vec<String>> = None;
+    let mut continues_component = false;
+
+    let mut pos = 0;
+    while pos + 4 <= system_use.len() {
+        let len = system_use[pos + 2] as usize;
+        if len < 4 || pos + len > system_use.len() {
+            break;
+        }
+        let data = &system_use[pos + 4..pos + len];
+        match &system_use[pos..pos + 2] {
+            // Flags 2 and 4 mark the "." and ".." names
+            b"NM" if !data.is_empty() && data[0] & 0x6 == 0 => {
+                name.get_or_insert_with(String::new)
+                    .push_str(&String::from_utf8_lossy(&data[1..]));
+            }
+            b"SL" if !data.is_empty() => {
+                let components = components.get_or_insert_with(Vec::new);
+                let mut i = 1;
+                while i + 2 <= data.len() {
+                    let flags = data[i];
+                    let content = data
+                        .get(i + 2..i + 2 + data[i + 1] as usize)
+                        .unwrap_or_default();
+                    let part = match flags {
+                        f if f & 0x2 != 0 => ".".to_string(),
+                        f if f & 0x4 != 0 => "..".to_string(),
+                        f if f & 0x8 != 0 => String::new(),
+                        _ => String::from_utf8_lossy(content).to_string(),
+                    };
+                    match components.last_mut() {
+                        Some(last) if continues_component => last.push_str(&part),
+                        _ => components.push(part),
+                    }
+                    continues_component = flags & 0x1 != 0;
+                    i += 2 + content.len();
+                }
+            }
+            b"ST" => break,
+            _ => {}
+        }
+        pos += len;
+    }
+
+    let target = components.map(|c| match c.as_slice() {
+        // A lone root component is the root itself
+        [root] if root.is_empty() => "/".to_string(),
+        _ => c.join("/"),
+    });
+    (name, target)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    /// A file tree to build test images from.
+    pub(crate) enum Node {
+        File(&'static str, Vec<u8>),
+        Symlink(&'static str, &'static str),
+        Dir(&'static str, Vec<Node>),
+    }
+
+    impl Node {
+        fn name(&self) -> &'static str {
+            match self {
+                Node::File(name, _) | Node::Symlink(name, _) | Node::Dir(name, _) => name,
+            }
+        }
+    }
+
+    /// The files of a typical AppImage.
+    pub(crate) fn sample_tree(desktop: &str) -> Vec<Node> {
+        vec![
+            Node::File("AppRun", b"#!/bin/sh\n".to_vec()),
+            Node::File("app.desktop", desktop.as_bytes().to_vec()),
+            Node::Symlink(".DirIcon", "app.png"),
+            Node::File("app.png", b"\x89PNG\r\n\x1a\nicon".to_vec()),
+            Node::Dir(
+                "usr",
+                vec![Node::Dir(
+                    "share",
+                    vec![Node::Dir(
+                        "metainfo",
+                        vec![Node::File(
+                            "app.appdata.xml",
+                            br#"<?xml version="1.0"?>
+<component type="desktop-application">
+  <id>app.desktop</id>
+  <name>App</name>
+  <name xml:lang="de">Anwendung</name>
+  <summary>Does things</summary>
+  <categories><category>Office</category></categories>
+  <releases>
+    <release version="2.1.0" date="2024-05-01"/>
+    <release version="2.0.0" date="2024-01-01"/>
+  </releases>
+</component>"#
+                                .to_vec(),
+                        )],
+                    )],
+                )],
+            ),
+        ]
+    }
+
+    /// A 64-bit ELF header whose section headers end at `end`, with AppImage magic.
+    fn elf_header(image_type: u8, end: u64) -> Vec<u8> {
+        let mut head = vec![0u8; 64];
+        head[..4].copy_from_slice(b"\x7fELF");
+        head[4] = 2;
+        head[5] = 1;
+        head[8..11].copy_from_slice(&[b'A', b'I', image_type]);
+        head[0x28..0x30].copy_from_slice(&end.to_le_bytes());
+        head
+    }
+
+    /// A type 2 AppImage: an ELF header followed by a gzip squashfs holding `tree`. The
+    /// first file is stored in a fragment; the others in compressed data blocks.
+    pub(crate) fn squashfs_appimage(tree: &[Node]) -> Vec<u8> {
+        const BLOCK_SIZE: u32 = 131072;
+        let mut data = vec![0u8; 96];
+        let mut inodes = Vec::new();
+        let mut dirs = Vec::new();
+        let mut fragment: Option<Vec<u8>> = None;
+        let mut inode_count = 0u32;
+
+        fn metadata(bytes: &[u8]) -> Vec<u8> {
+            let mut out = (0x8000u16 | bytes.len() as u16).to_le_bytes().to_vec();
+            out.extend_from_slice(bytes);
+            out
+        }
+
+        struct Builder<'a> {
+            data: &'a mut Vec<u8>,
+            inodes: &'a mut Vec<u8>,
+            dirs: &'a mut Vec<u8>,
+            fragment: &'a mut Option<Vec<u8>>,
+            inode_count: &'a mut u32,
+        }
+
+        impl Builder<'_> {
+            fn header(&mut self, kind: u16) -> u16 {
+                let offset = self.inodes.len() as u16;
+                *self.inode_count += 1;
+                self.inodes.extend_from_slice(&kind.to_le_bytes());
+                self.inodes.extend_from_slice(&[0; 10]);
+                self.inodes
+                    .extend_from_slice(&self.inode_count.to_le_bytes());
+                offset
+            }
+
+            /// Write `node`, returning its inode offset and directory entry type.
+            fn write(&mut self, node: &Node) -> (u16, u16) {
+                match node {
+                    Node::File(_, content) => {
+                        if self.fragment.is_none() {
+                            *self.fragment = Some(content.clone());
+                            let offset = self.header(2);
+                            for value in [0, 0, 0, content.len() as u32] {
+                                self.inodes.extend_from_slice(&value.to_le_bytes());
+                            }
+                            return (offset, 2);
+                        }
+                        let start = self.data.len() as u32;
+                        let mut encoder = flate2::write::ZlibEncoder::new(
+                            Vec::new(),
+                            flate2::Compression::default(),
+                        );
+                        encoder.write_all(content).unwrap();
+                        let compressed = encoder.finish().unwrap();
+                        self.data.extend_from_slice(&compressed);
+                        let offset = self.header(2);
+                        for value in [start, NO_FRAGMENT, 0, content.len() as u32] {
+                            self.inodes.extend_from_slice(&value.to_le_bytes());
+                        }
+                        if !content.is_empty() {
+                            self.inodes
+                                .extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+                        }
+                        (offset, 2)
+                    }
+                    Node::Symlink(_, target) => {
+                        let offset = self.header(3);
+                        self.inodes.extend_from_slice(&1u32.to_le_bytes());
+                        self.inodes
+                            .extend_from_slice(&(target.len() as u32).to_le_bytes());
+                        self.inodes.extend_from_slice(target.as_bytes());
+                        (offset, 3)
+                    }
+                    Node::Dir(_, children) => {
+                        let written: Vec<(u16, u16)> =
+                            children.iter().map(|c| self.write(c)).collect();
+                        let listing_offset = self.dirs.len() as u16;
+                        let mut listing = Vec::new();
+                        if !children.is_empty() {
+                            for value in [children.len() as u32 - 1, 0, 0] {
+                                listing.extend_from_slice(&value.to_le_bytes());
+                            }
+                            for (child, (inode, kind)) in children.iter().zip(written) {
+                                listing.extend_from_slice(&inode.to_le_bytes());
+                                listing.extend_from_slice(&0u16.to_le_bytes());
+                                listing.extend_from_slice(&kind.to_le_bytes());
+                                listing.extend_from_slice(
+                                    &(child.name().len() as u16 - 1).to_le_bytes(),
+                                );
+                                listing.extend_from_slice(child.name().as_bytes());
+                            }
+                        }
+                        self.dirs.extend_from_slice(&listing);
+                        let offset = self.header(1);
+                        for value in [0u32, 2] {
+                            self.inodes.extend_from_slice(&value.to_le_bytes());
+                        }
+                        self.inodes
+                            .extend_from_slice(&(listing.len() as u16 + 3).to_le_bytes());
+                        self.inodes.extend_from_slice(&listing_offset.to_le_bytes());
+                        self.inodes.extend_from_slice(&0u32.to_le_bytes());
+                        (offset, 1)
+                    }
+                }
+            }
+        }
+
+        let root = Node::Dir("", tree.iter().map(clone_node).collect());
+        let (root_inode, _) = Builder {
+            data: &mut data,
+            inodes: &mut inodes,
+            dirs: &mut dirs,
+            fragment: &mut fragment,
+            inode_count: &mut inode_count,
+        }
+        .write(&root);
+
+        // One uncompressed fragment block, and the table pointing at its entry
+        let fragment_start = data.len() as u64;
+        let fragment = fragment.unwrap_or_default();
+        data.extend_from_slice(&fragment);
+        let mut entry = fragment_start.to_le_bytes().to_vec();
+        entry.extend_from_slice(&(fragment.len() as u32 | UNCOMPRESSED_BLOCK).to_le_bytes());
+        entry.extend_from_slice(&0u32.to_le_bytes());
+        let fragment_entries = data.len() as u64;
+        data.extend_from_slice(&metadata(&entry));
+
+        let inode_table = data.len() as u64;
+        data.extend_from_slice(&metadata(&inodes));
+        let directory_table = data.len() as u64;
+        data.extend_from_slice(&metadata(&dirs));
+        let fragment_table = data.len() as u64;
+        data.extend_from_slice(&fragment_entries.to_le_bytes());
+
+        let mut sb = Vec::new();
+        sb.extend_from_slice(SQUASHFS_MAGIC);
+        for value in [inode_count, 0, BLOCK_SIZE, 1] {
+            sb.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [1u16, 17, 0, 1, 4, 0] {
+            sb.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            root_inode as u64,
+            data.len() as u64,
+            u64::MAX,
+            u64::MAX,
+            inode_table,
+            directory_table,
+            fragment_table,
+            u64::MAX,
+        ] {
+            sb.extend_from_slice(&value.to_le_bytes());
+        }
+        data[..96].copy_from_slice(&sb);
+
+        let mut image = elf_header(2, 64);
+        image.extend_from_slice(&data);
+        image
+    }
+
+    fn clone_node(node: &Node) -> Node {
+        match node {
+            Node::File(name, content) => Node::File(name, content.clone()),
+            Node::Symlink(name, target) => Node::Symlink(name, target),
+            Node::Dir(name, children) => Node::Dir(name, children.iter().map(clone_node).collect()),
+        }
+    }
+
+    /// A type 1 AppImage: an ISO 9660 image with Rock Ridge names holding `tree`.
+    pub(crate) fn iso_appimage(tree: &[Node]) -> Vec<u8> {
+        fn record(location: u32, size: u32, is_dir: bool, iso_name: &[u8], rr: &[u8]) -> Vec<u8> {
+            let mut r = vec![0u8; 33];
+            r[2..6].copy_from_slice(&location.to_le_bytes());
+            r[6..10].copy_from_slice(&location.to_be_bytes());
+            r[10..14].copy_from_slice(&size.to_le_bytes());
+            r[14..18].copy_from_slice(&size.to_be_bytes());
+            r[25] = if is_dir { 2 } else { 0 };
+            r[32] = iso_name.len() as u8;
+            r.extend_from_slice(iso_name);
+            if iso_name.len() % 2 == 0 {
+                r.push(0);
+            }
+            r.extend_from_slice(rr);
+            if r.len() % 2 == 1 {
+                r.push(0);
+            }
+            r[0] = r.len() as u8;
+            r
+        }
+
+        fn rock_ridge(name: &str, target: Option<&str>) -> Vec<u8> {
+            let mut su = vec![b'N', b'M', 5 + name.len() as u8, 1, 0];
+            su.extend_from_slice(name.as_bytes());
+            if let Some(target) = target {
+                let mut components = Vec::new();
+                if target.starts_with('/') {
+                    components.extend_from_slice(&[8, 0]);
+                }
+                for part in target.split('/').filter(|p| !p.is_empty()) {
+                    components.extend_from_slice(&[0, part.len() as u8]);
+                    components.extend_from_slice(part.as_bytes());
+                }
+                su.extend_from_slice(&[b'S', b'L', 5 + components.len() as u8, 1, 0]);
+                su.extend_from_slice(&components);
+            }
+            su
+        }
+
+        fn sectors(len: usize) -> u32 {
+            len.div_ceil(SECTOR as usize).max(1) as u32
+        }
+
+        /// Write `children` as the directory at sector `location`, returning the
+        /// directory's size. Files and subdirectories go after `image`'s end.
+        fn write_dir(image: &mut Vec<u8>, location: u32, parent: u32, children: &[Node]) -> u32 {
+            let mut listing = record(location, SECTOR as u32, true, &[0], &[]);
+            listing.extend(record(parent, SECTOR as u32, true, &[1], &[]));
+            for (i, child) in children.iter().enumerate() {
+                let iso_name = format!("F{};1", i);
+                let next = (image.len() as u64 / SECTOR) as u32;
+                let rec = match child {
+                    Node::File(name, content) => {
+                        image.extend_from_slice(content);
+                        image.resize(
+                            ((next + sectors(content.len())) as u64 * SECTOR) as usize,
+                            0,
+                        );
+                        record(
+                            next,
+                            content.len() as u32,
+                            false,
+                            iso_name.as_bytes(),
+                            &rock_ridge(name, None),
+                        )
+                    }
+                    Node::Symlink(name, target) => record(
+                        0,
+                        0,
+                        false,
+                        iso_name.as_bytes(),
+                        &rock_ridge(name, Some(target)),
+                    ),
+                    Node::Dir(name, grandchildren) => {
+                        image.resize(((next + 1) as u64 * SECTOR) as usize, 0);
+                        let size = write_dir(image, next, location, grandchildren);
+                        record(
+                            next,
+                            size,
+                            true,
+                            format!("D{}", i).as_bytes(),
+                            &rock_ridge(name, None),
+                        )
+                    }
+                };
+                listing.extend(rec);
+            }
+            let start = (location as u64 * SECTOR) as usize;
+            image[start..start + listing.len()].copy_from_slice(&listing);
+            SECTOR as u32
+        }
+
+        // System area with the ELF header, volume descriptors, then the root directory
+        let mut image = vec![0u8; 19 * SECTOR as usize];
+        image[..64].copy_from_slice(&elf_header(1, 0));
+        write_dir(&mut image, 18, 18, tree);
+
+        let pvd = 16 * SECTOR as usize;
+        image[pvd] = 1;
+        image[pvd + 1..pvd + 6].copy_from_slice(b"CD001");
+        image[pvd + 6] = 1;
+        let root = record(18, SECTOR as u32, true, &[0], &[]);
+        image[pvd + 156..pvd + 190].copy_from_slice(&root);
+        let terminator = 17 * SECTOR as usize;
+        image[terminator] = 255;
+        image[terminator + 1..terminator + 6].copy_from_slice(b"CD001");
+        image
+    }
+
+    fn write_image(dir: &TempDir, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    const DESKTOP: &str = "[Desktop Entry]\nType=Application\nName=App\nExec=app %F\n";
+
+    fn assert_sample(image: &AppImage) {
+        let (name, content) = image.desktop_entry.as_ref().unwrap();
+        assert_eq!(name, "app.desktop");
+        assert_eq!(content, DESKTOP);
+        assert_eq!(image.icon.as_deref(), Some(&b"\x89PNG\r\n\x1a\nicon"[..]));
+        let appstream = image.appstream.as_ref().unwrap();
+        assert_eq!(appstream.name.as_deref(), Some("App"));
+        assert_eq!(appstream.version.as_deref(), Some("2.1.0"));
+        assert_eq!(appstream.categories, vec!["Office"]);
+    }
+
+    #[test]
+    fn test_read_type2_squashfs() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_image(
+            &tmp,
+            "App.AppImage",
+            &squashfs_appimage(&sample_tree(DESKTOP)),
+        );
+        let image = AppImage::read(&path).unwrap().unwrap();
+        assert_eq!(image.image_type, 2);
+        assert_sample(&image);
+    }
+
+    #[test]
+    fn test_read_type1_iso() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_image(&tmp, "App.AppImage", &iso_appimage(&sample_tree(DESKTOP)));
+        let image = AppImage::read(&path).unwrap().unwrap();
+        assert_eq!(image.image_type, 1);
+        assert_sample(&image);
+    }
+
+    #[test]
+    fn test_absolute_symlinks_stay_in_the_image() {
+        let tree = vec![
+            Node::Symlink(".DirIcon", "/usr/icon.svg"),
+            Node::Dir("usr", vec![Node::File("icon.svg", b"<svg/>".to_vec())]),
+        ];
+        let tmp = TempDir::new().unwrap();
+        let path = write_image(&tmp, "a", &squashfs_appimage(&tree));
+        let image = AppImage::read(&path).unwrap().unwrap();
+        assert_eq!(image.icon.as_deref(), Some(&b"<svg/>"[..]));
+        assert!(image.desktop_entry.is_none());
+        assert!(image.appstream.is_none());
+    }
+
+    #[test]
+    fn test_other_files_are_not_appimages() {
+        let tmp = TempDir::new().unwrap();
+        let script = write_image(&tmp, "script", b"#!/bin/sh\necho hi\n");
+        assert!(AppImage::read(&script).unwrap().is_none());
+        let elf = write_image(&tmp, "elf", &elf_header(0, 64));
+        assert!(AppImage::read(&elf).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corrupted_squashfs_fails_cleanly() {
+        let valid = squashfs_appimage(&sample_tree(DESKTOP));
+        let tmp = TempDir::new().unwrap();
+        let read = |bytes: &[u8]| {
+            let path = write_image(&tmp, "corrupt.AppImage", bytes);
+            let _ = AppImage::read(&path);
+        };
+
+        // Table offsets near the top of the range must not overflow
+        let superblock = 64;
+        for field in [32, 64, 72, 80] {
+            for value in [u64::MAX, u64::MAX - 1, 1 << 63, u64::MAX << 16] {
+                let mut image = valid.clone();
+                let at = superblock + field;
+                image[at..at + 8].copy_from_slice(&value.to_le_bytes());
+                read(&image);
+            }
+        }
+
+        // Random bytes anywhere in the ELF header, superblock and tables
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..500 {
+            let mut image = valid.clone();
+            for _ in 0..4 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let at = (state % image.len() as u64) as usize;
+                image[at] = (state >> 32) as u8;
+            }
+            read(&image);
+        }
+    }
+
+    #[test]
+    fn test_xz_output_is_bounded_while_decoding() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_image(&tmp, "a", &squashfs_appimage(&sample_tree(DESKTOP)));
+        let mut fs = SquashFs::open(File::open(&path).unwrap(), 64).unwrap();
+        fs.compressor = 4;
+
+        let data = vec![0u8; 4 * METADATA_SIZE];
+        let mut compressed = Vec::new();
+        lzma_rs::xz_compress(&mut data.as_slice(), &mut compressed).unwrap();
+        assert_eq!(fs.decompress(&compressed, data.len()).unwrap(), data);
+        assert!(fs.decompress(&compressed, METADATA_SIZE).is_err());
+    }
+
+    /// 64 copies of [`ZSTD_LINE`], compressed with `zstd -19 --no-check`.
+    const ZSTD_FIXTURE: &[u8] = &[
+        0x28, 0xb5, 0x2f, 0xfd, 0x60, 0x80, 0x0f, 0x35, 0x02, 0x00, 0x22, 0xc4,
+        0x0e, 0x11, 0x90, 0x7d, 0x20, 0xd9, 0x0e, 0x31, 0x34, 0xcb, 0xf5, 0x0b,
+        0x28, 0x79, 0x18, 0xea, 0xe9, 0x52, 0x02, 0x00, 0x89, 0xf0, 0x3a, 0xfe,
+        0x80, 0xaf, 0x3b, 0x79, 0x71, 0x1f, 0xf0, 0x3a, 0xaa, 0x00, 0x5f, 0x2f,
+        0x12, 0xab, 0x4b, 0xc0, 0x5e, 0xf9, 0xee, 0x49, 0x8a, 0x71, 0x21, 0x11,
+        0x73, 0x05, 0x1a, 0xcf, 0x6f, 0x4f, 0xc0, 0xf7, 0xa7, 0x8e, 0x86, 0x05,
+        0x01, 0x00, 0xc2, 0x0e, 0x14, 0x7d, 0x88, 0x02,
+    ];
+    const ZSTD_LINE: &str =
+        "[Desktop Entry]\nType=Application\nName=Zstd Test\nExec=zstd-test %F\n";
+
+    #[test]
+    fn test_zstd_decompression() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_image(&tmp, "a", &squashfs_appimage(&sample_tree(DESKTOP)));
+        let mut fs = SquashFs::open(File::open(&path).unwrap(), 64).unwrap();
+        fs.compressor = 6;
+
+        let expected = ZSTD_LINE.repeat(64);
+        assert_eq!(fs.decompress(ZSTD_FIXTURE, METADATA_SIZE).unwrap(), expected.as_bytes());
+        // Output stops at the limit
+        assert_eq!(fs.decompress(ZSTD_FIXTURE, 100).unwrap(), &expected.as_bytes()[..100]);
+
+        let mut corrupt = ZSTD_FIXTURE.to_vec();
+        corrupt[0] ^= 0xff;
+        assert!(fs.decompress(&corrupt, METADATA_SIZE).is_err());
+    }
+}
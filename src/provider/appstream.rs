@@ -0,0 +1,93 @@
+//! Reading the AppStream metainfo files applications ship, shared by the flatpak and
+//! standalone providers.
+
+/// The parts of an AppStream `<component>` appgrep reports.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AppStream {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    pub categories: Vec<String>,
+    /// Version of the newest `<release>`.
+    pub version: Option<String>,
+}
+
+/// Parse an AppStream metainfo file. `None` if it is not a `<component>`.
+pub fn parse_appstream(xml: &str) -> Option<AppStream> {
+    let doc = roxmltree::Document::parse(xml).ok()?;
+    let component = doc.root_element();
+    if !component.has_tag_name("component") {
+        return None;
+    }
+    let text = |node: roxmltree::Node| {
+        node.text()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+    };
+    // Translations carry an xml:lang attribute
+    let untranslated = |tag: &str| {
+        component
+            .children()
+            .filter(|n| n.has_tag_name(tag))
+            .find(|n| n.attribute((roxmltree::NS_XML_URI, "lang")).is_none())
+            .and_then(text)
+    };
+    let categories = component
+        .children()
+        .filter(|n| n.has_tag_name("categories"))
+        .flat_map(|n| n.children())
+        .filter(|n| n.has_tag_name("category"))
+        .filter_map(text)
+        .collect();
+    // Releases are listed newest first
+    let version = component
+        .children()
+        .filter(|n| n.has_tag_name("releases"))
+        .flat_map(|n| n.children())
+        .find(|n| n.has_tag_name("release"))
+        .and_then(|n| n.attribute("version"))
+        .map(str::to_string);
+
+    Some(AppStream {
+        id: untranslated("id"),
+        name: untranslated("name"),
+        summary: untranslated("summary"),
+        categories,
+        version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_appstream() {
+        let appstream = parse_appstream(
+            r#"<component><id>org.example.App</id><name xml:lang="fr">Appli</name><name>App</name>
+               <summary>An app</summary></component>"#,
+        )
+        .unwrap();
+        assert_eq!(appstream.id.as_deref(), Some("org.example.App"));
+        assert_eq!(appstream.name.as_deref(), Some("App"));
+        assert_eq!(appstream.summary.as_deref(), Some("An app"));
+        assert!(appstream.version.is_none());
+        assert!(parse_appstream("<components/>").is_none());
+        assert!(parse_appstream("not xml").is_none());
+    }
+
+    #[test]
+    fn test_parse_appstream_releases_and_entities() {
+        let appstream = parse_appstream(
+            "<?xml version=\"1.0\"?>\n<component type=\"desktop-application\">\n  \
+             <summary>Fast &amp; private</summary>\n  <categories><category>Network</category>\
+             </categories>\n  <releases>\n    <release version=\"128.0\" date=\"2024-07-09\"/>\n    \
+             <release version=\"127.0\"/>\n  </releases>\n</component>\n",
+        )
+        .unwrap();
+        assert_eq!(appstream.summary.as_deref(), Some("Fast & private"));
+        assert_eq!(appstream.categories, vec!["Network"]);
+        assert_eq!(appstream.version.as_deref(), Some("128.0"));
+    }
+}
@@ -2,8 +2,10 @@ use std::path::PathBuf;
 use std::process::Command;
 
 use crate::app::{AppSource, Application};
+use crate::provider::process::{self, has_command};
 use crate::provider::{AppProvider, ProviderError};
 
+#[derive(Default)]
 pub struct BrewProvider;
 
 impl BrewProvider {
@@ -12,20 +14,11 @@ impl BrewProvider {
     }
 
     fn has_brew() -> bool {
-        Command::new("which")
-            .arg("brew")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+        has_command("brew")
     }
 
     fn brew_prefix() -> Option<String> {
-        let output = Command::new("brew")
-            .arg("--prefix")
-            .output()
-            .ok()?;
+        let output = process::output(Command::new("brew").arg("--prefix")).ok()?;
 
         if output.status.success() {
             Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
@@ -84,10 +77,8 @@ impl AppProvider for BrewProvider {
         let bin_dir = PathBuf::from(&prefix).join("bin");
 
         // Try to get descriptions from brew info --json=v2 --installed
-        let json_output = Command::new("brew")
-            .args(["info", "--json=v2", "--installed"])
-            .output()
-            .ok();
+        let json_output =
+            process::output(Command::new("brew").args(["info", "--json=v2", "--installed"])).ok();
 
         let desc_map: std::collections::HashMap<String, Option<String>> =
             if let Some(ref out) = json_output {
@@ -104,10 +95,7 @@ impl AppProvider for BrewProvider {
             };
 
         // List installed formulae
-        let list_output = Command::new("brew")
-            .args(["list", "--formula"])
-            .output()
-            .map_err(ProviderError::Io)?;
+        let list_output = process::output(Command::new("brew").args(["list", "--formula"]))?;
 
         if !list_output.status.success() {
             return Ok(Vec::new());
@@ -135,14 +123,26 @@ impl AppProvider for BrewProvider {
                 exec_command: abs_path.clone(),
                 source: AppSource::Brew,
                 location: abs_path,
-                icon: None,
                 categories: vec!["Homebrew".to_string()],
                 description,
+                ..Default::default()
             });
         }
 
         Ok(apps)
     }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Some(prefix) = std::env::var_os("HOMEBREW_PREFIX") {
+            paths.push(PathBuf::from(prefix).join("Cellar"));
+        }
+        paths.push(PathBuf::from("/home/linuxbrew/.linuxbrew/Cellar"));
+        if let Some(home) = dirs::home_dir() {
+            paths.push(home.join(".linuxbrew/Cellar"));
+        }
+        paths
+    }
 }
 
 #[cfg(test)]
@@ -1,42 +1,261 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 
 use crate::app::{AppSource, Application};
 use crate::provider::{AppProvider, ProviderError};
 
+/// Binaries rustup installs as proxies for the active toolchain.
+const RUSTUP_PROXIES: &[&str] = &[
+    "cargo",
+    "cargo-clippy",
+    "cargo-fmt",
+    "cargo-miri",
+    "clippy-driver",
+    "rls",
+    "rust-analyzer",
+    "rust-gdb",
+    "rust-gdbgui",
+    "rust-lldb",
+    "rustc",
+    "rustdoc",
+    "rustfmt",
+    "rustup",
+];
+
+#[derive(Default)]
 pub struct CargoProvider;
 
+/// A crate recorded in cargo's install tracking files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateInstall {
+    pub name: String,
+    pub version: String,
+    /// Human-readable source: `crates.io`, a git URL or a local path.
+    pub source: String,
+    pub features: Vec<String>,
+    pub bins: Vec<String>,
+}
+
 impl CargoProvider {
     pub fn new() -> Self {
         Self
     }
 
-    fn cargo_bin_dir() -> Option<PathBuf> {
-        dirs::home_dir().map(|h| h.join(".cargo").join("bin"))
+    /// Install roots to scan: `$CARGO_INSTALL_ROOT` and `$CARGO_HOME` (default `~/.cargo`).
+    fn install_roots() -> Vec<PathBuf> {
+        Self::install_roots_from(
+            std::env::var_os("CARGO_INSTALL_ROOT"),
+            std::env::var_os("CARGO_HOME"),
+            dirs::home_dir(),
+        )
     }
-}
 
-impl AppProvider for CargoProvider {
-    fn name(&self) -> &str {
-        "cargo"
+    fn install_roots_from(
+        install_root: Option<OsString>,
+        cargo_home: Option<OsString>,
+        home: Option<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let mut roots = Vec::new();
+
+        if let Some(root) = install_root.filter(|r| !r.is_empty()) {
+            roots.push(PathBuf::from(root));
+        }
+
+        if let Some(cargo_home) = Self::cargo_home_from(cargo_home, home) {
+            if !roots.contains(&cargo_home) {
+                roots.push(cargo_home);
+            }
+        }
+
+        roots
     }
 
-    fn is_available(&self) -> bool {
-        true
+    /// `$CARGO_HOME` (default `~/.cargo`), which holds the registry cache whatever the
+    /// install root.
+    fn cargo_home() -> Option<PathBuf> {
+        Self::cargo_home_from(std::env::var_os("CARGO_HOME"), dirs::home_dir())
     }
 
-    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
-        let bin_dir = match Self::cargo_bin_dir() {
-            Some(d) => d,
-            None => return Ok(Vec::new()),
+    fn cargo_home_from(cargo_home: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+        match cargo_home.filter(|h| !h.is_empty()) {
+            Some(h) => Some(PathBuf::from(h)),
+            None => home.map(|h| h.join(".cargo")),
+        }
+    }
+
+    /// Split a package id like `ripgrep 14.1.0 (registry+https://...)`.
+    fn parse_package_id(id: &str) -> Option<(String, String, String)> {
+        let mut parts = id.splitn(3, ' ');
+        let name = parts.next()?.to_string();
+        let version = parts.next()?.to_string();
+        let source = parts
+            .next()
+            .and_then(|s| s.strip_prefix('('))
+            .and_then(|s| s.strip_suffix(')'))
+            .unwrap_or("");
+        if name.is_empty() || version.is_empty() {
+            return None;
+        }
+        Some((name, version, describe_source(source)))
+    }
+
+    /// Parse `.crates2.json`, which records bins, features and source per crate.
+    pub fn parse_crates2_json(json_str: &str) -> Vec<CrateInstall> {
+        let value: serde_json::Value = match serde_json::from_str(json_str) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
         };
 
-        if !bin_dir.is_dir() {
-            return Ok(Vec::new());
+        let installs = match value.get("installs").and_then(|v| v.as_object()) {
+            Some(i) => i,
+            None => return Vec::new(),
+        };
+
+        let mut crates = Vec::new();
+        for (id, info) in installs {
+            let (name, version, source) = match Self::parse_package_id(id) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            let bins = string_array(info.get("bins"));
+            let mut features = string_array(info.get("features"));
+            if info.get("all_features").and_then(|v| v.as_bool()) == Some(true) {
+                features = vec!["all".to_string()];
+            } else if info.get("no_default_features").and_then(|v| v.as_bool()) == Some(true) {
+                features.insert(0, "no-default".to_string());
+            }
+
+            crates.push(CrateInstall {
+                name,
+                version,
+                source,
+                features,
+                bins,
+            });
         }
 
-        let entries = fs::read_dir(&bin_dir).map_err(ProviderError::Io)?;
+        crates
+    }
+
+    /// Parse the older `.crates.toml`, which only maps package ids to bins.
+    pub fn parse_crates_toml(content: &str) -> Vec<CrateInstall> {
+        let value: toml::Value = match toml::from_str(content) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+
+        let v1 = match value.get("v1").and_then(|v| v.as_table()) {
+            Some(t) => t,
+            None => return Vec::new(),
+        };
+
+        let mut crates = Vec::new();
+        for (id, bins) in v1 {
+            let (name, version, source) = match Self::parse_package_id(id) {
+                Some(p) => p,
+                None => continue,
+            };
+            let bins = bins
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|b| b.as_str())
+                        .map(|b| b.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            crates.push(CrateInstall {
+                name,
+                version,
+                source,
+                features: Vec::new(),
+                bins,
+            });
+        }
+
+        crates
+    }
+
+    /// Read the install tracking files of a root, keyed by binary name.
+    fn read_installs(root: &Path) -> HashMap<String, CrateInstall> {
+        let mut crates = fs::read_to_string(root.join(".crates2.json"))
+            .map(|c| Self::parse_crates2_json(&c))
+            .unwrap_or_default();
+
+        // .crates.toml may list crates installed by cargo versions predating .crates2.json
+        let known: HashSet<String> = crates.iter().map(|c| c.name.clone()).collect();
+        if let Ok(content) = fs::read_to_string(root.join(".crates.toml")) {
+            crates.extend(
+                Self::parse_crates_toml(&content)
+                    .into_iter()
+                    .filter(|c| !known.contains(&c.name)),
+            );
+        }
+
+        let mut by_bin = HashMap::new();
+        for krate in crates {
+            for bin in &krate.bins {
+                by_bin.insert(bin.clone(), krate.clone());
+            }
+        }
+        by_bin
+    }
+
+    /// Read a crate's description from the registry source cache, if present.
+    fn registry_description(cargo_home: &Path, krate: &CrateInstall) -> Option<String> {
+        if krate.source != "crates.io" {
+            return None;
+        }
+
+        let src_dir = cargo_home.join("registry").join("src");
+        for index in fs::read_dir(src_dir).ok()?.flatten() {
+            let manifest = index
+                .path()
+                .join(format!("{}-{}", krate.name, krate.version))
+                .join("Cargo.toml");
+            if let Ok(content) = fs::read_to_string(&manifest) {
+                let value: toml::Value = toml::from_str(&content).ok()?;
+                return value
+                    .get("package")
+                    .and_then(|p| p.get("description"))
+                    .and_then(|d| d.as_str())
+                    .map(|d| d.split_whitespace().collect::<Vec<_>>().join(" "))
+                    .filter(|d| !d.is_empty());
+            }
+        }
+
+        None
+    }
+
+    /// Whether a binary is a rustup toolchain proxy rather than an installed crate.
+    fn is_rustup_proxy(bin_dir: &Path, name: &str, metadata: &fs::Metadata) -> bool {
+        if RUSTUP_PROXIES.contains(&name) {
+            return true;
+        }
+
+        // Proxies are hard links (or copies) of the rustup binary itself
+        match fs::metadata(bin_dir.join("rustup")) {
+            Ok(rustup) => rustup.dev() == metadata.dev() && rustup.ino() == metadata.ino(),
+            Err(_) => false,
+        }
+    }
+
+    /// Binaries installed under `root`, with descriptions from the registry cache in
+    /// `cargo_home`.
+    fn scan_root(root: &Path, cargo_home: Option<&Path>) -> Vec<Application> {
+        let bin_dir = root.join("bin");
+        let entries = match fs::read_dir(&bin_dir) {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+
+        let installs = Self::read_installs(root);
+        let mut descriptions: HashMap<String, Option<String>> = HashMap::new();
         let mut apps = Vec::new();
 
         for entry in entries.flatten() {
@@ -70,24 +289,123 @@ impl AppProvider for CargoProvider {
                 None => continue,
             };
 
+            let krate = installs.get(&name);
+            if krate.is_none() && Self::is_rustup_proxy(&bin_dir, &name, &metadata) {
+                continue;
+            }
+
             let abs_path = match path.canonicalize() {
                 Ok(p) => p.to_string_lossy().to_string(),
                 Err(_) => path.to_string_lossy().to_string(),
             };
 
+            let mut app_metadata = BTreeMap::new();
+            let mut description = None;
+            if let Some(krate) = krate {
+                app_metadata.insert("crate".to_string(), krate.name.clone());
+                app_metadata.insert("version".to_string(), krate.version.clone());
+                if !krate.source.is_empty() {
+                    app_metadata.insert("source".to_string(), krate.source.clone());
+                }
+                if !krate.features.is_empty() {
+                    app_metadata.insert("features".to_string(), krate.features.join(", "));
+                }
+                if krate.bins.len() > 1 {
+                    app_metadata.insert("binaries".to_string(), krate.bins.join(", "));
+                }
+                description = descriptions
+                    .entry(krate.name.clone())
+                    .or_insert_with(|| {
+                        cargo_home.and_then(|home| Self::registry_description(home, krate))
+                    })
+                    .clone();
+            }
+
             apps.push(Application {
                 name,
                 exec_command: abs_path.clone(),
                 source: AppSource::Cargo,
                 location: abs_path,
-                icon: None,
                 categories: vec!["Development".to_string()],
-                description: None,
+                description,
+                metadata: app_metadata,
+                ..Default::default()
             });
         }
 
+        apps
+    }
+}
+
+/// Collect a JSON array of strings, ignoring anything else.
+fn string_array(value: Option<&serde_json::Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|s| s.as_str())
+                .map(|s| s.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Turn a cargo source id into something readable.
+fn describe_source(source: &str) -> String {
+    if source == "registry+https://github.com/rust-lang/crates.io-index"
+        || source == "sparse+https://index.crates.io/"
+    {
+        return "crates.io".to_string();
+    }
+    if let Some(url) = source.strip_prefix("git+") {
+        return url.to_string();
+    }
+    if let Some(url) = source.strip_prefix("path+") {
+        return url.strip_prefix("file://").unwrap_or(url).to_string();
+    }
+    if let Some(url) = source
+        .strip_prefix("registry+")
+        .or_else(|| source.strip_prefix("sparse+"))
+    {
+        return format!("registry {}", url);
+    }
+    source.to_string()
+}
+
+impl AppProvider for CargoProvider {
+    fn name(&self) -> &str {
+        "cargo"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        let mut apps = Vec::new();
+        let mut seen = HashSet::new();
+        let cargo_home = Self::cargo_home();
+
+        for root in Self::install_roots() {
+            if !root.join("bin").is_dir() {
+                continue;
+            }
+            for app in Self::scan_root(&root, cargo_home.as_deref()) {
+                if seen.insert(app.exec_command.clone()) {
+                    apps.push(app);
+                }
+            }
+        }
+
         Ok(apps)
     }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        Self::install_roots()
+            .into_iter()
+            .flat_map(|root| [root.join("bin"), root.join(".crates2.json")])
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -96,6 +414,30 @@ mod tests {
     use std::os::unix::fs::PermissionsExt;
     use tempfile::TempDir;
 
+    const CRATES2: &str = r#"{
+        "installs": {
+            "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)": {
+                "version_req": null,
+                "bins": ["rg"],
+                "features": ["pcre2"],
+                "all_features": false,
+                "no_default_features": false,
+                "profile": "release"
+            },
+            "mytool 0.1.0 (git+https://github.com/me/mytool#abc123)": {
+                "bins": ["mytool", "mytool-helper"],
+                "features": [],
+                "all_features": false,
+                "no_default_features": false
+            }
+        }
+    }"#;
+
+    fn write_executable(path: &Path) {
+        fs::write(path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
     #[test]
     fn test_discover_empty_dir() {
         let provider = CargoProvider::new();
@@ -104,11 +446,156 @@ mod tests {
     }
 
     #[test]
-    fn test_cargo_bin_dir_returns_path() {
-        let dir = CargoProvider::cargo_bin_dir();
-        assert!(dir.is_some());
-        let path = dir.unwrap();
-        assert!(path.to_string_lossy().contains(".cargo/bin"));
+    fn test_install_roots_default() {
+        let roots =
+            CargoProvider::install_roots_from(None, None, Some(PathBuf::from("/home/me")));
+        assert_eq!(roots, vec![PathBuf::from("/home/me/.cargo")]);
+    }
+
+    #[test]
+    fn test_install_roots_from_env() {
+        let roots = CargoProvider::install_roots_from(
+            Some(OsString::from("/opt/rust-tools")),
+            Some(OsString::from("/srv/cargo")),
+            Some(PathBuf::from("/home/me")),
+        );
+        assert_eq!(
+            roots,
+            vec![PathBuf::from("/opt/rust-tools"), PathBuf::from("/srv/cargo")]
+        );
+    }
+
+    #[test]
+    fn test_parse_crates2_json() {
+        let mut crates = CargoProvider::parse_crates2_json(CRATES2);
+        crates.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(crates.len(), 2);
+
+        assert_eq!(crates[0].name, "mytool");
+        assert_eq!(crates[0].source, "https://github.com/me/mytool#abc123");
+        assert_eq!(crates[0].bins, vec!["mytool", "mytool-helper"]);
+
+        assert_eq!(crates[1].name, "ripgrep");
+        assert_eq!(crates[1].version, "14.1.0");
+        assert_eq!(crates[1].source, "crates.io");
+        assert_eq!(crates[1].features, vec!["pcre2"]);
+    }
+
+    #[test]
+    fn test_parse_crates2_json_invalid() {
+        assert!(CargoProvider::parse_crates2_json("not json").is_empty());
+        assert!(CargoProvider::parse_crates2_json("{}").is_empty());
+    }
+
+    #[test]
+    fn test_parse_crates_toml() {
+        let content = r#"[v1]
+"fd-find 9.0.0 (sparse+https://index.crates.io/)" = ["fd"]
+"local 0.1.0 (path+file:///home/me/src/local)" = ["local"]
+"#;
+        let mut crates = CargoProvider::parse_crates_toml(content);
+        crates.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(crates.len(), 2);
+        assert_eq!(crates[0].name, "fd-find");
+        assert_eq!(crates[0].source, "crates.io");
+        assert_eq!(crates[0].bins, vec!["fd"]);
+        assert_eq!(crates[1].source, "/home/me/src/local");
+    }
+
+    #[test]
+    fn test_describe_source() {
+        assert_eq!(
+            describe_source("registry+https://github.com/rust-lang/crates.io-index"),
+            "crates.io"
+        );
+        assert_eq!(
+            describe_source("sparse+https://my.registry/index/"),
+            "registry https://my.registry/index/"
+        );
+        assert_eq!(describe_source(""), "");
+    }
+
+    #[test]
+    fn test_scan_root_attributes_crates() {
+        let tmp = TempDir::new().unwrap();
+        let bin_dir = tmp.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(tmp.path().join(".crates2.json"), CRATES2).unwrap();
+
+        write_executable(&bin_dir.join("rg"));
+        write_executable(&bin_dir.join("mytool"));
+        write_executable(&bin_dir.join("mytool-helper"));
+        write_executable(&bin_dir.join("handmade"));
+        write_executable(&bin_dir.join("rustc"));
+
+        let manifest_dir = tmp
+            .path()
+            .join("registry/src/index.crates.io-6f17d22bba15001f/ripgrep-14.1.0");
+        fs::create_dir_all(&manifest_dir).unwrap();
+        fs::write(
+            manifest_dir.join("Cargo.toml"),
+            "[package]\nname = \"ripgrep\"\ndescription = \"\"\"\nripgrep is a line-oriented search tool.\n\"\"\"\n",
+        )
+        .unwrap();
+
+        let mut apps = CargoProvider::scan_root(tmp.path(), Some(tmp.path()));
+        apps.sort();
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["handmade", "mytool", "mytool-helper", "rg"]);
+
+        let rg = &apps[3];
+        assert_eq!(rg.metadata.get("crate").unwrap(), "ripgrep");
+        assert_eq!(rg.metadata.get("version").unwrap(), "14.1.0");
+        assert_eq!(rg.metadata.get("source").unwrap(), "crates.io");
+        assert_eq!(rg.metadata.get("features").unwrap(), "pcre2");
+        assert_eq!(
+            rg.description,
+            Some("ripgrep is a line-oriented search tool.".to_string())
+        );
+
+        let mytool = &apps[1];
+        assert_eq!(mytool.metadata.get("binaries").unwrap(), "mytool, mytool-helper");
+
+        // Binaries without install records keep working, just without metadata
+        assert!(apps[0].metadata.is_empty());
+    }
+
+    #[test]
+    fn test_scan_custom_install_root_reads_cargo_home_registry() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("tools");
+        let cargo_home = tmp.path().join("cargo");
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(root.join(".crates2.json"), CRATES2).unwrap();
+        write_executable(&root.join("bin/rg"));
+
+        let manifest_dir =
+            cargo_home.join("registry/src/index.crates.io-1949cf8c6b5b557f/ripgrep-14.1.0");
+        fs::create_dir_all(&manifest_dir).unwrap();
+        fs::write(
+            manifest_dir.join("Cargo.toml"),
+            "[package]\nname = \"ripgrep\"\ndescription = \"Fast search\"\n",
+        )
+        .unwrap();
+
+        let apps = CargoProvider::scan_root(&root, Some(&cargo_home));
+        assert_eq!(apps[0].description, Some("Fast search".to_string()));
+        assert_eq!(CargoProvider::scan_root(&root, Some(&root))[0].description, None);
+    }
+
+    #[test]
+    fn test_scan_root_skips_rustup_hardlinks() {
+        let tmp = TempDir::new().unwrap();
+        let bin_dir = tmp.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+
+        write_executable(&bin_dir.join("rustup"));
+        fs::hard_link(bin_dir.join("rustup"), bin_dir.join("cargo-nightly-proxy")).unwrap();
+        write_executable(&bin_dir.join("bat"));
+
+        let apps = CargoProvider::scan_root(tmp.path(), None);
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "bat");
     }
 
     #[test]
@@ -127,29 +614,7 @@ mod tests {
         fs::write(&noexec_path, "data").unwrap();
         fs::set_permissions(&noexec_path, fs::Permissions::from_mode(0o644)).unwrap();
 
-        // Scan the directory directly
-        let entries = fs::read_dir(&bin_dir).unwrap();
-        let mut apps = Vec::new();
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if !path.is_file() {
-                continue;
-            }
-            let metadata = fs::metadata(&path).unwrap();
-            if metadata.permissions().mode() & 0o111 == 0 {
-                continue;
-            }
-            let name = path.file_name().unwrap().to_str().unwrap().to_string();
-            apps.push(Application {
-                name,
-                exec_command: path.to_string_lossy().to_string(),
-                source: AppSource::Cargo,
-                location: path.to_string_lossy().to_string(),
-                icon: None,
-                categories: vec!["Development".to_string()],
-                description: None,
-            });
-        }
+        let apps = CargoProvider::scan_root(&tmp.path().join(".cargo"), None);
 
         assert_eq!(apps.len(), 1);
         assert_eq!(apps[0].name, "mytool");
@@ -166,11 +631,6 @@ mod tests {
         // Create a subdirectory (should be skipped)
         fs::create_dir_all(bin_dir.join("subdir")).unwrap();
 
-        let entries = fs::read_dir(&bin_dir).unwrap();
-        let files: Vec<_> = entries
-            .flatten()
-            .filter(|e| e.path().is_file())
-            .collect();
-        assert_eq!(files.len(), 0);
+        assert!(CargoProvider::scan_root(tmp.path(), None).is_empty());
     }
 }
@@ -1,21 +1,245 @@
 use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use configparser::ini::Ini;
 
-use crate::app::{AppSource, Application};
-use crate::provider::{AppProvider, ProviderError};
+use crate::app::{AppSource, Application, DesktopAction};
+use crate::exec;
+use crate::provider::{self, AppProvider, ProviderError};
+
+/// A POSIX locale, `lang_COUNTRY.ENCODING@MODIFIER`, as used to pick localized keys
+/// such as `Name[de_DE]`. The encoding plays no part in matching and is dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    pub lang: String,
+    pub country: Option<String>,
+    pub modifier: Option<String>,
+}
+
+impl Locale {
+    /// Parse a locale name. `None` for the untranslated `C` and `POSIX` locales.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (rest, modifier) = match name.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier.to_string())),
+            None => (name, None),
+        };
+        let rest = rest.split('.').next().unwrap_or_default();
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang, Some(country.to_string())),
+            None => (rest, None),
+        };
+        if lang.is_empty() || lang == "C" || lang == "POSIX" {
+            return None;
+        }
+        Some(Self {
+            lang: lang.to_string(),
+            country,
+            modifier,
+        })
+    }
+
+    /// The locale messages are shown in: the first of `$LC_ALL`, `$LC_MESSAGES` and
+    /// `$LANG` that is set.
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+    }
 
-pub struct DesktopProvider;
+    /// Suffixes of localized keys to look for, best match first: `lang_COUNTRY@MODIFIER`,
+    /// `lang_COUNTRY`, `lang@MODIFIER`, `lang`.
+    fn key_suffixes(&self) -> Vec<String> {
+        let mut suffixes = Vec::new();
+        if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+            suffixes.push(format!("{}_{}@{}", self.lang, country, modifier));
+        }
+        if let Some(country) = &self.country {
+            suffixes.push(format!("{}_{}", self.lang, country));
+        }
+        if let Some(modifier) = &self.modifier {
+            suffixes.push(format!("{}@{}", self.lang, modifier));
+        }
+        suffixes.push(self.lang.clone());
+        suffixes
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lang)?;
+        if let Some(country) = &self.country {
+            write!(f, "_{}", country)?;
+        }
+        if let Some(modifier) = &self.modifier {
+            write!(f, "@{}", modifier)?;
+        }
+        Ok(())
+    }
+}
+
+/// The environment desktop entries are evaluated in: what `TryExec`, `OnlyShowIn` and
+/// `NotShowIn` are checked against, and which translations are used.
+#[derive(Debug, Clone, Default)]
+pub struct ShowContext {
+    /// Directories searched for `TryExec` programs, separated like `$PATH`.
+    pub path: OsString,
+    /// Desktop names from `$XDG_CURRENT_DESKTOP`, most specific first.
+    pub current_desktops: Vec<String>,
+    /// Locale whose translations of `Name`, `GenericName`, `Comment` and `Keywords` are
+    /// used.
+    pub locale: Option<Locale>,
+}
+
+impl ShowContext {
+    /// `$PATH` and `$XDG_CURRENT_DESKTOP` of this process.
+    pub fn current() -> Self {
+        Self {
+            path: std::env::var_os("PATH").unwrap_or_default(),
+            current_desktops: std::env::var("XDG_CURRENT_DESKTOP")
+                .unwrap_or_default()
+                .split(':')
+                .filter(|d| !d.is_empty())
+                .map(str::to_string)
+                .collect(),
+            locale: Locale::from_env(),
+        }
+    }
+
+    /// Cache entry name for a provider evaluating desktop entries in this context. Show-in
+    /// lists and translations make the results differ per session.
+    pub fn cache_key(&self, provider: &str) -> String {
+        let mut key = provider.to_string();
+        let locale = self.locale.as_ref().map(Locale::to_string);
+        for part in self.current_desktops.iter().chain(&locale) {
+            key.push('-');
+            key.extend(
+                part.chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '@')
+                    .map(|c| c.to_ascii_lowercase()),
+            );
+        }
+        key
+    }
+
+    /// The value of `key`, translated for this context's locale when a translation
+    /// exists. Empty values count as missing.
+    fn localized(&self, config: &Ini, section: &str, key: &str) -> Option<String> {
+        let suffixes = self.locale.as_ref().map(Locale::key_suffixes).unwrap_or_default();
+        suffixes
+            .iter()
+            .filter_map(|suffix| config.get(section, &format!("{}[{}]", key, suffix)))
+            .chain(config.get(section, key))
+            .find(|value| !value.is_empty())
+    }
+
+    /// Why an entry with these keys should not be shown, if it should not.
+    fn hidden_reason(&self, config: &Ini, section: &str) -> Option<String> {
+        let is_true = |key: &str| {
+            config
+                .get(section, key)
+                .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+        };
+        if is_true("NoDisplay") {
+            return Some("NoDisplay=true".to_string());
+        }
+        if is_true("Hidden") {
+            return Some("Hidden=true".to_string());
+        }
+
+        if let Some(try_exec) = config.get(section, "TryExec").filter(|s| !s.is_empty()) {
+            let found = if try_exec.contains('/') {
+                provider::is_executable_file(Path::new(&try_exec))
+            } else {
+                provider::on_path(&try_exec, &self.path)
+            };
+            if !found {
+                return Some(format!("TryExec={} not found", try_exec));
+            }
+        }
+
+        let only_show_in = config.get(section, "OnlyShowIn").unwrap_or_default();
+        let not_show_in = config.get(section, "NotShowIn").unwrap_or_default();
+        let listed = |list: &str, desktop: &str| list.split(';').any(|d| d == desktop);
+        // The first current desktop named in either list decides
+        for desktop in &self.current_desktops {
+            if listed(&only_show_in, desktop) {
+                return None;
+            }
+            if listed(&not_show_in, desktop) {
+                return Some(format!(
+                    "NotShowIn={} (current desktop: {})",
+                    not_show_in,
+                    self.current_desktops.join(":")
+                ));
+            }
+        }
+        if !only_show_in.is_empty() {
+            let current = if self.current_desktops.is_empty() {
+                "none".to_string()
+            } else {
+                self.current_desktops.join(":")
+            };
+            return Some(format!(
+                "OnlyShowIn={} (current desktop: {})",
+                only_show_in, current
+            ));
+        }
+        None
+    }
+}
+
+pub struct DesktopProvider {
+    extra_dirs: Vec<PathBuf>,
+    show_hidden: bool,
+    context: ShowContext,
+}
+
+impl Default for DesktopProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl DesktopProvider {
     pub fn new() -> Self {
-        Self
+        Self {
+            extra_dirs: Vec::new(),
+            show_hidden: false,
+            context: ShowContext::current(),
+        }
+    }
+
+    /// Also scan `dirs` for .desktop files, e.g. from the user configuration.
+    pub fn with_extra_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.extra_dirs = dirs;
+        self
+    }
+
+    /// Also report entries that would not be shown, with `hidden_reason` set.
+    pub fn with_show_hidden(mut self, show_hidden: bool) -> Self {
+        self.show_hidden = show_hidden;
+        self
+    }
+
+    /// Use translations for `locale` instead of the one from the environment, or none.
+    pub fn with_locale(mut self, locale: Option<Locale>) -> Self {
+        self.context.locale = locale;
+        self
+    }
+
+    /// Evaluate `TryExec`, the show-in lists and translations against `context` instead
+    /// of this process's environment.
+    pub fn with_context(mut self, context: ShowContext) -> Self {
+        self.context = context;
+        self
     }
 
     /// Collect all XDG application directories.
-    fn app_dirs() -> Vec<PathBuf> {
+    pub fn default_app_dirs() -> Vec<PathBuf> {
         let mut dirs = Vec::new();
 
         // $XDG_DATA_HOME/applications/ (default ~/.local/share/applications/)
@@ -25,35 +249,108 @@ impl DesktopProvider {
             dirs.push(home.join(".local/share/applications"));
         }
 
-        // Standard system directories
-        dirs.push(PathBuf::from("/usr/share/applications"));
-        dirs.push(PathBuf::from("/usr/local/share/applications"));
-
-        // $XDG_DATA_DIRS entries
-        if let Ok(data_dirs) = std::env::var("XDG_DATA_DIRS") {
-            for dir in data_dirs.split(':') {
-                if !dir.is_empty() {
-                    let app_dir = PathBuf::from(dir).join("applications");
-                    if !dirs.contains(&app_dir) {
-                        dirs.push(app_dir);
-                    }
-                }
+        // $XDG_DATA_DIRS entries in order, since earlier ones mask later ones, then the
+        // standard system directories in case it leaves them out
+        let data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_default();
+        for dir in data_dirs
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .chain(["/usr/local/share", "/usr/share"])
+        {
+            let app_dir = PathBuf::from(dir).join("applications");
+            if !dirs.contains(&app_dir) {
+                dirs.push(app_dir);
             }
         }
 
         dirs
     }
 
+    /// XDG application directories followed by configured extras.
+    fn app_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = Self::default_app_dirs();
+        for dir in &self.extra_dirs {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+
+    /// Scan `dirs` recursively, highest precedence first.
+    fn discover_in(&self, dirs: &[PathBuf]) -> Vec<Application> {
+        let mut apps = Vec::new();
+        // The first file with a given desktop ID masks the rest, even when it is
+        // hidden or not an application
+        let mut seen_ids = HashSet::new();
+
+        for dir in dirs {
+            if !dir.is_dir() {
+                continue;
+            }
+
+            let mut files = Vec::new();
+            if let Err(e) = walk_app_dir(dir, 0, &mut files, &mut Vec::new()) {
+                eprintln!("appgrep: warning: cannot read {}: {}", dir.display(), e);
+                continue;
+            }
+
+            for path in files {
+                let id = match desktop_id(dir, &path) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                if !seen_ids.insert(id.clone()) {
+                    continue;
+                }
+
+                match self.parse_desktop_file(&path) {
+                    Ok(Some(mut app)) if self.show_hidden || app.hidden_reason.is_none() => {
+                        app.metadata.insert("desktop_id".to_string(), id);
+                        apps.push(app)
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("appgrep: warning: {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+        apps
+    }
+
     /// Parse a single .desktop file into an Application.
-    fn parse_desktop_file(path: &Path) -> Result<Option<Application>, ProviderError> {
+    fn parse_desktop_file(&self, path: &Path) -> Result<Option<Application>, ProviderError> {
         let content = fs::read_to_string(path).map_err(ProviderError::Io)?;
-        Self::parse_desktop_content(&content, path)
+        Self::parse_desktop_entry(&content, path, &self.context)
     }
 
-    /// Parse desktop entry content string into an Application.
+    /// Parse desktop entry content string into an Application, leaving out entries that
+    /// should not be shown in the current environment.
     pub fn parse_desktop_content(
         content: &str,
         path: &Path,
+    ) -> Result<Option<Application>, ProviderError> {
+        Self::parse_desktop_content_in(content, path, &ShowContext::current())
+    }
+
+    /// Parse desktop entry content string into an Application, leaving out entries that
+    /// should not be shown in `context`.
+    pub fn parse_desktop_content_in(
+        content: &str,
+        path: &Path,
+        context: &ShowContext,
+    ) -> Result<Option<Application>, ProviderError> {
+        let app = Self::parse_desktop_entry(content, path, context)?;
+        Ok(app.filter(|a| a.hidden_reason.is_none()))
+    }
+
+    /// Parse desktop entry content string into an Application. Entries that should not
+    /// be shown in `context` are returned with `hidden_reason` set.
+    pub fn parse_desktop_entry(
+        content: &str,
+        path: &Path,
+        context: &ShowContext,
     ) -> Result<Option<Application>, ProviderError> {
         let mut config = Ini::new_cs();
         config.set_comment_symbols(&['#']);
@@ -69,22 +366,12 @@ impl DesktopProvider {
             return Ok(None);
         }
 
-        // Check NoDisplay and Hidden
-        let no_display = config.get(section, "NoDisplay").unwrap_or_default();
-        if no_display.to_lowercase() == "true" {
-            return Ok(None);
-        }
-
-        let hidden = config.get(section, "Hidden").unwrap_or_default();
-        if hidden.to_lowercase() == "true" {
-            return Ok(None);
-        }
-
         // Name is required
-        let name = match config.get(section, "Name") {
-            Some(n) if !n.is_empty() => n,
-            _ => return Ok(None),
+        let name = match context.localized(&config, section, "Name") {
+            Some(n) => n,
+            None => return Ok(None),
         };
+        let untranslated_name = config.get(section, "Name").filter(|n| *n != name);
 
         // Exec is required
         let exec_raw = match config.get(section, "Exec") {
@@ -92,7 +379,10 @@ impl DesktopProvider {
             _ => return Ok(None),
         };
 
-        let exec_command = strip_field_codes(&exec_raw);
+        let exec_value = exec::unescape_value(&exec_raw);
+        let exec_args = exec::split(&exec_value)
+            .map_err(|e| ProviderError::ParseError(format!("{}: Exec: {}", path.display(), e)))?;
+        let exec_command = strip_field_codes(&exec_value);
 
         let icon = config.get(section, "Icon").filter(|s| !s.is_empty());
 
@@ -106,7 +396,24 @@ impl DesktopProvider {
             })
             .unwrap_or_default();
 
-        let description = config.get(section, "Comment").filter(|s| !s.is_empty());
+        let description = context.localized(&config, section, "Comment");
+        let generic_name = context.localized(&config, section, "GenericName");
+        let keywords = context
+            .localized(&config, section, "Keywords")
+            .map(|k| {
+                k.split(';')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let terminal = config
+            .get(section, "Terminal")
+            .is_some_and(|t| t.eq_ignore_ascii_case("true"));
+
+        let actions = Self::parse_actions(&config, path, context)?;
+        let hidden_reason = context.hidden_reason(&config, section);
 
         Ok(Some(Application {
             name,
@@ -116,8 +423,88 @@ impl DesktopProvider {
             icon,
             categories,
             description,
+            exec_args,
+            terminal,
+            actions,
+            hidden_reason,
+            generic_name,
+            untranslated_name,
+            keywords,
+            ..Default::default()
         }))
     }
+
+    /// Parse the `[Desktop Action <id>]` groups listed in `Actions=`. Actions without a
+    /// group, Name or Exec are skipped.
+    fn parse_actions(
+        config: &Ini,
+        path: &Path,
+        context: &ShowContext,
+    ) -> Result<Vec<DesktopAction>, ProviderError> {
+        let ids = config.get("Desktop Entry", "Actions").unwrap_or_default();
+        let mut actions = Vec::new();
+        for id in ids.split(';').map(str::trim).filter(|id| !id.is_empty()) {
+            let section = format!("Desktop Action {}", id);
+            let name = match context.localized(config, &section, "Name") {
+                Some(n) => n,
+                None => continue,
+            };
+            let exec_raw = match config.get(&section, "Exec") {
+                Some(e) if !e.is_empty() => e,
+                _ => continue,
+            };
+            let exec_args = exec::split(&exec::unescape_value(&exec_raw)).map_err(|e| {
+                ProviderError::ParseError(format!("{}: {}: Exec: {}", path.display(), section, e))
+            })?;
+            actions.push(DesktopAction {
+                id: id.to_string(),
+                name,
+                icon: config.get(&section, "Icon").filter(|s| !s.is_empty()),
+                exec_args,
+            });
+        }
+        Ok(actions)
+    }
+}
+
+/// How deep subdirectories of an applications directory are scanned.
+const MAX_DEPTH: usize = 4;
+
+/// Collect the .desktop files below `dir` into `files` and its subdirectories into
+/// `subdirs`, each directory's entries in name order.
+fn walk_app_dir(
+    dir: &Path,
+    depth: usize,
+    files: &mut Vec<PathBuf>,
+    subdirs: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                subdirs.push(path.clone());
+                let _ = walk_app_dir(&path, depth + 1, files, subdirs);
+            }
+        } else if path.extension().and_then(|e| e.to_str()) == Some("desktop") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The desktop file ID of `path` below the applications directory `base`: its relative
+/// path with `/` replaced by `-`, e.g. `kde4-okular.desktop` for `kde4/okular.desktop`.
+pub fn desktop_id(base: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(base).ok()?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("-"))
 }
 
 /// Strip XDG field codes from an Exec string.
@@ -143,45 +530,26 @@ impl AppProvider for DesktopProvider {
     }
 
     fn discover(&self) -> Result<Vec<Application>, ProviderError> {
-        let mut apps = Vec::new();
-        let mut seen_paths = HashSet::new();
-
-        for dir in Self::app_dirs() {
-            if !dir.is_dir() {
-                continue;
-            }
-
-            let entries = match fs::read_dir(&dir) {
-                Ok(entries) => entries,
-                Err(e) => {
-                    eprintln!("appgrep: warning: cannot read {}: {}", dir.display(), e);
-                    continue;
-                }
-            };
-
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
-                    continue;
-                }
-
-                // Skip duplicates by canonical path
-                let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
-                if !seen_paths.insert(canonical) {
-                    continue;
-                }
+        Ok(self.discover_in(&self.app_dirs()))
+    }
 
-                match Self::parse_desktop_file(&path) {
-                    Ok(Some(app)) => apps.push(app),
-                    Ok(None) => {}
-                    Err(e) => {
-                        eprintln!("appgrep: warning: {}: {}", path.display(), e);
-                    }
-                }
-            }
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for dir in self.app_dirs() {
+            paths.push(dir.clone());
+            let _ = walk_app_dir(&dir, 0, &mut Vec::new(), &mut paths);
         }
+        // TryExec programs appearing or disappearing changes the results too
+        paths.extend(std::env::split_paths(&self.context.path));
+        paths
+    }
 
-        Ok(apps)
+    fn cache_key(&self) -> String {
+        if self.show_hidden {
+            self.context.cache_key("desktop-all")
+        } else {
+            self.context.cache_key(self.name())
+        }
     }
 }
 
@@ -238,6 +606,215 @@ Hidden=true
         assert!(result.is_none());
     }
 
+    fn entry_with(keys: &str) -> String {
+        format!("[Desktop Entry]\nType=Application\nName=App\nExec=app\n{}", keys)
+    }
+
+    fn reason(keys: &str, context: &ShowContext) -> Option<String> {
+        let path = PathBuf::from("/test/app.desktop");
+        DesktopProvider::parse_desktop_entry(&entry_with(keys), &path, context)
+            .unwrap()
+            .unwrap()
+            .hidden_reason
+    }
+
+    fn desktops(names: &[&str]) -> ShowContext {
+        ShowContext {
+            current_desktops: names.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hidden_reason_no_display() {
+        let context = ShowContext::default();
+        assert_eq!(reason("", &context), None);
+        assert_eq!(reason("NoDisplay=true\n", &context).unwrap(), "NoDisplay=true");
+        assert_eq!(reason("Hidden=True\n", &context).unwrap(), "Hidden=true");
+    }
+
+    #[test]
+    fn test_hidden_reason_try_exec() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let context = ShowContext {
+            path: tmp.path().as_os_str().to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(
+            reason("TryExec=mytool\n", &context).unwrap(),
+            "TryExec=mytool not found"
+        );
+
+        let tool = tmp.path().join("mytool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(reason("TryExec=mytool\n", &context), None);
+        let absolute = format!("TryExec={}\n", tool.display());
+        assert_eq!(reason(&absolute, &ShowContext::default()), None);
+    }
+
+    #[test]
+    fn test_hidden_reason_show_in() {
+        let only_gnome = "OnlyShowIn=GNOME;Unity;\n";
+        assert_eq!(reason(only_gnome, &desktops(&["GNOME"])), None);
+        assert_eq!(reason(only_gnome, &desktops(&["ubuntu", "GNOME"])), None);
+        assert_eq!(
+            reason(only_gnome, &desktops(&["KDE"])).unwrap(),
+            "OnlyShowIn=GNOME;Unity; (current desktop: KDE)"
+        );
+        assert_eq!(
+            reason(only_gnome, &desktops(&[])).unwrap(),
+            "OnlyShowIn=GNOME;Unity; (current desktop: none)"
+        );
+
+        let not_kde = "NotShowIn=KDE;\n";
+        assert_eq!(reason(not_kde, &desktops(&[])), None);
+        assert_eq!(reason(not_kde, &desktops(&["GNOME"])), None);
+        assert_eq!(
+            reason(not_kde, &desktops(&["KDE"])).unwrap(),
+            "NotShowIn=KDE; (current desktop: KDE)"
+        );
+
+        // The first current desktop named in either list decides
+        let both = "OnlyShowIn=GNOME;\nNotShowIn=KDE;\n";
+        assert_eq!(reason(both, &desktops(&["GNOME", "KDE"])), None);
+        assert!(reason(both, &desktops(&["KDE", "GNOME"])).is_some());
+    }
+
+    #[test]
+    fn test_cache_key_depends_on_options() {
+        let provider = DesktopProvider::new().with_context(desktops(&[]));
+        assert_eq!(provider.cache_key(), "desktop");
+        let provider = DesktopProvider::new()
+            .with_show_hidden(true)
+            .with_context(desktops(&["X-Cinnamon", "GNOME"]));
+        assert_eq!(provider.cache_key(), "desktop-all-xcinnamon-gnome");
+    }
+
+    #[test]
+    fn test_desktop_id() {
+        let base = Path::new("/usr/share/applications");
+        assert_eq!(
+            desktop_id(base, &base.join("firefox.desktop")).unwrap(),
+            "firefox.desktop"
+        );
+        assert_eq!(
+            desktop_id(base, &base.join("kde4/okular.desktop")).unwrap(),
+            "kde4-okular.desktop"
+        );
+        assert_eq!(desktop_id(base, Path::new("/elsewhere/x.desktop")), None);
+    }
+
+    #[test]
+    fn test_discover_masks_by_desktop_id() {
+        let user = tempfile::TempDir::new().unwrap();
+        let system = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(system.path().join("kde4")).unwrap();
+        let write = |dir: &Path, file: &str, keys: &str| {
+            let name = file.trim_end_matches(".desktop").replace('/', " ");
+            let content = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=app\n{}",
+                name, keys
+            );
+            fs::write(dir.join(file), content).unwrap();
+        };
+        write(user.path(), "editor.desktop", "Comment=user copy\n");
+        write(system.path(), "editor.desktop", "Comment=system copy\n");
+        write(user.path(), "removed.desktop", "Hidden=true\n");
+        write(system.path(), "removed.desktop", "");
+        write(system.path(), "kde4/okular.desktop", "");
+
+        let dirs = [user.path().to_path_buf(), system.path().to_path_buf()];
+        let provider = DesktopProvider::new().with_context(ShowContext::default());
+        let apps = provider.discover_in(&dirs);
+        let mut names: Vec<_> = apps.iter().map(|a| a.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["editor", "kde4 okular"]);
+        let editor = apps.iter().find(|a| a.name == "editor").unwrap();
+        assert_eq!(editor.description.as_deref(), Some("user copy"));
+        let okular = apps.iter().find(|a| a.name == "kde4 okular").unwrap();
+        assert_eq!(okular.metadata["desktop_id"], "kde4-okular.desktop");
+
+        // With --show-hidden the masking entry itself is listed, not the system one
+        let apps = provider.with_show_hidden(true).discover_in(&dirs);
+        let removed: Vec<_> = apps.iter().filter(|a| a.name == "removed").collect();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].hidden_reason.as_deref(), Some("Hidden=true"));
+    }
+
+    #[test]
+    fn test_locale_parse() {
+        let locale = Locale::parse("sr_RS.UTF-8@latin").unwrap();
+        assert_eq!(locale.lang, "sr");
+        assert_eq!(locale.country.as_deref(), Some("RS"));
+        assert_eq!(locale.modifier.as_deref(), Some("latin"));
+        assert_eq!(locale.to_string(), "sr_RS@latin");
+        assert_eq!(
+            locale.key_suffixes(),
+            vec!["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+        assert_eq!(Locale::parse("de").unwrap().key_suffixes(), vec!["de"]);
+        assert_eq!(Locale::parse("C"), None);
+        assert_eq!(Locale::parse("C.UTF-8"), None);
+        assert_eq!(Locale::parse("POSIX"), None);
+    }
+
+    #[test]
+    fn test_localized_keys() {
+        let content = r#"[Desktop Entry]
+Type=Application
+Name=Files
+Name[de]=Dateien
+Name[fr_CA]=Fichiers (CA)
+Name[fr]=Fichiers
+Name[sr@latin]=Datoteke
+GenericName=File Manager
+GenericName[de]=Dateiverwaltung
+Comment=Access and organize files
+Comment[de]=
+Keywords=folder;manager;
+Keywords[de]=Ordner;Verwaltung;
+Exec=nautilus
+Actions=new-window;
+
+[Desktop Action new-window]
+Name=New Window
+Name[de]=Neues Fenster
+Exec=nautilus --new-window
+"#;
+        let path = PathBuf::from("/test/files.desktop");
+        let parse = |locale: &str| {
+            let context = ShowContext {
+                locale: Locale::parse(locale),
+                ..Default::default()
+            };
+            DesktopProvider::parse_desktop_entry(content, &path, &context)
+                .unwrap()
+                .unwrap()
+        };
+
+        let app = parse("de_DE.UTF-8");
+        assert_eq!(app.name, "Dateien");
+        assert_eq!(app.untranslated_name.as_deref(), Some("Files"));
+        assert_eq!(app.generic_name.as_deref(), Some("Dateiverwaltung"));
+        // An empty translation falls back to the untranslated value
+        assert_eq!(app.description.as_deref(), Some("Access and organize files"));
+        assert_eq!(app.actions[0].name, "Neues Fenster");
+        assert_eq!(app.keywords, vec!["Ordner", "Verwaltung"]);
+
+        assert_eq!(parse("fr_CA").name, "Fichiers (CA)");
+        assert_eq!(parse("fr_BE").name, "Fichiers");
+        assert_eq!(parse("sr_RS@latin").name, "Datoteke");
+
+        let app = parse("C");
+        assert_eq!(app.name, "Files");
+        assert_eq!(app.untranslated_name, None);
+        assert_eq!(app.generic_name.as_deref(), Some("File Manager"));
+        assert_eq!(app.keywords, vec!["folder", "manager"]);
+    }
+
     #[test]
     fn test_skip_non_application_type() {
         let content = r#"[Desktop Entry]
@@ -250,6 +827,77 @@ Exec=/usr/bin/something
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_exec_args_keep_quoting_and_field_codes() {
+        let content = r#"[Desktop Entry]
+Type=Application
+Name=My App
+Exec="/opt/My App/app" --title="say \\"hi\\"" %F
+"#;
+        let path = PathBuf::from("/test/myapp.desktop");
+        let result = DesktopProvider::parse_desktop_content(content, &path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.exec_args, vec!["/opt/My App/app", r#"--title=say "hi""#, "%F"]);
+        assert_eq!(result.exec_command, r#""/opt/My App/app" --title="say \"hi\"""#);
+    }
+
+    #[test]
+    fn test_terminal_key() {
+        let path = PathBuf::from("/test/htop.desktop");
+        let content = "[Desktop Entry]\nType=Application\nName=htop\nExec=htop\nTerminal=true\n";
+        let result = DesktopProvider::parse_desktop_content(content, &path)
+            .unwrap()
+            .unwrap();
+        assert!(result.terminal);
+
+        let content = "[Desktop Entry]\nType=Application\nName=htop\nExec=htop\nTerminal=false\n";
+        let result = DesktopProvider::parse_desktop_content(content, &path)
+            .unwrap()
+            .unwrap();
+        assert!(!result.terminal);
+    }
+
+    #[test]
+    fn test_actions() {
+        let content = r#"[Desktop Entry]
+Type=Application
+Name=Firefox
+Exec=firefox %u
+Actions=new-window;new-private-window;missing;no-exec;
+
+[Desktop Action new-window]
+Name=New Window
+Exec=firefox --new-window %u
+
+[Desktop Action new-private-window]
+Name=New Private Window
+Exec=firefox --private-window %u
+Icon=firefox-private
+
+[Desktop Action no-exec]
+Name=D-Bus Only
+"#;
+        let path = PathBuf::from("/usr/share/applications/firefox.desktop");
+        let app = DesktopProvider::parse_desktop_content(content, &path)
+            .unwrap()
+            .unwrap();
+        let ids: Vec<_> = app.actions.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, vec!["new-window", "new-private-window"]);
+        let private = app.action("new-private-window").unwrap();
+        assert_eq!(private.name, "New Private Window");
+        assert_eq!(private.icon.as_deref(), Some("firefox-private"));
+        assert_eq!(private.exec_args, vec!["firefox", "--private-window", "%u"]);
+        assert!(app.action("missing").is_none());
+    }
+
+    #[test]
+    fn test_invalid_exec_is_an_error() {
+        let content = "[Desktop Entry]\nType=Application\nName=Broken\nExec=\"/opt/app\n";
+        let path = PathBuf::from("/test/broken.desktop");
+        assert!(DesktopProvider::parse_desktop_content(content, &path).is_err());
+    }
+
     #[test]
     fn test_strip_field_codes() {
         assert_eq!(strip_field_codes("/usr/bin/app %u"), "/usr/bin/app");
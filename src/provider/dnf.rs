@@ -1,45 +1,46 @@
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use crate::app::{AppSource, Application};
-use crate::provider::{AppProvider, ProviderError};
+use crate::provider::process::{self, has_command};
+use crate::provider::{
+    package_bin_dirs, select_package_binaries, ships_desktop_file, AppProvider, BinaryMode,
+    ProviderError,
+};
+
+pub struct RpmProvider {
+    binary_mode: BinaryMode,
+    bin_dirs: Vec<PathBuf>,
+}
 
-pub struct RpmProvider;
+impl Default for RpmProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl RpmProvider {
     pub fn new() -> Self {
-        Self
+        Self {
+            binary_mode: BinaryMode::default(),
+            bin_dirs: package_bin_dirs(),
+        }
+    }
+
+    pub fn with_binary_mode(mut self, binary_mode: BinaryMode) -> Self {
+        self.binary_mode = binary_mode;
+        self
+    }
+
+    /// Report executables from `bin_dirs` instead of the standard system directories.
+    pub fn with_bin_dirs(mut self, bin_dirs: Vec<PathBuf>) -> Self {
+        self.bin_dirs = bin_dirs;
+        self
     }
 
     fn has_rpm() -> bool {
-        Command::new("which")
-            .arg("rpm")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
-    }
-
-    fn has_desktop_file(pkg: &str) -> bool {
-        let apps_dir = Path::new("/usr/share/applications");
-        if !apps_dir.is_dir() {
-            return false;
-        }
-        if apps_dir.join(format!("{}.desktop", pkg)).exists() {
-            return true;
-        }
-        if let Ok(entries) = std::fs::read_dir(apps_dir) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if name.starts_with(pkg) && name.ends_with(".desktop") {
-                        return true;
-                    }
-                }
-            }
-        }
-        false
+        has_command("rpm")
     }
 
     pub fn parse_rpm_output(output: &str) -> Vec<(String, Option<String>)> {
@@ -60,28 +61,32 @@ impl RpmProvider {
         packages
     }
 
-    fn find_package_binary(pkg: &str) -> Option<String> {
-        let output = Command::new("rpm")
-            .args(["-ql", pkg])
-            .output()
-            .ok()?;
-
-        if !output.status.success() {
-            return None;
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        for line in stdout.lines() {
-            let line = line.trim();
-            if (line.starts_with("/usr/bin/") || line.starts_with("/usr/local/bin/")
-                || line.starts_with("/usr/sbin/"))
-                && Path::new(line).is_file()
-            {
-                return Some(line.to_string());
+    /// Parse `rpm -qa --queryformat "[%{NAME}\t%{FILENAMES}\n]"` into per-package file lists.
+    pub fn parse_rpm_file_list(output: &str) -> HashMap<String, Vec<String>> {
+        let mut files: HashMap<String, Vec<String>> = HashMap::new();
+        for line in output.lines() {
+            if let Some((name, path)) = line.split_once('\t') {
+                let path = path.trim();
+                if !name.is_empty() && !path.is_empty() {
+                    files.entry(name.to_string()).or_default().push(path.to_string());
+                }
             }
         }
+        files
+    }
 
-        None
+    fn package_files() -> HashMap<String, Vec<String>> {
+        let output = match process::output(Command::new("rpm").args([
+            "-qa",
+            "--queryformat",
+            "[%{NAME}\\t%{FILENAMES}\\n]",
+        ])) {
+            Ok(o) if o.status.success() => o,
+            _ => return HashMap::new(),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse_rpm_file_list(&stdout)
     }
 }
 
@@ -99,10 +104,11 @@ impl AppProvider for RpmProvider {
             return Ok(Vec::new());
         }
 
-        let output = Command::new("rpm")
-            .args(["-qa", "--queryformat", "%{NAME}\\t%{SUMMARY}\\n"])
-            .output()
-            .map_err(ProviderError::Io)?;
+        let output = process::output(Command::new("rpm").args([
+            "-qa",
+            "--queryformat",
+            "%{NAME}\\t%{SUMMARY}\\n",
+        ]))?;
 
         if !output.status.success() {
             return Ok(Vec::new());
@@ -110,16 +116,24 @@ impl AppProvider for RpmProvider {
 
         let stdout = String::from_utf8_lossy(&output.stdout);
         let packages = Self::parse_rpm_output(&stdout);
+        let package_files = Self::package_files();
 
         let mut seen_binaries = HashSet::new();
         let mut apps = Vec::new();
 
         for (pkg_name, description) in packages {
-            if Self::has_desktop_file(&pkg_name) {
+            let files = match package_files.get(&pkg_name) {
+                Some(f) => f,
+                None => continue,
+            };
+
+            if self.binary_mode == BinaryMode::Primary && ships_desktop_file(files) {
                 continue;
             }
 
-            if let Some(binary) = Self::find_package_binary(&pkg_name) {
+            let binaries =
+                select_package_binaries(&pkg_name, files, self.binary_mode, &self.bin_dirs);
+            for binary in binaries {
                 if !seen_binaries.insert(binary.clone()) {
                     continue;
                 }
@@ -135,15 +149,27 @@ impl AppProvider for RpmProvider {
                     exec_command: binary.clone(),
                     source: AppSource::Rpm,
                     location: binary,
-                    icon: None,
                     categories: vec!["CLI".to_string()],
-                    description,
+                    description: description.clone(),
+                    metadata: BTreeMap::from([("package".to_string(), pkg_name.clone())]),
+                    ..Default::default()
                 });
             }
         }
 
         Ok(apps)
     }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("/var/lib/rpm"),
+            PathBuf::from("/usr/lib/sysimage/rpm"),
+        ]
+    }
+
+    fn cache_key(&self) -> String {
+        self.binary_mode.cache_key(self.name())
+    }
 }
 
 #[cfg(test)]
@@ -180,6 +206,15 @@ mod tests {
         assert_eq!(packages[0].1, None);
     }
 
+    #[test]
+    fn test_parse_rpm_file_list() {
+        let output = "coreutils\t/usr/bin/ls\ncoreutils\t/usr/bin/cp\nbash\t/usr/bin/bash\n\nbroken line\n";
+        let files = RpmProvider::parse_rpm_file_list(output);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files["coreutils"], vec!["/usr/bin/ls", "/usr/bin/cp"]);
+        assert_eq!(files["bash"], vec!["/usr/bin/bash"]);
+    }
+
     #[test]
     fn test_parse_rpm_output_malformed() {
         let output = "\n   \n\n";
@@ -1,149 +1,191 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 
 use crate::app::{AppSource, Application};
-use crate::provider::{AppProvider, ProviderError};
+use crate::provider::{
+    package_bin_dirs, select_package_binaries, ships_desktop_file, AppProvider, BinaryMode,
+    ProviderError,
+};
 
-pub struct DpkgProvider;
+const STATUS_FILE: &str = "/var/lib/dpkg/status";
+const INFO_DIR: &str = "/var/lib/dpkg/info";
+
+/// An installed package from the dpkg status database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DpkgPackage {
+    pub name: String,
+    pub version: Option<String>,
+    pub architecture: Option<String>,
+    /// Installed size in KiB, as recorded by dpkg.
+    pub installed_size: Option<u64>,
+    pub section: Option<String>,
+    pub maintainer: Option<String>,
+    pub summary: Option<String>,
+}
+
+pub struct DpkgProvider {
+    binary_mode: BinaryMode,
+    bin_dirs: Vec<PathBuf>,
+}
+
+impl Default for DpkgProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl DpkgProvider {
     pub fn new() -> Self {
-        Self
+        Self {
+            binary_mode: BinaryMode::default(),
+            bin_dirs: package_bin_dirs(),
+        }
     }
 
-    fn has_dpkg_query() -> bool {
-        Command::new("which")
-            .arg("dpkg-query")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+    pub fn with_binary_mode(mut self, binary_mode: BinaryMode) -> Self {
+        self.binary_mode = binary_mode;
+        self
     }
 
-    fn has_desktop_file(pkg: &str) -> bool {
-        let apps_dir = Path::new("/usr/share/applications");
-        if !apps_dir.is_dir() {
-            return false;
-        }
-        // Check for <pkg>.desktop or <pkg>-*.desktop patterns
-        if apps_dir.join(format!("{}.desktop", pkg)).exists() {
-            return true;
-        }
-        // Check for any desktop file starting with the package name
-        if let Ok(entries) = fs::read_dir(apps_dir) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if name.starts_with(pkg) && name.ends_with(".desktop") {
-                        return true;
-                    }
-                }
-            }
-        }
-        false
-    }
-
-    fn find_package_binary(pkg: &str) -> Option<String> {
-        // Check /var/lib/dpkg/info/<pkg>.list for binaries
-        let list_file = format!("/var/lib/dpkg/info/{}.list", pkg);
-        if let Ok(content) = fs::read_to_string(&list_file) {
-            for line in content.lines() {
-                let line = line.trim();
-                if (line.starts_with("/usr/bin/") || line.starts_with("/usr/local/bin/")
-                    || line.starts_with("/usr/sbin/"))
-                    && Path::new(line).is_file()
-                {
-                    return Some(line.to_string());
+    /// Report executables from `bin_dirs` instead of the standard system directories.
+    pub fn with_bin_dirs(mut self, bin_dirs: Vec<PathBuf>) -> Self {
+        self.bin_dirs = bin_dirs;
+        self
+    }
+
+    /// Parse the dpkg status database, keeping only installed packages.
+    pub fn parse_status(content: &str) -> Vec<DpkgPackage> {
+        let mut packages = Vec::new();
+
+        for stanza in content.split("\n\n") {
+            let mut pkg = DpkgPackage::default();
+            let mut installed = false;
+
+            for line in stanza.lines() {
+                // Continuation lines belong to multi-line fields like Description
+                if line.starts_with(' ') || line.starts_with('\t') {
+                    continue;
                 }
-            }
-        }
+                let (key, value) = match line.split_once(':') {
+                    Some((k, v)) => (k, v.trim()),
+                    None => continue,
+                };
+                let value = Some(value.to_string()).filter(|v| !v.is_empty());
 
-        // Also try arch-qualified list files (e.g., pkg:amd64.list)
-        let info_dir = Path::new("/var/lib/dpkg/info");
-        if info_dir.is_dir() {
-            if let Ok(entries) = fs::read_dir(info_dir) {
-                for entry in entries.flatten() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        if name.starts_with(&format!("{}:", pkg)) && name.ends_with(".list") {
-                            if let Ok(content) = fs::read_to_string(entry.path()) {
-                                for line in content.lines() {
-                                    let line = line.trim();
-                                    if (line.starts_with("/usr/bin/")
-                                        || line.starts_with("/usr/local/bin/")
-                                        || line.starts_with("/usr/sbin/"))
-                                        && Path::new(line).is_file()
-                                    {
-                                        return Some(line.to_string());
-                                    }
-                                }
-                            }
-                        }
+                match key {
+                    "Package" => pkg.name = value.unwrap_or_default(),
+                    "Status" => {
+                        installed = value
+                            .as_deref()
+                            .and_then(|s| s.split_whitespace().last())
+                            == Some("installed");
+                    }
+                    "Version" => pkg.version = value,
+                    "Architecture" => pkg.architecture = value,
+                    "Installed-Size" => {
+                        pkg.installed_size = value.and_then(|v| v.parse().ok());
                     }
+                    "Section" => pkg.section = value,
+                    "Maintainer" => pkg.maintainer = value,
+                    "Description" => pkg.summary = value,
+                    _ => {}
                 }
             }
+
+            if installed && !pkg.name.is_empty() {
+                packages.push(pkg);
+            }
         }
 
-        None
+        packages
     }
 
-    pub fn parse_dpkg_output(output: &str) -> Vec<(String, Option<String>)> {
-        let mut packages = Vec::new();
-        for line in output.lines() {
-            let line = line.trim();
-            if line.is_empty() {
+    /// Read every `info/*.list` file once, keyed by package name without arch qualifier.
+    fn read_file_lists(
+        info_dir: &Path,
+        installed: &HashSet<&str>,
+    ) -> HashMap<String, Vec<String>> {
+        let mut lists: HashMap<String, Vec<String>> = HashMap::new();
+
+        let entries = match fs::read_dir(info_dir) {
+            Ok(e) => e,
+            Err(_) => return lists,
+        };
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            let stem = match file_name.strip_suffix(".list") {
+                Some(s) => s,
+                None => continue,
+            };
+            // Multi-arch packages use <pkg>:<arch>.list
+            let pkg = stem.split(':').next().unwrap_or(stem);
+            if !installed.contains(pkg) {
                 continue;
             }
-            let parts: Vec<&str> = line.splitn(2, '\t').collect();
-            if parts.is_empty() {
-                continue;
+
+            if let Ok(content) = fs::read_to_string(entry.path()) {
+                lists
+                    .entry(pkg.to_string())
+                    .or_default()
+                    .extend(content.lines().map(|l| l.trim().to_string()));
             }
-            let name = parts[0].to_string();
-            let description = parts.get(1).map(|d| d.to_string()).filter(|d| !d.is_empty());
-            packages.push((name, description));
         }
-        packages
-    }
-}
 
-impl AppProvider for DpkgProvider {
-    fn name(&self) -> &str {
-        "dpkg"
-    }
-
-    fn is_available(&self) -> bool {
-        Self::has_dpkg_query()
+        lists
     }
 
-    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
-        if !self.is_available() {
-            return Ok(Vec::new());
+    fn package_metadata(pkg: &DpkgPackage) -> BTreeMap<String, String> {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("package".to_string(), pkg.name.clone());
+        if let Some(ref v) = pkg.version {
+            metadata.insert("version".to_string(), v.clone());
         }
-
-        let output = Command::new("dpkg-query")
-            .args(["-W", "-f=${Package}\\t${binary:Summary}\\n"])
-            .output()
-            .map_err(ProviderError::Io)?;
-
-        if !output.status.success() {
-            return Ok(Vec::new());
+        if let Some(ref a) = pkg.architecture {
+            metadata.insert("architecture".to_string(), a.clone());
         }
+        if let Some(size) = pkg.installed_size {
+            metadata.insert("installed_size".to_string(), format!("{} KiB", size));
+        }
+        if let Some(ref s) = pkg.section {
+            metadata.insert("section".to_string(), s.clone());
+        }
+        if let Some(ref m) = pkg.maintainer {
+            metadata.insert("maintainer".to_string(), m.clone());
+        }
+        metadata
+    }
+
+    fn discover_from(
+        &self,
+        status_path: &Path,
+        info_dir: &Path,
+    ) -> Result<Vec<Application>, ProviderError> {
+        let content = fs::read_to_string(status_path).map_err(ProviderError::Io)?;
+        let packages = Self::parse_status(&content);
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let packages = Self::parse_dpkg_output(&stdout);
+        let installed: HashSet<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+        let file_lists = Self::read_file_lists(info_dir, &installed);
 
         let mut seen_binaries = HashSet::new();
         let mut apps = Vec::new();
 
-        for (pkg_name, description) in packages {
+        for pkg in &packages {
+            let files = match file_lists.get(&pkg.name) {
+                Some(f) => f,
+                None => continue,
+            };
+
             // Skip packages that have a .desktop file (already covered by desktop provider)
-            if Self::has_desktop_file(&pkg_name) {
+            if self.binary_mode == BinaryMode::Primary && ships_desktop_file(files) {
                 continue;
             }
 
-            // Find a binary for this package
-            if let Some(binary) = Self::find_package_binary(&pkg_name) {
+            let binaries =
+                select_package_binaries(&pkg.name, files, self.binary_mode, &self.bin_dirs);
+            for binary in binaries {
                 // Skip if we've already seen this binary
                 if !seen_binaries.insert(binary.clone()) {
                     continue;
@@ -152,7 +194,7 @@ impl AppProvider for DpkgProvider {
                 let exec_name = Path::new(&binary)
                     .file_name()
                     .and_then(|n| n.to_str())
-                    .unwrap_or(&pkg_name)
+                    .unwrap_or(&pkg.name)
                     .to_string();
 
                 apps.push(Application {
@@ -160,9 +202,10 @@ impl AppProvider for DpkgProvider {
                     exec_command: binary.clone(),
                     source: AppSource::Dpkg,
                     location: binary,
-                    icon: None,
                     categories: vec!["CLI".to_string()],
-                    description,
+                    description: pkg.summary.clone(),
+                    metadata: Self::package_metadata(pkg),
+                    ..Default::default()
                 });
             }
         }
@@ -171,9 +214,62 @@ impl AppProvider for DpkgProvider {
     }
 }
 
+impl AppProvider for DpkgProvider {
+    fn name(&self) -> &str {
+        "dpkg"
+    }
+
+    fn is_available(&self) -> bool {
+        Path::new(STATUS_FILE).is_file()
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        if !self.is_available() {
+            return Ok(Vec::new());
+        }
+
+        self.discover_from(Path::new(STATUS_FILE), Path::new(INFO_DIR))
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![PathBuf::from(STATUS_FILE)]
+    }
+
+    fn cache_key(&self) -> String {
+        self.binary_mode.cache_key(self.name())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    const STATUS: &str = "\
+Package: curl
+Status: install ok installed
+Priority: optional
+Section: web
+Installed-Size: 502
+Maintainer: Ubuntu Developers <ubuntu-devel-discuss@lists.ubuntu.com>
+Architecture: amd64
+Version: 8.5.0-2ubuntu10.6
+Description: command line tool for transferring data with URL syntax
+ curl is a command line tool for transferring data with URL syntax,
+ supporting DICT, FILE, FTP, FTPS, GOPHER, HTTP, HTTPS.
+
+Package: removed-pkg
+Status: deinstall ok config-files
+Version: 1.0
+
+Package: libc6
+Status: install ok installed
+Architecture: amd64
+Multi-Arch: same
+Version: 2.39-0ubuntu8
+Description: GNU C Library: Shared libraries
+";
 
     #[test]
     fn test_provider_name() {
@@ -182,37 +278,135 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_dpkg_output_valid() {
-        let output = "curl\tcommand line tool for transferring data\ngit\tfast, scalable, distributed revision control system\n";
-        let packages = DpkgProvider::parse_dpkg_output(output);
+    fn test_parse_status_valid() {
+        let packages = DpkgProvider::parse_status(STATUS);
         assert_eq!(packages.len(), 2);
-        assert_eq!(packages[0].0, "curl");
+
+        let curl = &packages[0];
+        assert_eq!(curl.name, "curl");
+        assert_eq!(curl.version, Some("8.5.0-2ubuntu10.6".to_string()));
+        assert_eq!(curl.architecture, Some("amd64".to_string()));
+        assert_eq!(curl.installed_size, Some(502));
+        assert_eq!(curl.section, Some("web".to_string()));
+        assert!(curl.maintainer.as_ref().unwrap().starts_with("Ubuntu Developers"));
         assert_eq!(
-            packages[0].1,
-            Some("command line tool for transferring data".to_string())
+            curl.summary,
+            Some("command line tool for transferring data with URL syntax".to_string())
         );
-        assert_eq!(packages[1].0, "git");
+
+        assert_eq!(packages[1].name, "libc6");
+    }
+
+    #[test]
+    fn test_parse_status_empty() {
+        assert!(DpkgProvider::parse_status("").is_empty());
     }
 
     #[test]
-    fn test_parse_dpkg_output_empty() {
-        let packages = DpkgProvider::parse_dpkg_output("");
+    fn test_parse_status_skips_not_installed() {
+        let packages =
+            DpkgProvider::parse_status("Package: gone\nStatus: purge ok not-installed\n");
         assert!(packages.is_empty());
     }
 
     #[test]
-    fn test_parse_dpkg_output_no_description() {
-        let output = "somepackage\t\n";
-        let packages = DpkgProvider::parse_dpkg_output(output);
+    fn test_parse_status_no_description() {
+        let packages =
+            DpkgProvider::parse_status("Package: somepackage\nStatus: install ok installed\n");
         assert_eq!(packages.len(), 1);
-        assert_eq!(packages[0].0, "somepackage");
-        assert_eq!(packages[0].1, None);
+        assert_eq!(packages[0].name, "somepackage");
+        assert_eq!(packages[0].summary, None);
     }
 
     #[test]
-    fn test_parse_dpkg_output_malformed() {
-        let output = "\n\n  \n";
-        let packages = DpkgProvider::parse_dpkg_output(output);
+    fn test_parse_status_malformed() {
+        let packages = DpkgProvider::parse_status("\n\n  \ngarbage line\n");
         assert!(packages.is_empty());
     }
+
+    #[test]
+    fn test_discover_from_fixture() {
+        let tmp = TempDir::new().unwrap();
+        let status = tmp.path().join("status");
+        let info = tmp.path().join("info");
+        let bin = tmp.path().join("bin");
+        fs::create_dir_all(&info).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        for name in ["curl", "curl-config", "gimp"] {
+            let path = bin.join(name);
+            fs::write(&path, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let exe = |name: &str| bin.join(name).to_string_lossy().into_owned();
+
+        fs::write(
+            &status,
+            "Package: curl\nStatus: install ok installed\nVersion: 8.5.0\nArchitecture: amd64\nInstalled-Size: 502\nDescription: transfer tool\n\n\
+Package: gimp\nStatus: install ok installed\nDescription: image editor\n",
+        )
+        .unwrap();
+        fs::write(
+            info.join("curl:amd64.list"),
+            format!("/.\n{}\n{}\n", bin.display(), exe("curl")),
+        )
+        .unwrap();
+        fs::write(
+            info.join("gimp.list"),
+            format!("{}\n/usr/share/applications/gimp.desktop\n", exe("gimp")),
+        )
+        .unwrap();
+        fs::write(info.join("unrelated.list"), format!("{}\n", exe("curl-config"))).unwrap();
+
+        let provider = DpkgProvider::new().with_bin_dirs(vec![bin.clone()]);
+        let apps = provider.discover_from(&status, &info).unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "curl");
+        assert_eq!(apps[0].exec_command, exe("curl"));
+        assert_eq!(apps[0].source, AppSource::Dpkg);
+        assert_eq!(apps[0].description, Some("transfer tool".to_string()));
+        assert_eq!(apps[0].metadata.get("package").unwrap(), "curl");
+        assert_eq!(apps[0].metadata.get("version").unwrap(), "8.5.0");
+        assert_eq!(apps[0].metadata.get("architecture").unwrap(), "amd64");
+        assert_eq!(apps[0].metadata.get("installed_size").unwrap(), "502 KiB");
+
+        // In all-binaries mode packages with desktop files are included too
+        let provider = provider.with_binary_mode(BinaryMode::All);
+        let apps = provider.discover_from(&status, &info).unwrap();
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["curl", "gimp"]);
+
+        // A binary shipped by two packages is reported once
+        fs::write(
+            info.join("gimp.list"),
+            format!("{}\n/usr/share/applications/gimp.desktop\n", exe("curl")),
+        )
+        .unwrap();
+        fs::write(
+            info.join("curl:amd64.list"),
+            format!("{}\n{}\n", exe("curl"), exe("curl-config")),
+        )
+        .unwrap();
+        let apps = provider.discover_from(&status, &info).unwrap();
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["curl", "curl-config"]);
+        assert_eq!(apps[1].metadata.get("package").unwrap(), "curl");
+    }
+
+    #[test]
+    fn test_discover_from_ignores_other_dirs() {
+        let tmp = TempDir::new().unwrap();
+        let status = tmp.path().join("status");
+        let info = tmp.path().join("info");
+        let bin = tmp.path().join("bin");
+        fs::create_dir_all(&info).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        let tool = bin.join("tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(&status, "Package: tool\nStatus: install ok installed\n").unwrap();
+        fs::write(info.join("tool.list"), format!("{}\n", tool.display())).unwrap();
+
+        let provider = DpkgProvider::new().with_bin_dirs(vec![tmp.path().join("sbin")]);
+        assert!(provider.discover_from(&status, &info).unwrap().is_empty());
+    }
 }
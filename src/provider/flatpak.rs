@@ -1,62 +1,351 @@
-use std::process::Command;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use configparser::ini::Ini;
 
 use crate::app::{AppSource, Application};
+use crate::provider::appstream::parse_appstream;
+use crate::provider::desktop::{DesktopProvider, ShowContext};
 use crate::provider::{AppProvider, ProviderError};
+use crate::sandbox::{self, SandboxPermissions};
+
+/// A directory flatpak installs applications into, such as `/var/lib/flatpak`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Installation {
+    /// `user`, `system`, or the ID of a custom installation from `installations.d`.
+    pub name: String,
+    pub path: PathBuf,
+}
 
-pub struct FlatpakProvider;
+/// Reads deployed applications straight from flatpak installation directories, so it
+/// works without the `flatpak` CLI, e.g. in a container with the host's
+/// `/var/lib/flatpak` mounted.
+pub struct FlatpakProvider {
+    installations: Vec<Installation>,
+    context: ShowContext,
+}
+
+impl Default for FlatpakProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl FlatpakProvider {
     pub fn new() -> Self {
-        Self
+        Self {
+            installations: Self::default_installations(),
+            context: ShowContext::current(),
+        }
     }
 
-    fn has_flatpak() -> bool {
-        Command::new("which")
-            .arg("flatpak")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+    /// Read these installations instead of the ones configured on this system.
+    pub fn with_installations(mut self, installations: Vec<Installation>) -> Self {
+        self.installations = installations;
+        self
     }
 
-    fn parse_flatpak_output(output: &str) -> Vec<Application> {
-        let mut apps = Vec::new();
+    /// Evaluate desktop entries against `context` instead of this process's environment.
+    pub fn with_context(mut self, context: ShowContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// The per-user installation (`$FLATPAK_USER_DIR`, default
+    /// `~/.local/share/flatpak`), the system one (`$FLATPAK_SYSTEM_DIR`, default
+    /// `/var/lib/flatpak`) and custom ones from `/etc/flatpak/installations.d`.
+    pub fn default_installations() -> Vec<Installation> {
+        let mut installations = Vec::new();
+
+        let user_dir = std::env::var_os("FLATPAK_USER_DIR")
+            .map(PathBuf::from)
+            .or_else(|| dirs::data_dir().map(|d| d.join("flatpak")));
+        if let Some(path) = user_dir {
+            installations.push(Installation {
+                name: "user".to_string(),
+                path,
+            });
+        }
+
+        let system_dir = std::env::var_os("FLATPAK_SYSTEM_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/var/lib/flatpak"));
+        installations.push(Installation {
+            name: "system".to_string(),
+            path: system_dir,
+        });
+
+        let config_dir = std::env::var_os("FLATPAK_CONFIG_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/etc/flatpak"));
+        installations.extend(Self::custom_installations(&config_dir.join("installations.d")));
+
+        installations
+    }
 
-        for line in output.lines() {
-            let line = line.trim();
-            if line.is_empty() {
+    /// Installations declared in `*.conf` files as `[Installation "<id>"]` groups.
+    pub fn custom_installations(dir: &Path) -> Vec<Installation> {
+        let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("conf"))
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        files.sort();
+
+        let mut installations = Vec::new();
+        for file in files {
+            let content = match fs::read_to_string(&file) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let mut config = Ini::new_cs();
+            config.set_comment_symbols(&['#']);
+            if config.read(content).is_err() {
                 continue;
             }
+            for section in config.sections() {
+                let id = match section
+                    .strip_prefix("Installation \"")
+                    .and_then(|s| s.strip_suffix('"'))
+                {
+                    Some(id) if !id.is_empty() => id.to_string(),
+                    _ => continue,
+                };
+                if let Some(path) = config.get(&section, "Path").filter(|p| !p.is_empty()) {
+                    installations.push(Installation {
+                        name: id,
+                        path: PathBuf::from(path),
+                    });
+                }
+            }
+        }
+        installations
+    }
 
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() < 2 {
-                continue;
+    /// Applications deployed in one installation, from `app/<id>/current/active`.
+    fn scan_installation(&self, installation: &Installation) -> Vec<Application> {
+        let entries = match fs::read_dir(installation.path.join("app")) {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut apps = Vec::new();
+        for entry in entries.flatten() {
+            let app_id = match entry.file_name().to_str() {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            if let Some(app) = self.read_app(installation, &entry.path(), &app_id) {
+                apps.push(app);
             }
+        }
+        apps
+    }
 
-            let name = parts[0].trim().to_string();
-            let app_id = parts[1].trim().to_string();
-            let description = parts
-                .get(2)
-                .map(|d| d.trim().to_string())
-                .filter(|d| !d.is_empty());
+    /// Describe the current deploy of `app_id`, or `None` if it has none or its desktop
+    /// entry should not be shown.
+    fn read_app(
+        &self,
+        installation: &Installation,
+        app_dir: &Path,
+        app_id: &str,
+    ) -> Option<Application> {
+        // `current` points at `<arch>/<branch>`, `active` at the deployed commit
+        let current = app_dir.join("current");
+        let active = current.join("active");
+        let mut metadata = Ini::new_cs();
+        metadata.set_comment_symbols(&['#']);
+        metadata
+            .read(fs::read_to_string(active.join("metadata")).ok()?)
+            .ok()?;
+        let runtime = metadata.get("Application", "runtime");
 
-            if name.is_empty() || app_id.is_empty() {
-                continue;
+        let mut permissions = SandboxPermissions::default();
+        apply_keyfile(&mut permissions, &metadata);
+        for path in self.override_files(installation, app_id) {
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let mut overrides = Ini::new_cs();
+            overrides.set_comment_symbols(&['#']);
+            if overrides.read(content).is_ok() {
+                apply_keyfile(&mut permissions, &overrides);
+            }
+        }
+
+        let appstream = Self::metainfo(&active.join("files"), app_id)
+            .as_deref()
+            .and_then(parse_appstream)
+            .unwrap_or_default();
+
+        let mut details = BTreeMap::new();
+        details.insert("app_id".to_string(), app_id.to_string());
+        if let Some(version) = appstream.version {
+            details.insert("version".to_string(), version);
+        }
+        if let Ok(target) = fs::read_link(&current) {
+            let target = target.to_string_lossy().to_string();
+            if let Some((arch, branch)) = target.split_once('/') {
+                details.insert("arch".to_string(), arch.to_string());
+                details.insert("branch".to_string(), branch.to_string());
             }
+        }
+        if let Some(origin) = fs::read(active.join("deploy"))
+            .ok()
+            .as_deref()
+            .and_then(Self::deploy_origin)
+        {
+            details.insert("origin".to_string(), origin);
+        }
+        if let Some(runtime) = runtime {
+            details.insert("runtime".to_string(), runtime);
+        }
+        details.insert("installation".to_string(), installation.name.clone());
+
+        let desktop_name = format!("{}.desktop", app_id);
+        let exported = active.join("export/share/applications").join(&desktop_name);
+        let internal = active.join("files/share/applications").join(&desktop_name);
 
-            apps.push(Application {
-                name,
+        let mut app = if let Ok(content) = fs::read_to_string(&exported) {
+            // Exported entries already run the app through `flatpak run`
+            match DesktopProvider::parse_desktop_content_in(&content, &exported, &self.context) {
+                Ok(app) => app?,
+                Err(e) => {
+                    eprintln!("appgrep: warning: {}: {}", exported.display(), e);
+                    return None;
+                }
+            }
+        } else if let Ok(content) = fs::read_to_string(&internal) {
+            let mut app = match DesktopProvider::parse_desktop_content_in(&content, &internal, &self.context) {
+                Ok(app) => app?,
+                Err(e) => {
+                    eprintln!("appgrep: warning: {}: {}", internal.display(), e);
+                    return None;
+                }
+            };
+            // The Exec line is only valid inside the sandbox
+            app.exec_args = vec!["flatpak".to_string(), "run".to_string(), app_id.to_string()];
+            app.exec_command = format!("flatpak run {}", app_id);
+            app.actions.clear();
+            app
+        } else {
+            Application {
+                name: app_id.to_string(),
                 exec_command: format!("flatpak run {}", app_id),
                 source: AppSource::Flatpak,
-                location: app_id.clone(),
-                icon: Some(app_id),
-                categories: Vec::new(),
-                description,
-            });
+                ..Default::default()
+            }
+        };
+
+        app.source = AppSource::Flatpak;
+        app.location = app_id.to_string();
+        app.icon.get_or_insert_with(|| app_id.to_string());
+        if app.description.is_none() {
+            app.description = appstream.summary;
         }
+        app.metadata.extend(details);
+        app.permissions = Some(permissions);
+        Some(app)
+    }
 
-        apps
+    /// Override files for `app_id` in the order flatpak applies them: global then
+    /// per-app, from the app's installation and then from the user installation, whose
+    /// overrides (`flatpak override --user`) apply to every app.
+    fn override_files(&self, installation: &Installation, app_id: &str) -> Vec<PathBuf> {
+        let user = self.installations.iter().find(|i| i.name == "user");
+        let mut roots = vec![&installation.path];
+        if let Some(user) = user.filter(|u| u.path != installation.path) {
+            roots.push(&user.path);
+        }
+        roots
+            .into_iter()
+            .flat_map(|root| {
+                let dir = root.join("overrides");
+                [dir.join("global"), dir.join(app_id)]
+            })
+            .collect()
+    }
+
+    /// Content of the app's AppStream metainfo file, under either of its usual names.
+    fn metainfo(files: &Path, app_id: &str) -> Option<String> {
+        [
+            format!("share/metainfo/{}.metainfo.xml", app_id),
+            format!("share/metainfo/{}.appdata.xml", app_id),
+            format!("share/appdata/{}.appdata.xml", app_id),
+        ]
+        .iter()
+        .find_map(|name| fs::read_to_string(files.join(name)).ok())
+    }
+
+    /// The remote a deploy was installed from. `deploy` is a GVariant whose first member
+    /// is the origin, stored as a NUL-terminated string at the start of the file.
+    pub fn deploy_origin(deploy: &[u8]) -> Option<String> {
+        let end = deploy.iter().position(|&b| b == 0)?;
+        let origin = std::str::from_utf8(&deploy[..end]).ok()?;
+        let valid = !origin.is_empty()
+            && origin
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+        valid.then(|| origin.to_string())
+    }
+}
+
+/// Merge the `[Context]` and bus policy groups of a metadata or override keyfile into
+/// `permissions`. `!value` entries and `none` policies revoke earlier grants.
+fn apply_keyfile(permissions: &mut SandboxPermissions, keyfile: &Ini) {
+    let lists = [
+        ("shared", &mut permissions.shared),
+        ("sockets", &mut permissions.sockets),
+        ("devices", &mut permissions.devices),
+        ("filesystems", &mut permissions.filesystems),
+    ];
+    for (key, list) in lists {
+        let value = keyfile.get("Context", key).unwrap_or_default();
+        for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (revoke, entry) = match entry.strip_prefix('!') {
+                Some(entry) => (true, entry),
+                None => (false, entry),
+            };
+            // A filesystem given again with another access mode replaces the old one
+            let path = |e: &str| {
+                if key == "filesystems" {
+                    sandbox::filesystem_path(e).to_string()
+                } else {
+                    e.to_string()
+                }
+            };
+            let entry_path = path(entry);
+            list.retain(|existing| path(existing) != entry_path);
+            if !revoke {
+                list.push(entry.to_string());
+            }
+        }
+    }
+
+    let policies = [
+        ("Session Bus Policy", &mut permissions.session_bus),
+        ("System Bus Policy", &mut permissions.system_bus),
+    ];
+    for (section, policy) in policies {
+        let names = match keyfile.get_map_ref().get(section) {
+            Some(names) => names,
+            None => continue,
+        };
+        for (name, access) in names {
+            match access.as_deref().map(str::trim) {
+                Some("none") | None => {
+                    policy.remove(name);
+                }
+                Some(access) => {
+                    policy.insert(name.clone(), access.to_string());
+                }
+            }
+        }
     }
 }
 
@@ -66,63 +355,263 @@ impl AppProvider for FlatpakProvider {
     }
 
     fn is_available(&self) -> bool {
-        Self::has_flatpak()
+        self.installations.iter().any(|i| i.path.join("app").is_dir())
     }
 
     fn discover(&self) -> Result<Vec<Application>, ProviderError> {
-        if !self.is_available() {
-            return Ok(Vec::new());
-        }
-
-        let output = Command::new("flatpak")
-            .args(["list", "--app", "--columns=name,application,description"])
-            .output()
-            .map_err(ProviderError::Io)?;
+        Ok(self
+            .installations
+            .iter()
+            .flat_map(|installation| self.scan_installation(installation))
+            .collect())
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("appgrep: warning: flatpak list failed: {}", stderr.trim());
-            return Ok(Vec::new());
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for installation in &self.installations {
+            paths.push(installation.path.join("app"));
+            // Touched by flatpak after every install, update and removal
+            paths.push(installation.path.join(".changed"));
+            // `flatpak override` does not touch `.changed`
+            paths.push(installation.path.join("overrides"));
         }
+        paths
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        Ok(Self::parse_flatpak_output(&stdout))
+    fn cache_key(&self) -> String {
+        self.context.cache_key(self.name())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
 
-    #[test]
-    fn test_parse_flatpak_output() {
-        let output = "Firefox\torg.mozilla.firefox\tWeb Browser\nLibreOffice\torg.libreoffice.LibreOffice\tOffice Suite\n";
-        let apps = FlatpakProvider::parse_flatpak_output(output);
-        assert_eq!(apps.len(), 2);
-        assert_eq!(apps[0].name, "Firefox");
-        assert_eq!(apps[0].exec_command, "flatpak run org.mozilla.firefox");
-        assert_eq!(apps[0].location, "org.mozilla.firefox");
-        assert_eq!(apps[0].description, Some("Web Browser".to_string()));
-        assert_eq!(apps[1].name, "LibreOffice");
+    /// Deploy `app_id` into the installation at `root` like flatpak does.
+    fn deploy(root: &Path, app_id: &str, desktop: Option<&str>, metainfo: Option<&str>) {
+        let app_dir = root.join("app").join(app_id);
+        let commit = app_dir.join("x86_64/stable/0123abcd");
+        fs::create_dir_all(commit.join("files")).unwrap();
+        symlink("0123abcd", app_dir.join("x86_64/stable/active")).unwrap();
+        symlink("x86_64/stable", app_dir.join("current")).unwrap();
+        fs::write(
+            commit.join("metadata"),
+            format!(
+                "[Application]\nname={}\nruntime=org.freedesktop.Platform/x86_64/23.08\ncommand=app\n",
+                app_id
+            ),
+        )
+        .unwrap();
+        fs::write(commit.join("deploy"), b"flathub\0abcdef\0\x00\x01\x02").unwrap();
+        if let Some(desktop) = desktop {
+            let dir = commit.join("export/share/applications");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("{}.desktop", app_id)), desktop).unwrap();
+        }
+        if let Some(metainfo) = metainfo {
+            let dir = commit.join("files/share/metainfo");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("{}.metainfo.xml", app_id)), metainfo).unwrap();
+        }
     }
 
-    #[test]
-    fn test_parse_empty_output() {
-        let apps = FlatpakProvider::parse_flatpak_output("");
-        assert!(apps.is_empty());
+    fn provider(root: &Path) -> FlatpakProvider {
+        FlatpakProvider::new().with_installations(vec![Installation {
+            name: "system".to_string(),
+            path: root.to_path_buf(),
+        }])
     }
 
     #[test]
-    fn test_parse_malformed_line() {
-        let apps = FlatpakProvider::parse_flatpak_output("OnlyOnePart\n");
-        assert!(apps.is_empty());
+    fn test_reads_deployed_app() {
+        let tmp = TempDir::new().unwrap();
+        deploy(
+            tmp.path(),
+            "org.mozilla.firefox",
+            Some(
+                "[Desktop Entry]\nType=Application\nName=Firefox\nGenericName=Web Browser\n\
+                 Exec=/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=firefox --file-forwarding org.mozilla.firefox @@u %u @@\n\
+                 Icon=org.mozilla.firefox\n",
+            ),
+            Some(
+                "<component>\n  <summary>Fast &amp; private</summary>\n  <releases>\n    \
+                 <release version=\"128.0\" date=\"2024-07-09\"/>\n    <release version=\"127.0\"/>\n  \
+                 </releases>\n</component>\n",
+            ),
+        );
+
+        let apps = provider(tmp.path()).discover().unwrap();
+        assert_eq!(apps.len(), 1);
+        let app = &apps[0];
+        assert_eq!(app.name, "Firefox");
+        assert_eq!(app.source, AppSource::Flatpak);
+        assert_eq!(app.location, "org.mozilla.firefox");
+        assert_eq!(app.exec_args[..3], ["/usr/bin/flatpak", "run", "--branch=stable"]);
+        assert_eq!(app.generic_name.as_deref(), Some("Web Browser"));
+        assert_eq!(app.description.as_deref(), Some("Fast & private"));
+        assert_eq!(app.metadata["version"], "128.0");
+        assert_eq!(app.metadata["branch"], "stable");
+        assert_eq!(app.metadata["arch"], "x86_64");
+        assert_eq!(app.metadata["origin"], "flathub");
+        assert_eq!(app.metadata["runtime"], "org.freedesktop.Platform/x86_64/23.08");
+        assert_eq!(app.metadata["installation"], "system");
     }
 
     #[test]
-    fn test_parse_no_description() {
-        let apps = FlatpakProvider::parse_flatpak_output("MyApp\tcom.example.myapp\t\n");
+    fn test_app_without_desktop_entry() {
+        let tmp = TempDir::new().unwrap();
+        deploy(tmp.path(), "com.example.Tool", None, None);
+        // Not deployed: no `current` link
+        fs::create_dir_all(tmp.path().join("app/com.example.Removed/x86_64")).unwrap();
+
+        let apps = provider(tmp.path()).discover().unwrap();
         assert_eq!(apps.len(), 1);
-        assert_eq!(apps[0].name, "MyApp");
+        assert_eq!(apps[0].name, "com.example.Tool");
+        assert_eq!(apps[0].exec_command, "flatpak run com.example.Tool");
+        assert_eq!(apps[0].icon.as_deref(), Some("com.example.Tool"));
         assert!(apps[0].description.is_none());
     }
+
+    #[test]
+    fn test_hidden_desktop_entry_is_skipped() {
+        let tmp = TempDir::new().unwrap();
+        deploy(
+            tmp.path(),
+            "com.example.Hidden",
+            Some("[Desktop Entry]\nType=Application\nName=Hidden\nExec=hidden\nNoDisplay=true\n"),
+            None,
+        );
+        assert!(provider(tmp.path()).discover().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_desktop_entry_uses_context() {
+        use crate::provider::desktop::Locale;
+
+        let tmp = TempDir::new().unwrap();
+        deploy(
+            tmp.path(),
+            "org.kde.Editor",
+            Some(
+                "[Desktop Entry]\nType=Application\nName=Editor\nName[de]=Bearbeiter\n\
+                 Exec=editor\nOnlyShowIn=KDE;\n",
+            ),
+            None,
+        );
+        let context = |desktop: &str| ShowContext {
+            current_desktops: vec![desktop.to_string()],
+            locale: Locale::parse("de_DE.UTF-8"),
+            ..Default::default()
+        };
+
+        let gnome = provider(tmp.path()).with_context(context("GNOME"));
+        assert!(gnome.discover().unwrap().is_empty());
+        let apps = provider(tmp.path()).with_context(context("KDE")).discover().unwrap();
+        assert_eq!(apps[0].name, "Bearbeiter");
+    }
+
+    #[test]
+    fn test_cache_key_depends_on_context() {
+        use crate::provider::desktop::Locale;
+
+        let provider = FlatpakProvider::new().with_context(ShowContext::default());
+        assert_eq!(provider.cache_key(), "flatpak");
+        let provider = FlatpakProvider::new().with_context(ShowContext {
+            current_desktops: vec!["KDE".to_string()],
+            locale: Locale::parse("de_DE.UTF-8"),
+            ..Default::default()
+        });
+        assert_eq!(provider.cache_key(), "flatpak-kde-de_de");
+    }
+
+    #[test]
+    fn test_missing_installation_is_unavailable() {
+        let tmp = TempDir::new().unwrap();
+        let provider = provider(&tmp.path().join("nope"));
+        assert!(!provider.is_available());
+        assert!(provider.discover().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_permissions_with_overrides() {
+        let system = TempDir::new().unwrap();
+        let user = TempDir::new().unwrap();
+        deploy(system.path(), "org.example.Editor", None, None);
+        let metadata = system.path().join("app/org.example.Editor/current/active/metadata");
+        let mut content = fs::read_to_string(&metadata).unwrap();
+        content.push_str(
+            "\n[Context]\nshared=network;ipc;\nsockets=x11;wayland;\ndevices=dri;\n\
+             filesystems=home;xdg-download:ro;\n\n\
+             [Session Bus Policy]\norg.freedesktop.secrets=talk\norg.kde.*=own\n\n\
+             [System Bus Policy]\norg.freedesktop.login1=talk\n",
+        );
+        fs::write(&metadata, content).unwrap();
+
+        fs::create_dir_all(system.path().join("overrides")).unwrap();
+        fs::write(
+            system.path().join("overrides/global"),
+            "[Context]\nsockets=!x11;\n",
+        )
+        .unwrap();
+        fs::create_dir_all(user.path().join("overrides")).unwrap();
+        fs::write(
+            user.path().join("overrides/org.example.Editor"),
+            "[Context]\nfilesystems=!home;xdg-download;~/Projects:ro;\n\n\
+             [Session Bus Policy]\norg.kde.*=none\n",
+        )
+        .unwrap();
+
+        let provider = FlatpakProvider::new().with_installations(vec![
+            Installation {
+                name: "user".to_string(),
+                path: user.path().to_path_buf(),
+            },
+            Installation {
+                name: "system".to_string(),
+                path: system.path().to_path_buf(),
+            },
+        ]);
+        let apps = provider.discover().unwrap();
+        let permissions = apps[0].permissions.as_ref().unwrap();
+        assert_eq!(permissions.shared, vec!["network", "ipc"]);
+        assert_eq!(permissions.sockets, vec!["wayland"]);
+        assert_eq!(permissions.devices, vec!["dri"]);
+        assert_eq!(permissions.filesystems, vec!["xdg-download", "~/Projects:ro"]);
+        assert_eq!(
+            permissions.session_bus,
+            BTreeMap::from([("org.freedesktop.secrets".to_string(), "talk".to_string())])
+        );
+        assert_eq!(permissions.system_bus["org.freedesktop.login1"], "talk");
+    }
+
+    #[test]
+    fn test_custom_installations() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(
+            tmp.path().join("extra.conf"),
+            "[Installation \"extra\"]\nPath=/mnt/sdcard/flatpak\nDisplayName=Extra\n\n\
+             [Installation \"nopath\"]\nDisplayName=Broken\n",
+        )
+        .unwrap();
+        fs::write(tmp.path().join("ignored.txt"), "[Installation \"x\"]\nPath=/x\n").unwrap();
+        assert_eq!(
+            FlatpakProvider::custom_installations(tmp.path()),
+            vec![Installation {
+                name: "extra".to_string(),
+                path: PathBuf::from("/mnt/sdcard/flatpak"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_deploy_origin() {
+        assert_eq!(
+            FlatpakProvider::deploy_origin(b"fedora\0commit\0").as_deref(),
+            Some("fedora")
+        );
+        assert_eq!(FlatpakProvider::deploy_origin(b"\0"), None);
+        assert_eq!(FlatpakProvider::deploy_origin(b"no terminator"), None);
+    }
 }
@@ -1,18 +1,73 @@
+#[cfg(feature = "standalone")]
+pub mod appimage;
+#[cfg(any(feature = "flatpak", feature = "standalone"))]
+pub mod appstream;
+#[cfg(feature = "brew")]
 pub mod brew;
+#[cfg(feature = "cargo")]
 pub mod cargo;
+#[cfg(feature = "desktop")]
 pub mod desktop;
+#[cfg(feature = "rpm")]
 pub mod dnf;
+#[cfg(feature = "dpkg")]
 pub mod dpkg;
+#[cfg(feature = "flatpak")]
 pub mod flatpak;
+#[cfg(feature = "nix")]
+pub mod nix;
+#[cfg(feature = "npm")]
 pub mod npm;
+#[cfg(feature = "pacman")]
 pub mod pacman;
+#[cfg(feature = "pipx")]
+pub mod pipx;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod process;
+#[cfg(any(feature = "pipx", feature = "uv"))]
+pub mod python;
+#[cfg(feature = "snap")]
 pub mod snap;
+#[cfg(feature = "snap")]
+pub mod snapd;
+#[cfg(feature = "standalone")]
 pub mod standalone;
+#[cfg(feature = "uv")]
+pub mod uv;
+
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use thiserror::Error;
 
 use crate::app::Application;
 
+/// Directories whose executables system package providers report by default.
+const PACKAGE_BIN_DIRS: &[&str] = &["/usr/bin", "/usr/local/bin", "/usr/sbin"];
+
+/// Which executables the system package providers (dpkg, rpm, pacman) report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinaryMode {
+    /// One representative executable per package, skipping packages with a desktop entry.
+    #[default]
+    Primary,
+    /// Every executable a package ships, each as its own application.
+    All,
+}
+
+impl BinaryMode {
+    /// Cache entry name for a provider running in this mode.
+    pub fn cache_key(&self, provider: &str) -> String {
+        match self {
+            BinaryMode::Primary => provider.to_string(),
+            BinaryMode::All => format!("{}-all", provider),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ProviderError {
     #[error("tool not available: {0}")]
@@ -23,10 +78,169 @@ pub enum ProviderError {
     ParseError(String),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
 }
 
 pub trait AppProvider: Send + Sync {
     fn name(&self) -> &str;
     fn is_available(&self) -> bool;
     fn discover(&self) -> Result<Vec<Application>, ProviderError>;
+
+    /// Files and directories whose modification invalidates cached results.
+    /// Providers that return nothing here are never cached.
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Name of this provider's cache entry; differs when options change the results.
+    fn cache_key(&self) -> String {
+        self.name().to_string()
+    }
+}
+
+/// Whether a package's file list includes a desktop entry.
+pub fn ships_desktop_file(files: &[String]) -> bool {
+    files
+        .iter()
+        .any(|f| f.starts_with("/usr/share/applications/") && f.ends_with(".desktop"))
+}
+
+/// The default directories for [`select_package_binaries`].
+pub fn package_bin_dirs() -> Vec<PathBuf> {
+    PACKAGE_BIN_DIRS.iter().map(PathBuf::from).collect()
+}
+
+/// Pick the executables to report from a package's file list, considering only
+/// files directly inside one of `bin_dirs`.
+///
+/// In [`BinaryMode::Primary`] this is the binary named after the package if it
+/// ships one, otherwise the first executable found.
+pub fn select_package_binaries(
+    pkg: &str,
+    files: &[String],
+    mode: BinaryMode,
+    bin_dirs: &[PathBuf],
+) -> Vec<String> {
+    let mut binaries = files.iter().filter(|f| {
+        let path = Path::new(f.as_str());
+        path.parent().is_some_and(|parent| bin_dirs.iter().any(|dir| dir == parent))
+            && is_executable_file(path)
+    });
+
+    match mode {
+        BinaryMode::All => binaries.cloned().collect(),
+        BinaryMode::Primary => {
+            let first = match binaries.next() {
+                Some(b) => b,
+                None => return Vec::new(),
+            };
+            let named = std::iter::once(first)
+                .chain(binaries)
+                .find(|b| Path::new(b.as_str()).file_name().and_then(|n| n.to_str()) == Some(pkg));
+            vec![named.unwrap_or(first).clone()]
+        }
+    }
+}
+
+pub fn is_executable_file(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Whether an executable named `program` is in one of the `path` directories, which
+/// are separated like `$PATH`.
+pub fn on_path(program: &str, path: &OsStr) -> bool {
+    std::env::split_paths(path).any(|dir| is_executable_file(&dir.join(program)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_ships_desktop_file() {
+        assert!(ships_desktop_file(&files(&[
+            "/usr/bin/gimp",
+            "/usr/share/applications/gimp.desktop",
+        ])));
+        assert!(!ships_desktop_file(&files(&["/usr/bin/curl"])));
+    }
+
+    /// A `bin` directory in `tmp` holding an executable for each of `names`.
+    fn fixture_bin_dir(tmp: &tempfile::TempDir, names: &[&str]) -> PathBuf {
+        let bin = tmp.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        for name in names {
+            let path = bin.join(name);
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        bin
+    }
+
+    #[test]
+    fn test_select_package_binaries_all() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bin = fixture_bin_dir(&tmp, &["env", "sh"]);
+        let path = |p: &str| tmp.path().join(p).to_string_lossy().into_owned();
+        let list = vec![path(""), path("bin"), path("bin/env"), path("bin/sh"), path("share/x")];
+        let binaries = select_package_binaries("coreutils", &list, BinaryMode::All, &[bin]);
+        assert_eq!(binaries, vec![path("bin/env"), path("bin/sh")]);
+    }
+
+    #[test]
+    fn test_select_package_binaries_primary_prefers_package_name() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bin = fixture_bin_dir(&tmp, &["env", "sh"]);
+        let env = bin.join("env").to_string_lossy().into_owned();
+        let sh = bin.join("sh").to_string_lossy().into_owned();
+        let list = vec![env.clone(), sh.clone()];
+        let dirs = [bin];
+        assert_eq!(select_package_binaries("sh", &list, BinaryMode::Primary, &dirs), vec![sh]);
+        assert_eq!(
+            select_package_binaries("coreutils", &list, BinaryMode::Primary, &dirs),
+            vec![env]
+        );
+    }
+
+    #[test]
+    fn test_select_package_binaries_only_in_bin_dirs() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bin = fixture_bin_dir(&tmp, &["tool"]);
+        let list = vec![bin.join("tool").to_string_lossy().into_owned()];
+        let other = tmp.path().join("sbin");
+        assert!(select_package_binaries("tool", &list, BinaryMode::All, &[other]).is_empty());
+    }
+
+    #[test]
+    fn test_on_path_requires_executable() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let foot = tmp.path().join("foot");
+        std::fs::write(&foot, "").unwrap();
+        assert!(!on_path("foot", tmp.path().as_os_str()));
+        std::fs::set_permissions(&foot, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(on_path("foot", tmp.path().as_os_str()));
+        assert!(!on_path("foot", OsStr::new("")));
+    }
+
+    #[test]
+    fn test_select_package_binaries_skips_missing() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let bin = fixture_bin_dir(&tmp, &[]);
+        // Present but not executable
+        std::fs::write(bin.join("data"), "").unwrap();
+        let list = vec![
+            bin.join("missing").to_string_lossy().into_owned(),
+            bin.join("data").to_string_lossy().into_owned(),
+        ];
+        let dirs = [bin];
+        assert!(select_package_binaries("x", &list, BinaryMode::All, &dirs).is_empty());
+        assert!(select_package_binaries("x", &list, BinaryMode::Primary, &dirs).is_empty());
+    }
 }
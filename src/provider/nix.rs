@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::app::{AppSource, Application};
+use crate::provider::desktop::{DesktopProvider, ShowContext};
+use crate::provider::process::{self, has_command};
+use crate::provider::{AppProvider, ProviderError};
+
+pub struct NixProvider {
+    context: ShowContext,
+}
+
+impl Default for NixProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NixProvider {
+    pub fn new() -> Self {
+        Self {
+            context: ShowContext::current(),
+        }
+    }
+
+    /// Evaluate desktop entries against `context` instead of this process's environment.
+    pub fn with_context(mut self, context: ShowContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    fn has_nix() -> bool {
+        has_command("nix")
+    }
+
+    /// Profile roots that expose `bin/` and `share/applications/`.
+    fn profile_dirs() -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(home.join(".nix-profile"));
+        }
+
+        if let Ok(user) = std::env::var("USER") {
+            if !user.is_empty() {
+                dirs.push(PathBuf::from("/etc/profiles/per-user").join(user));
+            }
+        }
+
+        dirs.push(PathBuf::from("/run/current-system/sw"));
+
+        dirs
+    }
+
+    /// Store paths of packages installed with `nix profile install`.
+    fn profile_store_paths() -> Vec<PathBuf> {
+        let output = match process::output(Command::new("nix").args(["profile", "list", "--json"])) {
+            Ok(o) if o.status.success() => o,
+            _ => return Vec::new(),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse_profile_list_json(&stdout)
+    }
+
+    /// Parse `nix profile list --json` output into store paths.
+    ///
+    /// Newer Nix versions key `elements` by name, older ones use an array.
+    pub fn parse_profile_list_json(json_str: &str) -> Vec<PathBuf> {
+        let value: serde_json::Value = match serde_json::from_str(json_str) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+
+        let elements: Vec<&serde_json::Value> = match value.get("elements") {
+            Some(serde_json::Value::Array(arr)) => arr.iter().collect(),
+            Some(serde_json::Value::Object(map)) => map.values().collect(),
+            _ => return Vec::new(),
+        };
+
+        let mut paths = Vec::new();
+        for element in elements {
+            if element.get("active").and_then(|a| a.as_bool()) == Some(false) {
+                continue;
+            }
+            if let Some(store_paths) = element.get("storePaths").and_then(|v| v.as_array()) {
+                for p in store_paths.iter().filter_map(|p| p.as_str()) {
+                    paths.push(PathBuf::from(p));
+                }
+            }
+        }
+
+        paths
+    }
+
+    /// Extract the package name from a store path like
+    /// `/nix/store/<hash>-ripgrep-14.1.0/bin/rg`.
+    pub fn store_package_name(path: &Path) -> Option<String> {
+        let rest = path.strip_prefix("/nix/store").ok()?;
+        let entry = rest.components().next()?.as_os_str().to_str()?;
+        let (hash, name_version) = entry.split_once('-')?;
+        if hash.len() != 32 || name_version.is_empty() {
+            return None;
+        }
+
+        // The version starts at the first '-' followed by a digit
+        let bytes = name_version.as_bytes();
+        for i in 0..bytes.len().saturating_sub(1) {
+            if bytes[i] == b'-' && bytes[i + 1].is_ascii_digit() {
+                return Some(name_version[..i].to_string());
+            }
+        }
+
+        Some(name_version.to_string())
+    }
+
+    /// Scan a profile (or store path) for desktop entries and executables.
+    fn scan_profile(&self, root: &Path, seen: &mut HashSet<PathBuf>) -> Vec<Application> {
+        let mut apps = Vec::new();
+        let mut desktop_execs = HashSet::new();
+
+        let apps_dir = root.join("share/applications");
+        if let Ok(entries) = fs::read_dir(&apps_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
+                    continue;
+                }
+
+                let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+                if !seen.insert(canonical) {
+                    continue;
+                }
+
+                let content = match fs::read_to_string(&path) {
+                    Ok(c) => c,
+                    Err(_) => continue,
+                };
+
+                match DesktopProvider::parse_desktop_content_in(&content, &path, &self.context) {
+                    Ok(Some(mut app)) => {
+                        if let Some(exec_name) = app
+                            .exec_command
+                            .split_whitespace()
+                            .next()
+                            .and_then(|e| Path::new(e).file_name())
+                            .and_then(|n| n.to_str())
+                        {
+                            desktop_execs.insert(exec_name.to_string());
+                        }
+                        app.source = AppSource::Nix;
+                        apps.push(app);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("appgrep: warning: {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+
+        let bin_dir = root.join("bin");
+        let entries = match fs::read_dir(&bin_dir) {
+            Ok(e) => e,
+            Err(_) => return apps,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            let metadata = match fs::metadata(&path) {
+                Ok(m) => m,
+                Err(_) => continue, // broken symlink
+            };
+
+            if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+                continue;
+            }
+
+            let name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(n) => n.to_string(),
+                None => continue,
+            };
+
+            let store_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+            if !seen.insert(store_path.clone()) {
+                continue;
+            }
+
+            // Already represented by one of the profile's desktop entries
+            if desktop_execs.contains(&name) {
+                continue;
+            }
+
+            let description = Self::store_package_name(&store_path)
+                .filter(|pkg| *pkg != name)
+                .map(|pkg| format!("Provided by {}", pkg));
+
+            apps.push(Application {
+                name,
+                exec_command: path.to_string_lossy().to_string(),
+                source: AppSource::Nix,
+                location: store_path.to_string_lossy().to_string(),
+                categories: vec!["CLI".to_string()],
+                description,
+                ..Default::default()
+            });
+        }
+
+        apps
+    }
+}
+
+impl AppProvider for NixProvider {
+    fn name(&self) -> &str {
+        "nix"
+    }
+
+    fn is_available(&self) -> bool {
+        Self::profile_dirs().iter().any(|d| d.is_dir()) || Self::has_nix()
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        let mut seen = HashSet::new();
+        let mut apps = Vec::new();
+
+        for dir in Self::profile_dirs() {
+            if dir.is_dir() {
+                apps.extend(self.scan_profile(&dir, &mut seen));
+            }
+        }
+
+        if Self::has_nix() {
+            for store_path in Self::profile_store_paths() {
+                if store_path.is_dir() {
+                    apps.extend(self.scan_profile(&store_path, &mut seen));
+                }
+            }
+        }
+
+        Ok(apps)
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Self::profile_dirs();
+        if let Some(home) = dirs::home_dir() {
+            // `nix profile` keeps its manifest here on newer installs
+            paths.push(home.join(".local/state/nix/profiles/profile"));
+        }
+        paths
+    }
+
+    fn cache_key(&self) -> String {
+        self.context.cache_key(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_executable(path: &Path) {
+        fs::write(path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_provider_name() {
+        let provider = NixProvider::new();
+        assert_eq!(provider.name(), "nix");
+    }
+
+    #[test]
+    fn test_parse_profile_list_json_object() {
+        let json = r#"{
+            "elements": {
+                "ripgrep": {
+                    "active": true,
+                    "originalUrl": "flake:nixpkgs",
+                    "storePaths": ["/nix/store/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa-ripgrep-14.1.0"]
+                },
+                "old": {
+                    "active": false,
+                    "storePaths": ["/nix/store/bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb-old-1.0"]
+                }
+            },
+            "version": 3
+        }"#;
+        let paths = NixProvider::parse_profile_list_json(json);
+        assert_eq!(
+            paths,
+            vec![PathBuf::from(
+                "/nix/store/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa-ripgrep-14.1.0"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_profile_list_json_array() {
+        let json = r#"{
+            "elements": [
+                {"storePaths": ["/nix/store/cccccccccccccccccccccccccccccccc-hello-2.12.1"]}
+            ],
+            "version": 2
+        }"#;
+        let paths = NixProvider::parse_profile_list_json(json);
+        assert_eq!(paths.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_profile_list_json_invalid() {
+        assert!(NixProvider::parse_profile_list_json("not json").is_empty());
+        assert!(NixProvider::parse_profile_list_json("{}").is_empty());
+    }
+
+    #[test]
+    fn test_store_package_name() {
+        let path = Path::new(
+            "/nix/store/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa-ripgrep-14.1.0/bin/rg",
+        );
+        assert_eq!(
+            NixProvider::store_package_name(path),
+            Some("ripgrep".to_string())
+        );
+
+        let path = Path::new(
+            "/nix/store/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa-gnome-text-editor-46.3/bin/x",
+        );
+        assert_eq!(
+            NixProvider::store_package_name(path),
+            Some("gnome-text-editor".to_string())
+        );
+
+        assert_eq!(NixProvider::store_package_name(Path::new("/usr/bin/rg")), None);
+    }
+
+    #[test]
+    fn test_scan_profile() {
+        let tmp = TempDir::new().unwrap();
+        let bin_dir = tmp.path().join("bin");
+        let apps_dir = tmp.path().join("share/applications");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::create_dir_all(&apps_dir).unwrap();
+
+        write_executable(&bin_dir.join("rg"));
+        write_executable(&bin_dir.join("firefox"));
+        fs::write(bin_dir.join("README"), "not executable").unwrap();
+        fs::write(
+            apps_dir.join("firefox.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Firefox\nExec=firefox %U\n",
+        )
+        .unwrap();
+
+        let provider = NixProvider::new();
+        let mut seen = HashSet::new();
+        let mut apps = provider.scan_profile(tmp.path(), &mut seen);
+        apps.sort();
+
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0].name, "Firefox");
+        assert_eq!(apps[0].source, AppSource::Nix);
+        assert_eq!(apps[1].name, "rg");
+        assert_eq!(apps[1].source, AppSource::Nix);
+        assert_eq!(apps[1].categories, vec!["CLI"]);
+
+        // Scanning the same profile again yields nothing new
+        assert!(provider.scan_profile(tmp.path(), &mut seen).is_empty());
+    }
+}
@@ -4,8 +4,10 @@ use std::path::PathBuf;
 use std::process::Command;
 
 use crate::app::{AppSource, Application};
+use crate::provider::process::{self, has_command};
 use crate::provider::{AppProvider, ProviderError};
 
+#[derive(Default)]
 pub struct NpmProvider;
 
 impl NpmProvider {
@@ -14,18 +16,12 @@ impl NpmProvider {
     }
 
     fn has_npm() -> bool {
-        Command::new("which")
-            .arg("npm")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+        has_command("npm")
     }
 
     fn get_global_bin_dir() -> Option<PathBuf> {
         // Try npm root -g and derive bin dir
-        if let Ok(output) = Command::new("npm").args(["root", "-g"]).output() {
+        if let Ok(output) = process::output(Command::new("npm").args(["root", "-g"])) {
             if output.status.success() {
                 let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
                 // Replace trailing /node_modules with /bin
@@ -114,9 +110,8 @@ impl NpmProvider {
                 exec_command: abs_path.clone(),
                 source: AppSource::Npm,
                 location: abs_path,
-                icon: None,
                 categories: vec!["Development".to_string()],
-                description: None,
+                ..Default::default()
             });
         }
 
@@ -1,45 +1,46 @@
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use crate::app::{AppSource, Application};
-use crate::provider::{AppProvider, ProviderError};
+use crate::provider::process::{self, has_command};
+use crate::provider::{
+    package_bin_dirs, select_package_binaries, ships_desktop_file, AppProvider, BinaryMode,
+    ProviderError,
+};
+
+pub struct PacmanProvider {
+    binary_mode: BinaryMode,
+    bin_dirs: Vec<PathBuf>,
+}
 
-pub struct PacmanProvider;
+impl Default for PacmanProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl PacmanProvider {
     pub fn new() -> Self {
-        Self
+        Self {
+            binary_mode: BinaryMode::default(),
+            bin_dirs: package_bin_dirs(),
+        }
+    }
+
+    pub fn with_binary_mode(mut self, binary_mode: BinaryMode) -> Self {
+        self.binary_mode = binary_mode;
+        self
+    }
+
+    /// Report executables from `bin_dirs` instead of the standard system directories.
+    pub fn with_bin_dirs(mut self, bin_dirs: Vec<PathBuf>) -> Self {
+        self.bin_dirs = bin_dirs;
+        self
     }
 
     fn has_pacman() -> bool {
-        Command::new("which")
-            .arg("pacman")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
-    }
-
-    fn has_desktop_file(pkg: &str) -> bool {
-        let apps_dir = Path::new("/usr/share/applications");
-        if !apps_dir.is_dir() {
-            return false;
-        }
-        if apps_dir.join(format!("{}.desktop", pkg)).exists() {
-            return true;
-        }
-        if let Ok(entries) = std::fs::read_dir(apps_dir) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if name.starts_with(pkg) && name.ends_with(".desktop") {
-                        return true;
-                    }
-                }
-            }
-        }
-        false
+        has_command("pacman")
     }
 
     pub fn parse_pacman_info(output: &str) -> Vec<(String, Option<String>)> {
@@ -68,38 +69,29 @@ impl PacmanProvider {
         packages
     }
 
-    fn find_package_binary(pkg: &str) -> Option<String> {
-        // First check if /usr/bin/<pkg> exists directly
-        let direct = format!("/usr/bin/{}", pkg);
-        if Path::new(&direct).is_file() {
-            return Some(direct);
-        }
-
-        // Fall back to pacman -Ql
-        let output = Command::new("pacman")
-            .args(["-Ql", pkg])
-            .output()
-            .ok()?;
-
-        if !output.status.success() {
-            return None;
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        for line in stdout.lines() {
-            // Format: "pkgname /usr/bin/something"
-            let parts: Vec<&str> = line.splitn(2, ' ').collect();
-            if parts.len() == 2 {
-                let path = parts[1].trim();
-                if (path.starts_with("/usr/bin/") || path.starts_with("/usr/local/bin/"))
-                    && Path::new(path).is_file()
-                {
-                    return Some(path.to_string());
+    /// Parse `pacman -Ql` output (`<pkg> <path>` per line) into per-package file lists.
+    pub fn parse_pacman_file_list(output: &str) -> HashMap<String, Vec<String>> {
+        let mut files: HashMap<String, Vec<String>> = HashMap::new();
+        for line in output.lines() {
+            if let Some((name, path)) = line.split_once(' ') {
+                let path = path.trim();
+                // Directories are listed with a trailing slash
+                if !name.is_empty() && !path.is_empty() && !path.ends_with('/') {
+                    files.entry(name.to_string()).or_default().push(path.to_string());
                 }
             }
         }
+        files
+    }
+
+    fn package_files() -> HashMap<String, Vec<String>> {
+        let output = match process::output(Command::new("pacman").arg("-Ql")) {
+            Ok(o) if o.status.success() => o,
+            _ => return HashMap::new(),
+        };
 
-        None
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse_pacman_file_list(&stdout)
     }
 }
 
@@ -118,10 +110,7 @@ impl AppProvider for PacmanProvider {
         }
 
         // Get list of installed packages
-        let list_output = Command::new("pacman")
-            .args(["-Qq"])
-            .output()
-            .map_err(ProviderError::Io)?;
+        let list_output = process::output(Command::new("pacman").args(["-Qq"]))?;
 
         if !list_output.status.success() {
             return Ok(Vec::new());
@@ -136,22 +125,28 @@ impl AppProvider for PacmanProvider {
         for name in &pkg_names {
             info_cmd.arg(name);
         }
-        let info_output = info_cmd
-            .output()
-            .map_err(ProviderError::Io)?;
+        let info_output = process::output(&mut info_cmd)?;
 
         let info_stdout = String::from_utf8_lossy(&info_output.stdout);
         let packages = Self::parse_pacman_info(&info_stdout);
+        let package_files = Self::package_files();
 
         let mut seen_binaries = HashSet::new();
         let mut apps = Vec::new();
 
         for (pkg_name, description) in packages {
-            if Self::has_desktop_file(&pkg_name) {
+            let files = match package_files.get(&pkg_name) {
+                Some(f) => f,
+                None => continue,
+            };
+
+            if self.binary_mode == BinaryMode::Primary && ships_desktop_file(files) {
                 continue;
             }
 
-            if let Some(binary) = Self::find_package_binary(&pkg_name) {
+            let binaries =
+                select_package_binaries(&pkg_name, files, self.binary_mode, &self.bin_dirs);
+            for binary in binaries {
                 if !seen_binaries.insert(binary.clone()) {
                     continue;
                 }
@@ -167,15 +162,24 @@ impl AppProvider for PacmanProvider {
                     exec_command: binary.clone(),
                     source: AppSource::Pacman,
                     location: binary,
-                    icon: None,
                     categories: vec!["CLI".to_string()],
-                    description,
+                    description: description.clone(),
+                    metadata: BTreeMap::from([("package".to_string(), pkg_name.clone())]),
+                    ..Default::default()
                 });
             }
         }
 
         Ok(apps)
     }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![PathBuf::from("/var/lib/pacman/local")]
+    }
+
+    fn cache_key(&self) -> String {
+        self.binary_mode.cache_key(self.name())
+    }
 }
 
 #[cfg(test)]
@@ -221,6 +225,15 @@ Description     : command line tool for transferring data
         assert_eq!(packages[0].1, Some("Vi Improved".to_string()));
     }
 
+    #[test]
+    fn test_parse_pacman_file_list() {
+        let output = "binutils /usr/\nbinutils /usr/bin/\nbinutils /usr/bin/objdump\nbinutils /usr/bin/ld\nvim /usr/bin/vim\n";
+        let files = PacmanProvider::parse_pacman_file_list(output);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files["binutils"], vec!["/usr/bin/objdump", "/usr/bin/ld"]);
+        assert_eq!(files["vim"], vec!["/usr/bin/vim"]);
+    }
+
     #[test]
     fn test_parse_pacman_info_no_description() {
         let output = "Name            : somepkg\n";
@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::{AppSource, Application};
+use crate::provider::python::find_dist_info;
+use crate::provider::{AppProvider, ProviderError};
+
+#[derive(Default)]
+pub struct PipxProvider;
+
+/// The parts of a venv's `pipx_metadata.json` that appgrep cares about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipxVenv {
+    pub package: String,
+    pub version: Option<String>,
+    pub apps: Vec<String>,
+    pub suffix: String,
+}
+
+impl PipxProvider {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// `$PIPX_HOME`, or the default `~/.local/share/pipx` (`~/.local/pipx` on old installs).
+    fn pipx_home() -> Option<PathBuf> {
+        if let Some(home) = std::env::var_os("PIPX_HOME") {
+            return Some(PathBuf::from(home));
+        }
+        let home = dirs::home_dir()?;
+        let legacy = home.join(".local/pipx");
+        if legacy.is_dir() {
+            return Some(legacy);
+        }
+        Some(home.join(".local/share/pipx"))
+    }
+
+    /// `$PIPX_BIN_DIR`, or the default `~/.local/bin`.
+    fn pipx_bin_dir() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("PIPX_BIN_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        dirs::home_dir().map(|h| h.join(".local/bin"))
+    }
+
+    fn venvs_dir() -> Option<PathBuf> {
+        Self::pipx_home().map(|h| h.join("venvs"))
+    }
+
+    /// `venvs_dir` and the metadata of each venv in it, which `pipx upgrade` and
+    /// `pipx inject` rewrite without touching `venvs_dir`.
+    fn watch_paths_in(venvs_dir: &Path) -> Vec<PathBuf> {
+        let mut metadata: Vec<PathBuf> = fs::read_dir(venvs_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path().join("pipx_metadata.json"))
+            .collect();
+        metadata.sort();
+        std::iter::once(venvs_dir.to_path_buf()).chain(metadata).collect()
+    }
+
+    pub fn parse_pipx_metadata(json_str: &str) -> Option<PipxVenv> {
+        let value: serde_json::Value = serde_json::from_str(json_str).ok()?;
+        let main = value.get("main_package")?;
+
+        let package = main.get("package").and_then(|v| v.as_str())?.to_string();
+        if package.is_empty() {
+            return None;
+        }
+
+        let version = main
+            .get("package_version")
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string());
+
+        let mut apps: Vec<String> = main
+            .get("apps")
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|a| a.as_str())
+                    .map(|a| a.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Apps of dependencies are only exposed with --include-deps
+        if main.get("include_dependencies").and_then(|v| v.as_bool()) == Some(true) {
+            if let Some(deps) = main.get("apps_of_dependencies").and_then(|v| v.as_array()) {
+                apps.extend(deps.iter().filter_map(|a| a.as_str()).map(|a| a.to_string()));
+            }
+        }
+
+        let suffix = main
+            .get("suffix")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        Some(PipxVenv {
+            package,
+            version,
+            apps,
+            suffix,
+        })
+    }
+
+    /// Build one application per entry point exposed by a pipx venv.
+    fn venv_apps(venv_dir: &Path, bin_dir: Option<&Path>) -> Vec<Application> {
+        let content = match fs::read_to_string(venv_dir.join("pipx_metadata.json")) {
+            Ok(c) => c,
+            Err(_) => return Vec::new(),
+        };
+
+        let venv = match Self::parse_pipx_metadata(&content) {
+            Some(v) => v,
+            None => {
+                eprintln!(
+                    "appgrep: warning: {}: invalid pipx metadata",
+                    venv_dir.display()
+                );
+                return Vec::new();
+            }
+        };
+
+        let dist_info = find_dist_info(venv_dir, &venv.package).unwrap_or_default();
+        let version = venv.version.clone().or(dist_info.version);
+
+        let mut metadata = BTreeMap::new();
+        metadata.insert("package".to_string(), venv.package.clone());
+        if let Some(ref v) = version {
+            metadata.insert("version".to_string(), v.clone());
+        }
+
+        let mut apps = Vec::new();
+        for app in &venv.apps {
+            let exposed_name = format!("{}{}", app, venv.suffix);
+
+            // Prefer the exposed link, fall back to the script inside the venv
+            let exposed = bin_dir.map(|d| d.join(&exposed_name));
+            let path = match exposed.filter(|p| p.exists()) {
+                Some(p) => p,
+                None => venv_dir.join("bin").join(app),
+            };
+            if !path.exists() {
+                continue;
+            }
+
+            let abs_path = path.canonicalize().unwrap_or(path);
+            let exec = abs_path.to_string_lossy().to_string();
+
+            apps.push(Application {
+                name: exposed_name,
+                exec_command: exec,
+                source: AppSource::Pipx,
+                location: venv_dir.to_string_lossy().to_string(),
+                categories: vec!["Python".to_string()],
+                description: dist_info.summary.clone(),
+                metadata: metadata.clone(),
+                ..Default::default()
+            });
+        }
+
+        apps
+    }
+}
+
+impl AppProvider for PipxProvider {
+    fn name(&self) -> &str {
+        "pipx"
+    }
+
+    fn is_available(&self) -> bool {
+        Self::venvs_dir().is_some_and(|d| d.is_dir())
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        let venvs_dir = match Self::venvs_dir() {
+            Some(d) if d.is_dir() => d,
+            _ => return Ok(Vec::new()),
+        };
+        let bin_dir = Self::pipx_bin_dir();
+
+        let entries = fs::read_dir(&venvs_dir).map_err(ProviderError::Io)?;
+        let mut apps = Vec::new();
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                apps.extend(Self::venv_apps(&path, bin_dir.as_deref()));
+            }
+        }
+
+        Ok(apps)
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        match Self::venvs_dir() {
+            Some(dir) => Self::watch_paths_in(&dir),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    const METADATA: &str = r#"{
+        "main_package": {
+            "package": "black",
+            "package_version": "24.1.0",
+            "apps": ["black", "blackd"],
+            "apps_of_dependencies": ["dep-tool"],
+            "include_dependencies": false,
+            "suffix": ""
+        },
+        "injected_packages": {},
+        "pipx_metadata_version": "0.5"
+    }"#;
+
+    #[test]
+    fn test_provider_name() {
+        let provider = PipxProvider::new();
+        assert_eq!(provider.name(), "pipx");
+    }
+
+    #[test]
+    fn test_parse_pipx_metadata() {
+        let venv = PipxProvider::parse_pipx_metadata(METADATA).unwrap();
+        assert_eq!(venv.package, "black");
+        assert_eq!(venv.version, Some("24.1.0".to_string()));
+        assert_eq!(venv.apps, vec!["black", "blackd"]);
+        assert_eq!(venv.suffix, "");
+    }
+
+    #[test]
+    fn test_parse_pipx_metadata_include_deps() {
+        let json = METADATA.replace(
+            "\"include_dependencies\": false",
+            "\"include_dependencies\": true",
+        );
+        let venv = PipxProvider::parse_pipx_metadata(&json).unwrap();
+        assert_eq!(venv.apps, vec!["black", "blackd", "dep-tool"]);
+    }
+
+    #[test]
+    fn test_parse_pipx_metadata_invalid() {
+        assert!(PipxProvider::parse_pipx_metadata("not json").is_none());
+        assert!(PipxProvider::parse_pipx_metadata("{}").is_none());
+    }
+
+    #[test]
+    fn test_venv_apps() {
+        let tmp = TempDir::new().unwrap();
+        let venv_dir = tmp.path().join("venvs/black");
+        let venv_bin = venv_dir.join("bin");
+        fs::create_dir_all(&venv_bin).unwrap();
+        fs::write(venv_dir.join("pipx_metadata.json"), METADATA).unwrap();
+
+        for script in ["black", "blackd"] {
+            let path = venv_bin.join(script);
+            fs::write(&path, "#!/usr/bin/env python\n").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let dist = venv_dir.join("lib/python3.12/site-packages/black-24.1.0.dist-info");
+        fs::create_dir_all(&dist).unwrap();
+        fs::write(
+            dist.join("METADATA"),
+            "Name: black\nVersion: 24.1.0\nSummary: The uncompromising code formatter.\n",
+        )
+        .unwrap();
+
+        let mut apps = PipxProvider::venv_apps(&venv_dir, None);
+        apps.sort();
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0].name, "black");
+        assert_eq!(apps[0].source, AppSource::Pipx);
+        assert_eq!(
+            apps[0].description,
+            Some("The uncompromising code formatter.".to_string())
+        );
+        assert_eq!(apps[0].metadata.get("package").unwrap(), "black");
+        assert_eq!(apps[0].metadata.get("version").unwrap(), "24.1.0");
+        assert_eq!(apps[1].name, "blackd");
+    }
+
+    #[test]
+    fn test_watch_paths_include_metadata() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("venvs");
+        fs::create_dir_all(dir.join("ruff")).unwrap();
+        fs::create_dir_all(dir.join("black")).unwrap();
+        assert_eq!(
+            PipxProvider::watch_paths_in(&dir),
+            vec![
+                dir.clone(),
+                dir.join("black/pipx_metadata.json"),
+                dir.join("ruff/pipx_metadata.json"),
+            ]
+        );
+        assert_eq!(PipxProvider::watch_paths_in(&tmp.path().join("missing")).len(), 1);
+    }
+}
@@ -0,0 +1,266 @@
+//! External provider plugins.
+//!
+//! Every executable in a `providers.d` directory is wrapped as a [`PluginProvider`]:
+//!
+//! - `<plugin> probe` must exit 0 when the plugin can discover anything on this system.
+//! - `<plugin> discover` prints a JSON array of applications in the same shape as
+//!   `appgrep --format json list`. Every entry's `source` is the plugin's name, which is
+//!   its file name without extension; `location` defaults to the exec command.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::app::{AppSource, Application};
+use crate::provider::process::{self, output_with_timeout};
+use crate::provider::{AppProvider, ProviderError};
+
+/// How long the availability probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct PluginProvider {
+    name: String,
+    path: PathBuf,
+}
+
+impl PluginProvider {
+    /// Wrap the plugin executable at `path`; `None` if its name is not a valid source name.
+    pub fn new(path: PathBuf) -> Option<Self> {
+        let stem = path.file_stem()?.to_str()?;
+        let name = match stem.parse::<AppSource>() {
+            Ok(AppSource::Custom(name)) => name,
+            // Plugins may not pose as a built-in source
+            _ => return None,
+        };
+        Some(Self { name, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `providers.d` directories, lowest precedence first.
+    pub fn plugin_dirs() -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+
+        let config_dirs = std::env::var("XDG_CONFIG_DIRS")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| "/etc/xdg".to_string());
+        for dir in config_dirs.split(':').rev().filter(|d| !d.is_empty()) {
+            dirs.push(PathBuf::from(dir).join("appgrep/providers.d"));
+        }
+        if let Some(dir) = dirs::config_dir() {
+            dirs.push(dir.join("appgrep/providers.d"));
+        }
+
+        dirs
+    }
+
+    /// Load every plugin from `dirs`; a plugin in a later directory replaces one of the same name.
+    pub fn load_all(dirs: &[PathBuf]) -> Vec<PluginProvider> {
+        let mut plugins: BTreeMap<String, PluginProvider> = BTreeMap::new();
+
+        for dir in dirs {
+            let entries = match fs::read_dir(dir) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let executable = fs::metadata(&path)
+                    .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                    .unwrap_or(false);
+                if !executable {
+                    continue;
+                }
+                match PluginProvider::new(path) {
+                    Some(plugin) => {
+                        plugins.insert(plugin.name.clone(), plugin);
+                    }
+                    None => eprintln!(
+                        "appgrep: warning: ignoring plugin {}: invalid provider name",
+                        entry.path().display()
+                    ),
+                }
+            }
+        }
+
+        plugins.into_values().collect()
+    }
+
+    /// Parse a plugin's `discover` output.
+    pub fn parse_output(&self, json_str: &str) -> Result<Vec<Application>, ProviderError> {
+        let value: serde_json::Value = serde_json::from_str(json_str)
+            .map_err(|e| ProviderError::ParseError(format!("plugin '{}': {}", self.name, e)))?;
+        let entries = match value {
+            serde_json::Value::Array(entries) => entries,
+            _ => {
+                return Err(ProviderError::ParseError(format!(
+                    "plugin '{}': expected a JSON array",
+                    self.name
+                )))
+            }
+        };
+
+        let mut apps = Vec::new();
+        for mut entry in entries {
+            // A plugin always reports under its own name, whatever the entry says
+            if let Some(obj) = entry.as_object_mut() {
+                obj.insert(
+                    "source".to_string(),
+                    serde_json::Value::String(self.name.clone()),
+                );
+            }
+
+            let mut app: Application = match serde_json::from_value(entry) {
+                Ok(app) => app,
+                Err(e) => {
+                    eprintln!(
+                        "appgrep: warning: plugin '{}': skipping entry: {}",
+                        self.name, e
+                    );
+                    continue;
+                }
+            };
+            if app.name.is_empty() || app.exec_command.is_empty() {
+                continue;
+            }
+            if app.location.is_empty() {
+                app.location = app.exec_command.clone();
+            }
+            apps.push(app);
+        }
+
+        Ok(apps)
+    }
+}
+
+impl AppProvider for PluginProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_available(&self) -> bool {
+        output_with_timeout(Command::new(&self.path).arg("probe"), PROBE_TIMEOUT)
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        let output = process::output(Command::new(&self.path).arg("discover"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ProviderError::ParseError(format!(
+                "plugin '{}' exited with {}: {}",
+                self.name,
+                output.status,
+                stderr.trim()
+            )));
+        }
+
+        self.parse_output(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_plugin(dir: &Path, name: &str, script: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_new_derives_name_from_file() {
+        let plugin = PluginProvider::new(PathBuf::from("/x/acme-deploy.sh")).unwrap();
+        assert_eq!(plugin.name(), "acme-deploy");
+        assert!(PluginProvider::new(PathBuf::from("/x/desktop")).is_none());
+        assert!(PluginProvider::new(PathBuf::from("/x/bad name")).is_none());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        let plugin = PluginProvider::new(PathBuf::from("/x/acme")).unwrap();
+        let json = r#"[
+            {"name": "Deployer", "exec_command": "/srv/acme/bin/deployer", "icon": null,
+             "description": "Ship it", "metadata": {"version": "2.1"}},
+            {"name": "Other", "exec_command": "/srv/other", "source": "desktop",
+             "location": "/srv", "icon": null, "categories": ["Dev"], "description": null},
+            {"name": "", "exec_command": "/srv/empty", "icon": null, "description": null},
+            {"exec_command": "missing name"}
+        ]"#;
+        let apps = plugin.parse_output(json).unwrap();
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0].source, AppSource::Custom("acme".to_string()));
+        assert_eq!(apps[0].location, "/srv/acme/bin/deployer");
+        assert_eq!(apps[0].metadata.get("version").unwrap(), "2.1");
+        assert_eq!(apps[1].source, AppSource::Custom("acme".to_string()));
+        assert_eq!(apps[1].location, "/srv");
+        assert_eq!(apps[1].categories, vec!["Dev"]);
+    }
+
+    #[test]
+    fn test_parse_output_rejects_non_array() {
+        let plugin = PluginProvider::new(PathBuf::from("/x/acme")).unwrap();
+        assert!(plugin.parse_output("{}").is_err());
+        assert!(plugin.parse_output("not json").is_err());
+    }
+
+    #[test]
+    fn test_load_all_later_dir_wins() {
+        let system = TempDir::new().unwrap();
+        let user = TempDir::new().unwrap();
+        write_plugin(system.path(), "acme", "#!/bin/sh\n");
+        let user_path = write_plugin(user.path(), "acme", "#!/bin/sh\n");
+        fs::write(user.path().join("notes.txt"), "not executable").unwrap();
+
+        let plugins =
+            PluginProvider::load_all(&[system.path().to_path_buf(), user.path().to_path_buf()]);
+        assert_eq!(plugins.len(), 1);
+        assert_eq!(plugins[0].path(), user_path);
+    }
+
+    #[test]
+    fn test_probe_and_discover() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_plugin(
+            tmp.path(),
+            "acme",
+            r#"#!/bin/sh
+case "$1" in
+    probe) exit 0 ;;
+    discover) echo '[{"name": "deployer", "exec_command": "/srv/deployer", "icon": null, "description": null}]' ;;
+esac
+"#,
+        );
+        let plugin = PluginProvider::new(path).unwrap();
+        assert!(plugin.is_available());
+        let apps = plugin.discover().unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "deployer");
+    }
+
+    #[test]
+    fn test_unavailable_when_probe_fails() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_plugin(tmp.path(), "acme", "#!/bin/sh\nexit 1\n");
+        assert!(!PluginProvider::new(path).unwrap().is_available());
+    }
+
+    #[test]
+    fn test_discover_times_out() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_plugin(tmp.path(), "slow", "#!/bin/sh\nexec sleep 10\n");
+        let plugin = PluginProvider::new(path).unwrap();
+        let result = process::with_deadline(Duration::from_millis(100), || plugin.discover());
+        assert!(matches!(result, Err(ProviderError::Timeout(_))));
+    }
+}
@@ -0,0 +1,158 @@
+//! Running external commands with a deadline.
+//!
+//! Providers run their subprocesses through [`output`], which is bounded by the deadline
+//! the engine sets for the calling thread with [`with_deadline`], or by
+//! [`DEFAULT_TIMEOUT`] outside of it.
+
+use std::cell::Cell;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::provider::ProviderError;
+
+/// How long a provider may take when no timeout is configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+thread_local! {
+    /// Deadline of the provider running on this thread, and the timeout it derives from.
+    static DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+}
+
+/// Run `f` with every [`output`] call on this thread bounded by `timeout` in total.
+pub fn with_deadline<T>(timeout: Duration, f: impl FnOnce() -> T) -> T {
+    let previous = DEADLINE.with(|d| d.replace(Some((Instant::now() + timeout, timeout))));
+    let result = f();
+    DEADLINE.with(|d| d.set(previous));
+    result
+}
+
+/// Run `cmd` to completion within the time left before this thread's deadline.
+pub fn output(cmd: &mut Command) -> Result<Output, ProviderError> {
+    match DEADLINE.with(|d| d.get()) {
+        Some((deadline, timeout)) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(ProviderError::Timeout(timeout));
+            }
+            output_with_timeout(cmd, remaining).map_err(|e| match e {
+                ProviderError::Timeout(_) => ProviderError::Timeout(timeout),
+                e => e,
+            })
+        }
+        None => output_with_timeout(cmd, DEFAULT_TIMEOUT),
+    }
+}
+
+/// Whether `program` is found on `$PATH`.
+pub fn has_command(program: &str) -> bool {
+    output(Command::new("which").arg(program))
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Like `Command::output`, but kill the child and fail with `ProviderError::Timeout`
+/// if it has not exited within `timeout`. Stdin is closed.
+pub fn output_with_timeout(cmd: &mut Command, timeout: Duration) -> Result<Output, ProviderError> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain both pipes concurrently so a chatty child cannot block on a full pipe
+    let stdout = child.stdout.take().map(spawn_reader);
+    let stderr = child.stderr.take().map(spawn_reader);
+
+    let deadline = Instant::now() + timeout;
+    let mut poll = Duration::from_millis(1);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(ProviderError::Timeout(timeout));
+        }
+        thread::sleep(poll.min(deadline - now));
+        poll = (poll * 2).min(Duration::from_millis(50));
+    };
+
+    let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+        reader.and_then(|r| r.join().ok()).unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+fn spawn_reader<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_with_timeout_success() {
+        let output = output_with_timeout(
+            Command::new("sh").args(["-c", "echo out; echo err >&2"]),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn test_output_with_timeout_kills_slow_child() {
+        let start = Instant::now();
+        let result = output_with_timeout(
+            Command::new("sh").args(["-c", "exec sleep 10"]),
+            Duration::from_millis(100),
+        );
+        assert!(matches!(result, Err(ProviderError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_output_shares_the_thread_deadline() {
+        let timeout = Duration::from_millis(200);
+        let result = with_deadline(timeout, || {
+            output(Command::new("sh").args(["-c", "sleep 0.15"])).unwrap();
+            output(Command::new("sh").args(["-c", "exec sleep 10"]))
+        });
+        match result {
+            Err(ProviderError::Timeout(t)) => assert_eq!(t, timeout),
+            other => panic!("expected timeout, got {:?}", other.map(|o| o.status)),
+        }
+        // The deadline ends with the closure
+        assert!(output(Command::new("sh").args(["-c", "true"])).is_ok());
+    }
+
+    #[test]
+    fn test_has_command() {
+        assert!(has_command("sh"));
+        assert!(!has_command("appgrep-definitely-missing-command"));
+    }
+
+    #[test]
+    fn test_output_with_timeout_missing_program() {
+        let result = output_with_timeout(
+            &mut Command::new("/nonexistent/appgrep-test-binary"),
+            Duration::from_secs(1),
+        );
+        assert!(matches!(result, Err(ProviderError::Io(_))));
+    }
+}
@@ -0,0 +1,151 @@
+//! Helpers shared by the pipx and uv providers for reading Python virtualenvs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Package details read from a `*.dist-info/METADATA` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DistInfo {
+    pub version: Option<String>,
+    pub summary: Option<String>,
+}
+
+/// Normalize a Python package name per PEP 503 (`Foo_Bar.baz` -> `foo-bar-baz`).
+pub fn normalize_package_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut last_sep = false;
+    for c in name.chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !last_sep {
+                normalized.push('-');
+            }
+            last_sep = true;
+        } else {
+            normalized.extend(c.to_lowercase());
+            last_sep = false;
+        }
+    }
+    normalized
+}
+
+/// Parse the header block of a core metadata file.
+pub fn parse_metadata(content: &str) -> DistInfo {
+    let mut info = DistInfo::default();
+    for line in content.lines() {
+        // Headers end at the first blank line; the long description follows
+        if line.is_empty() {
+            break;
+        }
+        if let Some(version) = line.strip_prefix("Version: ") {
+            info.version = Some(version.trim().to_string()).filter(|v| !v.is_empty());
+        } else if let Some(summary) = line.strip_prefix("Summary: ") {
+            info.summary = Some(summary.trim().to_string())
+                .filter(|s| !s.is_empty() && s != "UNKNOWN");
+        }
+    }
+    info
+}
+
+/// All `site-packages` directories inside a virtualenv.
+fn site_packages_dirs(venv: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for lib in ["lib", "lib64"] {
+        let entries = match fs::read_dir(venv.join(lib)) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with("python") {
+                let site = entry.path().join("site-packages");
+                if site.is_dir() && !dirs.contains(&site) {
+                    dirs.push(site);
+                }
+            }
+        }
+    }
+    dirs
+}
+
+/// Find and read the dist-info metadata of `package` inside a virtualenv.
+pub fn find_dist_info(venv: &Path, package: &str) -> Option<DistInfo> {
+    let wanted = normalize_package_name(package);
+
+    for site in site_packages_dirs(venv) {
+        let entries = match fs::read_dir(&site) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            let stem = match file_name.strip_suffix(".dist-info") {
+                Some(s) => s,
+                None => continue,
+            };
+            let (name, version) = match stem.rsplit_once('-') {
+                Some(parts) => parts,
+                None => continue,
+            };
+            if normalize_package_name(name) != wanted {
+                continue;
+            }
+
+            let mut info = fs::read_to_string(entry.path().join("METADATA"))
+                .map(|c| parse_metadata(&c))
+                .unwrap_or_default();
+            if info.version.is_none() {
+                info.version = Some(version.to_string());
+            }
+            return Some(info);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_normalize_package_name() {
+        assert_eq!(normalize_package_name("Black"), "black");
+        assert_eq!(normalize_package_name("python_dateutil"), "python-dateutil");
+        assert_eq!(normalize_package_name("zope..interface"), "zope-interface");
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let content = "Metadata-Version: 2.1\nName: black\nVersion: 24.1.0\nSummary: The uncompromising code formatter.\n\nVersion: not-a-header\n";
+        let info = parse_metadata(content);
+        assert_eq!(info.version, Some("24.1.0".to_string()));
+        assert_eq!(
+            info.summary,
+            Some("The uncompromising code formatter.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_unknown_summary() {
+        let info = parse_metadata("Name: x\nVersion: 1.0\nSummary: UNKNOWN\n");
+        assert_eq!(info.summary, None);
+    }
+
+    #[test]
+    fn test_find_dist_info() {
+        let tmp = TempDir::new().unwrap();
+        let dist = tmp
+            .path()
+            .join("lib/python3.12/site-packages/Foo_Bar-1.2.0.dist-info");
+        fs::create_dir_all(&dist).unwrap();
+        fs::write(dist.join("METADATA"), "Name: foo-bar\nSummary: Foo tools\n").unwrap();
+
+        let info = find_dist_info(tmp.path(), "foo-bar").unwrap();
+        assert_eq!(info.version, Some("1.2.0".to_string()));
+        assert_eq!(info.summary, Some("Foo tools".to_string()));
+
+        assert!(find_dist_info(tmp.path(), "other").is_none());
+    }
+}
@@ -1,113 +1,442 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 
 use crate::app::{AppSource, Application};
+use crate::provider::snapd::{SnapInfo, SnapdClient, SNAPD_SOCKET};
 use crate::provider::{AppProvider, ProviderError};
 
-pub struct SnapProvider;
+/// Snap types that are platform pieces rather than applications.
+const NON_APP_TYPES: &[&str] = &["base", "core", "os", "gadget", "kernel", "snapd"];
+
+/// The parts of `meta/snap.yaml` appgrep reports.
+#[derive(Debug, Default)]
+pub struct SnapYaml {
+    pub name: String,
+    pub version: Option<String>,
+    pub snap_type: Option<String>,
+    pub confinement: Option<String>,
+    pub summary: Option<String>,
+    pub apps: BTreeMap<String, SnapApp>,
+}
+
+/// An entry of `apps:` in `snap.yaml`.
+#[derive(Debug, Default)]
+pub struct SnapApp {
+    pub command: Option<String>,
+    /// Service type (`simple`, `forking`, ...) when the app is a daemon.
+    pub daemon: Option<String>,
+}
+
+/// What snapd's `state.json` adds: only readable by root.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SnapState {
+    active: bool,
+    channel: Option<String>,
+}
+
+/// Reports each app a snap exposes as `/snap/bin/<snap>.<app>` (or `/snap/bin/<snap>`
+/// for the app named after the snap) together with its aliases. Installed snaps come
+/// from snapd's API, or from their mounted `meta/snap.yaml` when snapd is not running.
+pub struct SnapProvider {
+    mount_dir: PathBuf,
+    state_file: PathBuf,
+    socket: PathBuf,
+}
+
+impl Default for SnapProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl SnapProvider {
     pub fn new() -> Self {
-        Self
+        // Distributions without a /snap directory mount snaps under /var/lib/snapd/snap
+        let mount_dir = ["/snap", "/var/lib/snapd/snap"]
+            .iter()
+            .map(PathBuf::from)
+            .find(|d| d.is_dir())
+            .unwrap_or_else(|| PathBuf::from("/snap"));
+        Self {
+            mount_dir,
+            state_file: PathBuf::from("/var/lib/snapd/state.json"),
+            socket: PathBuf::from(SNAPD_SOCKET),
+        }
     }
 
-    fn has_snap() -> bool {
-        Command::new("which")
-            .arg("snap")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+    /// Ask the snapd listening on `socket` for installed snaps.
+    pub fn with_socket(mut self, socket: PathBuf) -> Self {
+        self.socket = socket;
+        self
     }
 
-    fn parse_snap_list(output: &str) -> Vec<String> {
-        let mut names = Vec::new();
+    /// Read snaps mounted under `mount_dir` and snapd state from `state_file`.
+    pub fn with_dirs(mut self, mount_dir: PathBuf, state_file: PathBuf) -> Self {
+        self.mount_dir = mount_dir;
+        self.state_file = state_file;
+        self
+    }
+
+    fn bin_dir(&self) -> PathBuf {
+        self.mount_dir.join("bin")
+    }
 
-        for (i, line) in output.lines().enumerate() {
-            // Skip header
-            if i == 0 {
+    /// Parse `meta/snap.yaml`.
+    ///
+    /// Only the block mappings snapcraft writes are understood: scalars at the top level
+    /// and under each entry of `apps:`. Everything nested elsewhere, like `plugs:` lists
+    /// or a multi-line `description: |`, is skipped.
+    pub fn parse_snap_yaml(content: &str) -> Result<SnapYaml, ProviderError> {
+        let mut snap = SnapYaml::default();
+        let mut name = None;
+        let mut in_apps = false;
+        // Indentation of the app names under `apps:` and of the current app's keys
+        let mut app_indent = None;
+        let mut key_indent = None;
+        let mut app: Option<&mut SnapApp> = None;
+
+        for (number, line) in content.lines().enumerate() {
+            let trimmed = line.trim_start();
+            // Sequence items only appear under keys that are skipped
+            if trimmed.is_empty() || trimmed.starts_with(['#', '-']) || trimmed == "..." {
                 continue;
             }
-
-            let line = line.trim();
-            if line.is_empty() {
+            let unsupported = || {
+                let message = format!("snap.yaml line {}: unsupported syntax", number + 1);
+                ProviderError::ParseError(message)
+            };
+            let (key, value) = yaml_key_value(trimmed).ok_or_else(unsupported)?;
+            let indent = line.len() - trimmed.len();
+
+            if indent == 0 {
+                in_apps = key == "apps";
+                if in_apps && !value.is_empty() && value != "{}" {
+                    return Err(unsupported());
+                }
+                let field = match key {
+                    "name" => &mut name,
+                    "version" => &mut snap.version,
+                    "type" => &mut snap.snap_type,
+                    "confinement" => &mut snap.confinement,
+                    "summary" => &mut snap.summary,
+                    _ => continue,
+                };
+                *field = yaml_scalar(value);
                 continue;
             }
-
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.is_empty() {
+            if !in_apps {
                 continue;
             }
 
-            let name = parts[0].to_string();
-
-            // Check if disabled
-            if let Some(notes) = parts.last() {
-                if notes.contains("disabled") {
-                    continue;
+            let app_indent = *app_indent.get_or_insert(indent);
+            if indent == app_indent {
+                if !value.is_empty() && value != "{}" {
+                    return Err(unsupported());
+                }
+                app = Some(snap.apps.entry(key.to_string()).or_default());
+                key_indent = None;
+            } else if indent > app_indent && *key_indent.get_or_insert(indent) == indent {
+                let Some(app) = app.as_deref_mut() else {
+                    return Err(unsupported());
+                };
+                match key {
+                    "command" => app.command = yaml_scalar(value),
+                    "daemon" => app.daemon = yaml_scalar(value),
+                    _ => {}
                 }
             }
-
-            names.push(name);
         }
 
-        names
+        snap.name = name
+            .ok_or_else(|| ProviderError::ParseError("snap.yaml has no name".to_string()))?;
+        Ok(snap)
+    }
+
+    /// Per-snap state from `state.json`, empty when it cannot be read (as non-root).
+    fn read_state(path: &Path) -> HashMap<String, SnapState> {
+        let value: serde_json::Value = match fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+        {
+            Some(v) => v,
+            None => return HashMap::new(),
+        };
+        let snaps = match value.get("data").unwrap_or(&value).get("snaps") {
+            Some(serde_json::Value::Object(snaps)) => snaps,
+            _ => match value.get("snaps").and_then(|s| s.as_object()) {
+                Some(snaps) => snaps,
+                None => return HashMap::new(),
+            },
+        };
+        snaps
+            .iter()
+            .map(|(name, snap)| {
+                let channel = snap
+                    .get("tracking-channel")
+                    .or_else(|| snap.get("channel"))
+                    .and_then(|c| c.as_str())
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_string);
+                let state = SnapState {
+                    active: snap.get("active").and_then(|a| a.as_bool()).unwrap_or(true),
+                    channel,
+                };
+                (name.clone(), state)
+            })
+            .collect()
     }
 
-    /// Try to extract richer metadata from a snap's .desktop file.
+    /// Aliases in the snap bin directory, keyed by the command they point to. Commands
+    /// themselves link to `/usr/bin/snap`; aliases link to a command name.
+    fn read_aliases(bin_dir: &Path) -> HashMap<String, Vec<String>> {
+        let mut aliases: HashMap<String, Vec<String>> = HashMap::new();
+        let entries = match fs::read_dir(bin_dir) {
+            Ok(e) => e,
+            Err(_) => return aliases,
+        };
+        for entry in entries.flatten() {
+            let alias = match entry.file_name().to_str() {
+                Some(a) => a.to_string(),
+                None => continue,
+            };
+            if let Ok(target) = fs::read_link(entry.path()) {
+                let target = target.to_string_lossy().to_string();
+                if !target.contains('/') {
+                    aliases.entry(target).or_default().push(alias);
+                }
+            }
+        }
+        for list in aliases.values_mut() {
+            list.sort();
+        }
+        aliases
+    }
+
+    /// Name, icon, categories and comment from a desktop file shipped in `meta/gui`.
     fn enrich_from_desktop(
-        name: &str,
+        path: &Path,
     ) -> (Option<String>, Option<String>, Vec<String>, Option<String>) {
-        let gui_dir = PathBuf::from(format!("/snap/{}/current/meta/gui", name));
         let mut display_name = None;
         let mut icon = None;
         let mut categories = Vec::new();
         let mut description = None;
 
-        if gui_dir.is_dir() {
-            if let Ok(entries) = fs::read_dir(&gui_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.extension().and_then(|e| e.to_str()) == Some("desktop") {
-                        if let Ok(content) = fs::read_to_string(&path) {
-                            let mut config = configparser::ini::Ini::new_cs();
-                            config.set_comment_symbols(&['#']);
-                            if config.read(content).is_ok() {
-                                let section = "Desktop Entry";
-                                if let Some(n) = config.get(section, "Name") {
-                                    if !n.is_empty() {
-                                        display_name = Some(n);
-                                    }
-                                }
-                                if let Some(i) = config.get(section, "Icon") {
-                                    if !i.is_empty() {
-                                        icon = Some(i);
-                                    }
-                                }
-                                if let Some(c) = config.get(section, "Categories") {
-                                    categories = c
-                                        .split(';')
-                                        .map(|s| s.trim().to_string())
-                                        .filter(|s| !s.is_empty())
-                                        .collect();
-                                }
-                                if let Some(d) = config.get(section, "Comment") {
-                                    if !d.is_empty() {
-                                        description = Some(d);
-                                    }
-                                }
-                            }
-                        }
-                        break; // Use the first .desktop file
-                    }
+        if let Ok(content) = fs::read_to_string(path) {
+            let mut config = configparser::ini::Ini::new_cs();
+            config.set_comment_symbols(&['#']);
+            if config.read(content).is_ok() {
+                let section = "Desktop Entry";
+                display_name = config.get(section, "Name").filter(|n| !n.is_empty());
+                icon = config.get(section, "Icon").filter(|i| !i.is_empty());
+                if let Some(c) = config.get(section, "Categories") {
+                    categories = c
+                        .split(';')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
                 }
+                description = config.get(section, "Comment").filter(|d| !d.is_empty());
             }
         }
 
         (display_name, icon, categories, description)
     }
+
+    /// The desktop file for `app` in `meta/gui`: `<app>.desktop`, or for the app named
+    /// after the snap, any desktop file the snap ships.
+    fn desktop_file(gui_dir: &Path, snap: &str, app: &str) -> Option<PathBuf> {
+        let path = gui_dir.join(format!("{}.desktop", app));
+        if path.is_file() {
+            return Some(path);
+        }
+        if app != snap {
+            return None;
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(gui_dir)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("desktop"))
+            .collect();
+        files.sort();
+        files.into_iter().next()
+    }
+
+    /// Applications exposed by the snap mounted at `snap_dir`.
+    fn read_snap(
+        &self,
+        snap_dir: &Path,
+        state: &HashMap<String, SnapState>,
+        aliases: &HashMap<String, Vec<String>>,
+    ) -> Option<Vec<Application>> {
+        let current = snap_dir.join("current");
+        let content = fs::read_to_string(current.join("meta/snap.yaml")).ok()?;
+        let snap = match Self::parse_snap_yaml(&content) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("appgrep: warning: {}: {}", snap_dir.display(), e);
+                return None;
+            }
+        };
+        if snap
+            .snap_type
+            .as_deref()
+            .is_some_and(|t| NON_APP_TYPES.contains(&t))
+        {
+            return Some(Vec::new());
+        }
+        let snap_state = state.get(&snap.name);
+        if snap_state.is_some_and(|s| !s.active) {
+            return Some(Vec::new());
+        }
+
+        let mut details = BTreeMap::new();
+        details.insert("snap".to_string(), snap.name.clone());
+        if let Some(version) = &snap.version {
+            details.insert("version".to_string(), version.clone());
+        }
+        if let Ok(revision) = fs::read_link(&current) {
+            details.insert(
+                "revision".to_string(),
+                revision.to_string_lossy().to_string(),
+            );
+        }
+        if let Some(channel) = snap_state.and_then(|s| s.channel.clone()) {
+            details.insert("channel".to_string(), channel);
+        }
+        if let Some(confinement) = &snap.confinement {
+            details.insert("confinement".to_string(), confinement.clone());
+        }
+
+        let gui_dir = current.join("meta/gui");
+        let apps = snap
+            .apps
+            .iter()
+            .map(|(app_name, app)| {
+                let mut metadata = details.clone();
+                if let Some(daemon) = &app.daemon {
+                    metadata.insert("daemon".to_string(), daemon.clone());
+                }
+                let desktop = Self::desktop_file(&gui_dir, &snap.name, app_name);
+                self.make_app(
+                    &snap.name,
+                    app_name,
+                    metadata,
+                    desktop.as_deref(),
+                    snap.summary.as_deref(),
+                    aliases,
+                )
+            })
+            .collect();
+        Some(apps)
+    }
+
+    /// Applications of every active snap, as reported by snapd.
+    fn discover_from_snapd(
+        &self,
+        client: &SnapdClient,
+        aliases: &HashMap<String, Vec<String>>,
+    ) -> Result<Vec<Application>, ProviderError> {
+        let snaps: HashMap<String, SnapInfo> = client
+            .snaps()?
+            .into_iter()
+            .filter(|snap| {
+                matches!(snap.status.as_deref(), None | Some("active"))
+                    && !snap
+                        .snap_type
+                        .as_deref()
+                        .is_some_and(|t| NON_APP_TYPES.contains(&t))
+            })
+            .map(|snap| (snap.name.clone(), snap))
+            .collect();
+
+        let mut apps = Vec::new();
+        for app in client.apps()? {
+            let snap = match snaps.get(&app.snap) {
+                Some(s) => s,
+                None => continue,
+            };
+            let mut metadata = BTreeMap::new();
+            metadata.insert("snap".to_string(), snap.name.clone());
+            let publisher = snap.publisher.as_ref();
+            let fields = [
+                ("version", snap.version.as_ref()),
+                ("revision", snap.revision.as_ref()),
+                (
+                    "channel",
+                    snap.tracking_channel.as_ref().or(snap.channel.as_ref()),
+                ),
+                ("confinement", snap.confinement.as_ref()),
+                (
+                    "publisher",
+                    publisher.and_then(|p| p.display_name.as_ref().or(p.username.as_ref())),
+                ),
+                (
+                    "publisher_validation",
+                    publisher.and_then(|p| p.validation.as_ref()),
+                ),
+                ("daemon", app.daemon.as_ref()),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value.filter(|v| !v.is_empty()) {
+                    metadata.insert(key.to_string(), value.clone());
+                }
+            }
+            apps.push(self.make_app(
+                &snap.name,
+                &app.name,
+                metadata,
+                app.desktop_file.as_deref(),
+                snap.summary.as_deref(),
+                aliases,
+            ));
+        }
+        Ok(apps)
+    }
+
+    /// The application for `app` of `snap`, named after its desktop file if it has one.
+    fn make_app(
+        &self,
+        snap: &str,
+        app: &str,
+        mut metadata: BTreeMap<String, String>,
+        desktop_file: Option<&Path>,
+        summary: Option<&str>,
+        aliases: &HashMap<String, Vec<String>>,
+    ) -> Application {
+        let command = if app == snap {
+            snap.to_string()
+        } else {
+            format!("{}.{}", snap, app)
+        };
+        let exec = self.bin_dir().join(&command).to_string_lossy().to_string();
+
+        let (display_name, icon, categories, description) = match desktop_file {
+            Some(path) => Self::enrich_from_desktop(path),
+            None => (None, None, Vec::new(), None),
+        };
+
+        if app != snap {
+            metadata.insert("app".to_string(), app.to_string());
+        }
+        if let Some(list) = aliases.get(&command) {
+            metadata.insert("aliases".to_string(), list.join(", "));
+        }
+
+        Application {
+            name: display_name.unwrap_or_else(|| command.clone()),
+            exec_command: exec.clone(),
+            source: AppSource::Snap,
+            location: command,
+            icon,
+            categories,
+            description: description.or_else(|| summary.map(str::to_string)),
+            metadata,
+            exec_args: vec![exec],
+            ..Default::default()
+        }
+    }
 }
 
 impl AppProvider for SnapProvider {
@@ -116,98 +445,362 @@ impl AppProvider for SnapProvider {
     }
 
     fn is_available(&self) -> bool {
-        Self::has_snap()
+        self.socket.exists() || self.bin_dir().is_dir()
     }
 
     fn discover(&self) -> Result<Vec<Application>, ProviderError> {
-        if !self.is_available() {
-            return Ok(Vec::new());
+        let aliases = Self::read_aliases(&self.bin_dir());
+        let client = SnapdClient::new(self.socket.clone());
+        if client.is_available() {
+            match self.discover_from_snapd(&client, &aliases) {
+                // snapd is not running: read the mounted snaps instead
+                Err(ProviderError::Io(_)) => {}
+                result => return result,
+            }
         }
 
-        let output = Command::new("snap")
-            .arg("list")
-            .output()
-            .map_err(ProviderError::Io)?;
+        let entries = match fs::read_dir(&self.mount_dir) {
+            Ok(e) => e,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let state = Self::read_state(&self.state_file);
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("appgrep: warning: snap list failed: {}", stderr.trim());
-            return Ok(Vec::new());
+        let mut apps = Vec::new();
+        for entry in entries.flatten() {
+            if entry.file_name() == "bin" {
+                continue;
+            }
+            if let Some(snap_apps) = self.read_snap(&entry.path(), &state, &aliases) {
+                apps.extend(snap_apps);
+            }
         }
+        Ok(apps)
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let snap_names = Self::parse_snap_list(&stdout);
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![
+            // Every install, refresh or removal adds or removes a .snap file here
+            PathBuf::from("/var/lib/snapd/snaps"),
+            // Aliases are added and removed without installing anything
+            self.bin_dir(),
+        ]
+    }
+}
 
-        let mut apps = Vec::new();
-        for name in snap_names {
-            let (display_name, icon, categories, description) = Self::enrich_from_desktop(&name);
+/// Split a `key: value` line of a YAML block mapping.
+fn yaml_key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = match line.split_once(": ") {
+        Some((key, value)) => (key, value.trim()),
+        None => (line.strip_suffix(':')?, ""),
+    };
+    let key = key.trim();
+    let key = key
+        .strip_prefix('"')
+        .and_then(|k| k.strip_suffix('"'))
+        .or_else(|| key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')))
+        .unwrap_or(key);
+    // A comment after the colon leaves the value empty
+    let value = if value.starts_with('#') { "" } else { value };
+    Some((key, value))
+}
 
-            apps.push(Application {
-                name: display_name.unwrap_or_else(|| name.clone()),
-                exec_command: format!("snap run {}", name),
-                source: AppSource::Snap,
-                location: name,
-                icon,
-                categories,
-                description,
-            });
+/// A plain or quoted YAML scalar, or `None` for an empty value, `null` or a
+/// block scalar (`|`, `>`).
+fn yaml_scalar(value: &str) -> Option<String> {
+    if let Some(rest) = value.strip_prefix('\'') {
+        let mut out = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                if chars.peek() != Some(&'\'') {
+                    break;
+                }
+                chars.next();
+            }
+            out.push(c);
         }
-
-        Ok(apps)
+        return Some(out);
+    }
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next()? {
+                    'n' => out.push('\n'),
+                    't' => out.push('\t'),
+                    other => out.push(other),
+                },
+                _ => out.push(c),
+            }
+        }
+        return Some(out);
     }
+    let value = match value.find(" #") {
+        Some(end) => value[..end].trim_end(),
+        None => value,
+    };
+    if value.is_empty() || value == "~" || value == "null" || value.starts_with(['|', '>']) {
+        return None;
+    }
+    Some(value.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::provider::snapd::tests::fake_snapd;
+    use std::os::unix::fs::symlink;
+    use std::os::unix::net::UnixListener;
+    use tempfile::TempDir;
+
+    /// Mount a snap at `<root>/<name>/<revision>` with `current` pointing at it.
+    fn install(root: &Path, name: &str, revision: &str, yaml: &str) -> PathBuf {
+        let dir = root.join(name).join(revision);
+        fs::create_dir_all(dir.join("meta")).unwrap();
+        fs::write(dir.join("meta/snap.yaml"), yaml).unwrap();
+        symlink(revision, root.join(name).join("current")).unwrap();
+        dir
+    }
+
+    fn provider(root: &Path) -> SnapProvider {
+        SnapProvider::new()
+            .with_dirs(root.to_path_buf(), root.join("state.json"))
+            .with_socket(root.join("snapd.socket"))
+    }
+
+    #[test]
+    fn test_parse_snap_yaml() {
+        let yaml = "name: lxd\nversion: 5.21.1\nsummary: LXD\nconfinement: strict\n\
+                    apps:\n  lxd:\n    command: commands/lxd\n  lxc:\n    command: commands/lxc\n  \
+                    daemon:\n    command: commands/daemon.start\n    daemon: simple\n\
+                    plugs: [network]\n";
+        let snap = SnapProvider::parse_snap_yaml(yaml).unwrap();
+        assert_eq!(snap.name, "lxd");
+        assert_eq!(snap.version.as_deref(), Some("5.21.1"));
+        assert_eq!(snap.confinement.as_deref(), Some("strict"));
+        assert_eq!(snap.apps.len(), 3);
+        assert_eq!(snap.apps["daemon"].daemon.as_deref(), Some("simple"));
+        assert!(SnapProvider::parse_snap_yaml("apps: [").is_err());
+        assert!(SnapProvider::parse_snap_yaml("version: '1'\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_snap_yaml_skips_what_it_does_not_need() {
+        let yaml = r#"# generated by snapcraft
+name: 'it''s'
+version: "2.0 \"beta\"" # quoted
+description: |
+  Multi-line text with a: colon
+  command: not-an-app
+plugs:
+- network
+- home
+apps:
+  editor:
+    command: bin/editor --flag
+    plugs:
+      - x11
+    environment:
+      command: nested
+  empty: {}
+type: app
+"#;
+        let snap = SnapProvider::parse_snap_yaml(yaml).unwrap();
+        assert_eq!(snap.name, "it's");
+        assert_eq!(snap.version.as_deref(), Some("2.0 \"beta\""));
+        assert_eq!(snap.snap_type.as_deref(), Some("app"));
+        assert_eq!(snap.summary, None);
+        assert_eq!(snap.apps.len(), 2);
+        assert_eq!(snap.apps["editor"].command.as_deref(), Some("bin/editor --flag"));
+        assert_eq!(snap.apps["empty"].command, None);
+    }
 
     #[test]
-    fn test_parse_snap_list() {
-        let output = "Name      Version    Rev    Tracking       Publisher   Notes\nfirefox   128.0      4173   latest/stable  mozilla     -\ncore22    20240111   1380   latest/stable  canonical   base\nspotify   1.2.26     73    latest/stable  spotify     -\n";
-        let names = SnapProvider::parse_snap_list(output);
-        assert_eq!(names, vec!["firefox", "core22", "spotify"]);
+    fn test_reports_every_app_with_aliases() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        install(
+            root,
+            "lxd",
+            "28373",
+            "name: lxd\nversion: 5.21.1\nconfinement: strict\n\
+             apps:\n  lxd:\n    command: lxd\n  lxc:\n    command: lxc\n  \
+             daemon:\n    command: daemon.start\n    daemon: simple\n",
+        );
+        fs::create_dir_all(root.join("bin")).unwrap();
+        symlink("/usr/bin/snap", root.join("bin/lxd.lxc")).unwrap();
+        symlink("lxd.lxc", root.join("bin/lxc")).unwrap();
+
+        let mut apps = provider(root).discover().unwrap();
+        apps.sort_by(|a, b| a.name.cmp(&b.name));
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["lxd", "lxd.daemon", "lxd.lxc"]);
+
+        let lxc = &apps[2];
+        let bin = root.join("bin/lxd.lxc").to_string_lossy().to_string();
+        assert_eq!(lxc.exec_command, bin);
+        assert_eq!(lxc.exec_args, vec![bin]);
+        assert_eq!(lxc.metadata["aliases"], "lxc");
+        assert_eq!(lxc.metadata["app"], "lxc");
+        assert_eq!(lxc.metadata["revision"], "28373");
+        assert_eq!(lxc.metadata["version"], "5.21.1");
+        assert_eq!(lxc.metadata["confinement"], "strict");
+        assert_eq!(apps[1].metadata["daemon"], "simple");
+        assert!(!apps[0].metadata.contains_key("app"));
     }
 
     #[test]
-    fn test_parse_snap_list_skips_disabled() {
-        let output = "Name      Version    Rev    Tracking       Publisher   Notes\nmyapp     1.0        10     latest/stable  me          disabled\nother     2.0        20     latest/stable  me          -\n";
-        let names = SnapProvider::parse_snap_list(output);
-        assert_eq!(names, vec!["other"]);
+    fn test_skips_base_snaps_and_uses_desktop_files() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        install(
+            root,
+            "core22",
+            "1380",
+            "name: core22\nversion: '20240111'\ntype: base\n",
+        );
+        let firefox = install(
+            root,
+            "firefox",
+            "4173",
+            "name: firefox\nversion: '128.0'\nsummary: Mozilla Firefox\n\
+             apps:\n  firefox:\n    command: firefox.launcher\n  geckodriver:\n    command: geckodriver\n",
+        );
+        fs::create_dir_all(firefox.join("meta/gui")).unwrap();
+        fs::write(
+            firefox.join("meta/gui/firefox.desktop"),
+            "[Desktop Entry]\nName=Firefox Web Browser\nComment=Browse the Web\nIcon=${SNAP}/default256.png\n",
+        )
+        .unwrap();
+
+        let mut apps = provider(root).discover().unwrap();
+        apps.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0].name, "Firefox Web Browser");
+        assert_eq!(apps[0].description.as_deref(), Some("Browse the Web"));
+        assert_eq!(apps[1].name, "firefox.geckodriver");
+        assert_eq!(apps[1].description.as_deref(), Some("Mozilla Firefox"));
     }
 
     #[test]
-    fn test_parse_empty_snap_list() {
-        let output = "Name      Version    Rev    Tracking       Publisher   Notes\n";
-        let names = SnapProvider::parse_snap_list(output);
-        assert!(names.is_empty());
+    fn test_state_adds_channel_and_skips_inactive() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        install(
+            root,
+            "spotify",
+            "73",
+            "name: spotify\napps:\n  spotify:\n    command: spotify\n",
+        );
+        install(
+            root,
+            "old",
+            "5",
+            "name: old\napps:\n  old:\n    command: old\n",
+        );
+        fs::write(
+            root.join("state.json"),
+            r#"{"data": {}, "snaps": {
+                "spotify": {"type": "app", "active": true, "current": "73", "tracking-channel": "latest/stable"},
+                "old": {"type": "app", "active": false, "current": "5"}
+            }}"#,
+        )
+        .unwrap();
+
+        let apps = provider(root).discover().unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "spotify");
+        assert_eq!(apps[0].metadata["channel"], "latest/stable");
     }
 
     #[test]
-    fn test_parse_snap_list_empty_lines() {
-        let output = "Name      Version    Rev    Tracking       Publisher   Notes\n\n\nfirefox   128.0      4173   latest/stable  mozilla     -\n\n";
-        let names = SnapProvider::parse_snap_list(output);
-        assert_eq!(names, vec!["firefox"]);
+    fn test_discovers_from_snapd() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("bin")).unwrap();
+        symlink("lxd.lxc", root.join("bin/lxc")).unwrap();
+        let desktop = root.join("firefox_firefox.desktop");
+        fs::write(
+            &desktop,
+            "[Desktop Entry]\nName=Firefox Web Browser\nCategories=Network;\n",
+        )
+        .unwrap();
+        let snaps = r#"[
+            {"name": "firefox", "version": "128.0", "revision": "4173", "channel": "stable",
+             "tracking-channel": "latest/stable", "confinement": "strict", "type": "app",
+             "status": "active", "summary": "Mozilla Firefox",
+             "publisher": {"username": "mozilla", "display-name": "Mozilla", "validation": "verified"}},
+            {"name": "lxd", "version": "5.21.1", "revision": "28373", "type": "app", "status": "active"},
+            {"name": "old", "version": "1", "revision": "5", "type": "app", "status": "installed"},
+            {"name": "core22", "version": "20240111", "revision": "1380", "type": "base", "status": "active"}
+        ]"#;
+        let apps = format!(
+            r#"[
+            {{"snap": "firefox", "name": "firefox", "desktop-file": "{}"}},
+            {{"snap": "lxd", "name": "lxc"}},
+            {{"snap": "lxd", "name": "daemon", "daemon": "simple"}},
+            {{"snap": "old", "name": "old"}}
+        ]"#,
+            desktop.display()
+        );
+        let socket = fake_snapd(
+            root,
+            vec![("/v2/snaps", snaps.to_string()), ("/v2/apps", apps)],
+        );
+        // Nothing is mounted: everything comes from snapd
+        let provider = SnapProvider::new()
+            .with_dirs(root.to_path_buf(), root.join("state.json"))
+            .with_socket(socket);
+        assert!(provider.is_available());
+
+        let mut apps = provider.discover().unwrap();
+        apps.sort_by(|a, b| a.name.cmp(&b.name));
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Firefox Web Browser", "lxd.daemon", "lxd.lxc"]);
+
+        let firefox = &apps[0];
+        assert_eq!(firefox.location, "firefox");
+        assert_eq!(firefox.categories, vec!["Network"]);
+        assert_eq!(firefox.description.as_deref(), Some("Mozilla Firefox"));
+        assert_eq!(firefox.metadata["channel"], "latest/stable");
+        assert_eq!(firefox.metadata["publisher"], "Mozilla");
+        assert_eq!(firefox.metadata["publisher_validation"], "verified");
+        assert_eq!(firefox.metadata["revision"], "4173");
+        assert_eq!(apps[1].metadata["daemon"], "simple");
+        assert_eq!(apps[2].metadata["aliases"], "lxc");
+        assert_eq!(apps[2].metadata["app"], "lxc");
     }
 
     #[test]
-    fn test_parse_snap_list_malformed_line() {
-        // A line with only whitespace after the header should be skipped
-        let output = "Name      Version    Rev    Tracking       Publisher   Notes\n   \nfirefox   128.0      4173   latest/stable  mozilla     -\n";
-        let names = SnapProvider::parse_snap_list(output);
-        assert_eq!(names, vec!["firefox"]);
+    fn test_falls_back_to_mounted_snaps_without_snapd() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        install(
+            root,
+            "spotify",
+            "73",
+            "name: spotify\napps:\n  spotify:\n    command: spotify\n",
+        );
+        // A socket left behind by a stopped snapd refuses connections
+        drop(UnixListener::bind(root.join("snapd.socket")).unwrap());
+
+        let apps = provider(root).discover().unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].metadata["revision"], "73");
     }
 
     #[test]
-    fn test_provider_name_and_availability() {
-        let provider = SnapProvider::new();
-        assert_eq!(provider.name(), "snap");
-        // is_available depends on system, just ensure it doesn't panic
-        let _ = provider.is_available();
+    fn test_provider_name() {
+        assert_eq!(SnapProvider::new().name(), "snap");
     }
 
     #[test]
-    fn test_parse_completely_empty() {
-        let names = SnapProvider::parse_snap_list("");
-        assert!(names.is_empty());
+    fn test_missing_mount_dir() {
+        let tmp = TempDir::new().unwrap();
+        let provider = provider(&tmp.path().join("nope"));
+        assert!(!provider.is_available());
+        assert!(provider.discover().unwrap().is_empty());
     }
 }
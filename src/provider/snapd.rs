@@ -0,0 +1,331 @@
+//! A minimal client for snapd's REST API on its unix socket.
+//!
+//! Only the read-only `GET /v2/snaps` and `GET /v2/apps` endpoints are used, which need
+//! no authentication.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::provider::process::DEFAULT_TIMEOUT;
+use crate::provider::ProviderError;
+
+/// Where snapd listens.
+pub const SNAPD_SOCKET: &str = "/run/snapd.socket";
+
+/// Largest response read from snapd. Listing every installed snap takes well under
+/// a megabyte; the limit only guards against a misbehaving socket.
+const MAX_RESPONSE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// An installed snap, as returned by `/v2/snaps`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SnapInfo {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub revision: Option<String>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub tracking_channel: Option<String>,
+    #[serde(default)]
+    pub confinement: Option<String>,
+    #[serde(default, rename = "type")]
+    pub snap_type: Option<String>,
+    /// `active`, or `installed` for a disabled snap.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub publisher: Option<Publisher>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Publisher {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// `verified`, `starred` or `unproven`.
+    #[serde(default)]
+    pub validation: Option<String>,
+}
+
+/// An app of an active snap, as returned by `/v2/apps`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AppInfo {
+    pub snap: String,
+    pub name: String,
+    #[serde(default)]
+    pub desktop_file: Option<PathBuf>,
+    #[serde(default)]
+    pub daemon: Option<String>,
+}
+
+/// The envelope snapd wraps every response in.
+#[derive(Deserialize)]
+struct Response {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    result: serde_json::Value,
+}
+
+pub struct SnapdClient {
+    socket: PathBuf,
+}
+
+impl SnapdClient {
+    pub fn new(socket: PathBuf) -> Self {
+        Self { socket }
+    }
+
+    /// Whether snapd's socket exists; it may still refuse connections.
+    pub fn is_available(&self) -> bool {
+        self.socket.exists()
+    }
+
+    pub fn snaps(&self) -> Result<Vec<SnapInfo>, ProviderError> {
+        self.get("/v2/snaps")
+    }
+
+    pub fn apps(&self) -> Result<Vec<AppInfo>, ProviderError> {
+        self.get("/v2/apps")
+    }
+
+    /// `GET path` and decode the `result` of snapd's response.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ProviderError> {
+        let body = request(&self.socket, path)?;
+        let response: Response = serde_json::from_slice(&body)
+            .map_err(|e| ProviderError::ParseError(format!("snapd {}: {}", path, e)))?;
+        if response.kind == "error" {
+            let message = response
+                .result
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            return Err(ProviderError::ParseError(format!(
+                "snapd {}: {}",
+                path, message
+            )));
+        }
+        serde_json::from_value(response.result)
+            .map_err(|e| ProviderError::ParseError(format!("snapd {}: {}", path, e)))
+    }
+}
+
+/// Send an HTTP/1.1 `GET` over the unix socket and return the response body.
+fn request(socket: &Path, path: &str) -> Result<Vec<u8>, ProviderError> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+    stream.set_write_timeout(Some(DEFAULT_TIMEOUT))?;
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nUser-Agent: appgrep\r\nConnection: close\r\n\r\n",
+        path
+    )?;
+
+    let response = read_limited(stream, MAX_RESPONSE_SIZE)?;
+    parse_response(&response)
+}
+
+/// Read `reader` to the end, failing once more than `max` bytes arrive.
+fn read_limited(reader: impl Read, max: u64) -> Result<Vec<u8>, ProviderError> {
+    let mut response = Vec::new();
+    reader.take(max + 1).read_to_end(&mut response)?;
+    if response.len() as u64 > max {
+        return Err(ProviderError::ParseError(format!(
+            "snapd response exceeds {} bytes",
+            max
+        )));
+    }
+    Ok(response)
+}
+
+/// The body of a raw HTTP response, de-chunked if needed. snapd reports API errors
+/// in the body, so the status code is not checked here.
+fn parse_response(response: &[u8]) -> Result<Vec<u8>, ProviderError> {
+    let malformed = || ProviderError::ParseError("malformed response from snapd".to_string());
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(malformed)?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+
+    if !head.starts_with("HTTP/1.") {
+        return Err(malformed());
+    }
+    let header = |name: &str| {
+        head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+
+    if header("Transfer-Encoding").is_some_and(|e| e.eq_ignore_ascii_case("chunked")) {
+        return dechunk(body).ok_or_else(malformed);
+    }
+    match header("Content-Length").and_then(|l| l.parse::<usize>().ok()) {
+        Some(len) if len <= body.len() => Ok(body[..len].to_vec()),
+        Some(_) => Err(malformed()),
+        None => Ok(body.to_vec()),
+    }
+}
+
+/// Decode a `Transfer-Encoding: chunked` body.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size_line = std::str::from_utf8(&body[..line_end]).ok()?;
+        // Chunk extensions follow a ';'
+        let size_hex = size_line.split(';').next()?.trim();
+        let size = usize::from_str_radix(size_hex, 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(out);
+        }
+        out.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+    use tempfile::TempDir;
+
+    /// Serve `responses` (request path, JSON body) on a unix socket in `dir` until the
+    /// test ends, answering unknown paths with a snapd error.
+    pub(crate) fn fake_snapd(dir: &Path, responses: Vec<(&'static str, String)>) -> PathBuf {
+        let socket = dir.join("snapd.socket");
+        let listener = UnixListener::bind(&socket).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request_line).unwrap();
+                // Drain the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or("");
+                let body = responses
+                    .iter()
+                    .find(|(p, _)| *p == path)
+                    .map(|(_, body)| {
+                        format!(
+                            r#"{{"type":"sync","status-code":200,"status":"OK","result":{}}}"#,
+                            body
+                        )
+                    })
+                    .unwrap_or_else(|| {
+                        r#"{"type":"error","status-code":404,"result":{"message":"not found"}}"#
+                            .to_string()
+                    });
+                // Send the body in two chunks, as snapd does for large responses
+                let (first, second) = body.split_at(body.len() / 2);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                    first.len(),
+                    first,
+                    second.len(),
+                    second
+                );
+            }
+        });
+        socket
+    }
+
+    #[test]
+    fn test_client_reads_snaps_and_apps() {
+        let tmp = TempDir::new().unwrap();
+        let socket = fake_snapd(
+            tmp.path(),
+            vec![
+                (
+                    "/v2/snaps",
+                    r#"[{"name":"firefox","version":"128.0","revision":"4173","tracking-channel":"latest/stable",
+                        "confinement":"strict","type":"app","status":"active",
+                        "publisher":{"id":"x","username":"mozilla","display-name":"Mozilla","validation":"verified"}}]"#
+                        .to_string(),
+                ),
+                (
+                    "/v2/apps",
+                    r#"[{"snap":"firefox","name":"firefox","desktop-file":"/var/lib/snapd/desktop/applications/firefox_firefox.desktop"}]"#
+                        .to_string(),
+                ),
+            ],
+        );
+        let client = SnapdClient::new(socket);
+        assert!(client.is_available());
+
+        let snaps = client.snaps().unwrap();
+        assert_eq!(snaps.len(), 1);
+        assert_eq!(snaps[0].revision.as_deref(), Some("4173"));
+        assert_eq!(snaps[0].tracking_channel.as_deref(), Some("latest/stable"));
+        let publisher = snaps[0].publisher.as_ref().unwrap();
+        assert_eq!(publisher.display_name.as_deref(), Some("Mozilla"));
+        assert_eq!(publisher.validation.as_deref(), Some("verified"));
+
+        let apps = client.apps().unwrap();
+        assert_eq!(apps[0].snap, "firefox");
+        assert!(apps[0].desktop_file.is_some());
+        assert!(apps[0].daemon.is_none());
+    }
+
+    #[test]
+    fn test_client_reports_snapd_errors() {
+        let tmp = TempDir::new().unwrap();
+        let client = SnapdClient::new(fake_snapd(tmp.path(), Vec::new()));
+        match client.snaps() {
+            Err(ProviderError::ParseError(message)) => assert!(message.contains("not found")),
+            other => panic!("expected an error, got {:?}", other.map(|s| s.len())),
+        }
+
+        let missing = SnapdClient::new(tmp.path().join("missing.socket"));
+        assert!(!missing.is_available());
+        assert!(matches!(missing.snaps(), Err(ProviderError::Io(_))));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let plain = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}trailing";
+        assert_eq!(parse_response(plain).unwrap(), b"{}");
+        let chunked = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n3;x=y\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        assert_eq!(parse_response(chunked).unwrap(), b"abcde");
+        assert!(parse_response(b"garbage").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n{}").is_err());
+    }
+
+    #[test]
+    fn test_read_limited() {
+        assert_eq!(read_limited(&b"abcd"[..], 4).unwrap(), b"abcd");
+        assert!(matches!(
+            read_limited(&b"abcde"[..], 4),
+            Err(ProviderError::ParseError(_))
+        ));
+        // An endless stream stops at the limit
+        assert!(read_limited(std::io::repeat(b'x'), 1024).is_err());
+    }
+}
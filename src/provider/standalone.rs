@@ -2,18 +2,57 @@ use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 
+use configparser::ini::Ini;
+
 use crate::app::{AppSource, Application};
+use crate::exec;
+use crate::provider::appimage::AppImage;
+use crate::provider::desktop::{strip_field_codes, DesktopProvider, ShowContext};
 use crate::provider::{AppProvider, ProviderError};
 
-pub struct StandaloneProvider;
+pub struct StandaloneProvider {
+    extra_dirs: Vec<PathBuf>,
+    icon_dir: Option<PathBuf>,
+    context: ShowContext,
+}
+
+impl Default for StandaloneProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl StandaloneProvider {
     pub fn new() -> Self {
-        Self
+        Self {
+            extra_dirs: Vec::new(),
+            icon_dir: dirs::cache_dir().map(|d| d.join("appgrep").join("icons")),
+            context: ShowContext::current(),
+        }
     }
 
-    /// Directories to scan for standalone executables.
-    fn scan_dirs() -> Vec<PathBuf> {
+    /// Also scan `dirs`, e.g. from the user configuration.
+    pub fn with_extra_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.extra_dirs = dirs;
+        self
+    }
+
+    /// Extract AppImage icons into `dir` (default `$XDG_CACHE_HOME/appgrep/icons`), or
+    /// not at all.
+    pub fn with_icon_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.icon_dir = dir;
+        self
+    }
+
+    /// Translate embedded desktop entries for `context` instead of this process's
+    /// environment.
+    pub fn with_context(mut self, context: ShowContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Built-in directories to scan for standalone executables.
+    pub fn default_scan_dirs() -> Vec<PathBuf> {
         let mut dirs = Vec::new();
 
         if let Some(home) = dirs::home_dir() {
@@ -27,6 +66,17 @@ impl StandaloneProvider {
         dirs
     }
 
+    /// Built-in directories followed by configured extras.
+    fn scan_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = Self::default_scan_dirs();
+        for dir in &self.extra_dirs {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+
     /// Check if a file is an executable (non-directory, has execute bit).
     fn is_executable(path: &Path) -> bool {
         if let Ok(metadata) = fs::metadata(path) {
@@ -67,11 +117,11 @@ impl StandaloneProvider {
         let name = strip_version_suffix(&name);
 
         // Strip trailing separators
-        name.trim_end_matches(|c: char| c == '-' || c == '_' || c == '.')
+        name.trim_end_matches(['-', '_', '.'])
             .to_string()
     }
 
-    fn scan_directory(dir: &Path, depth: usize) -> Vec<Application> {
+    fn scan_directory(&self, dir: &Path, depth: usize) -> Vec<Application> {
         let mut apps = Vec::new();
 
         let entries = match fs::read_dir(dir) {
@@ -84,7 +134,7 @@ impl StandaloneProvider {
 
             // For /opt, go one level deep
             if path.is_dir() && depth > 0 {
-                apps.extend(Self::scan_directory(&path, 0));
+                apps.extend(self.scan_directory(&path, 0));
                 continue;
             }
 
@@ -109,19 +159,125 @@ impl StandaloneProvider {
             let abs_path = path.canonicalize().unwrap_or(path.clone());
             let location = abs_path.to_string_lossy().to_string();
 
-            apps.push(Application {
+            let mut app = Application {
                 name,
                 exec_command: location.clone(),
                 source: AppSource::Standalone,
                 location,
-                icon: None,
-                categories: Vec::new(),
-                description: None,
-            });
+                ..Default::default()
+            };
+            match AppImage::read(&abs_path) {
+                Ok(Some(image)) => self.apply_appimage(&mut app, image),
+                Ok(None) => {}
+                Err(e) => eprintln!("appgrep: warning: {}: {}", abs_path.display(), e),
+            }
+            apps.push(app);
         }
 
         apps
     }
+
+    /// Fill in `app` from the desktop entry, icon and AppStream metadata embedded in
+    /// its AppImage.
+    fn apply_appimage(&self, app: &mut Application, image: AppImage) {
+        let location = app.location.clone();
+        app.metadata
+            .insert("appimage_type".to_string(), image.image_type.to_string());
+
+        let mut named = false;
+        if let Some((desktop_name, content)) = &image.desktop_entry {
+            let path = Path::new(&location).join(desktop_name);
+            // TryExec and the show-in keys describe the image's own integration
+            if let Ok(Some(entry)) =
+                DesktopProvider::parse_desktop_entry(content, &path, &self.context)
+            {
+                // The entry runs the image's AppRun; launch the AppImage with its arguments
+                let with_image = |args: &[String]| {
+                    std::iter::once(location.clone())
+                        .chain(args.iter().skip(1).cloned())
+                        .collect::<Vec<_>>()
+                };
+                app.exec_args = with_image(&entry.exec_args);
+                app.exec_command = strip_field_codes(&exec::join(&app.exec_args));
+                app.actions = entry
+                    .actions
+                    .into_iter()
+                    .map(|mut action| {
+                        action.exec_args = with_image(&action.exec_args);
+                        action
+                    })
+                    .collect();
+                app.name = entry.name;
+                named = true;
+                app.untranslated_name = entry.untranslated_name;
+                app.generic_name = entry.generic_name;
+                app.description = entry.description;
+                app.categories = entry.categories;
+                app.keywords = entry.keywords;
+                app.terminal = entry.terminal;
+                app.icon = entry.icon;
+            }
+            let mut config = Ini::new_cs();
+            config.set_comment_symbols(&['#']);
+            if let Some(version) = config
+                .read(content.clone())
+                .ok()
+                .and_then(|_| config.get("Desktop Entry", "X-AppImage-Version"))
+                .filter(|v| !v.is_empty())
+            {
+                app.metadata.insert("version".to_string(), version);
+            }
+        }
+
+        if let Some(appstream) = image.appstream {
+            if let Some(name) = appstream.name.filter(|_| !named) {
+                app.name = name;
+            }
+            if app.categories.is_empty() {
+                app.categories = appstream.categories;
+            }
+            if app.description.is_none() {
+                app.description = appstream.summary;
+            }
+            if let Some(version) = appstream.version {
+                app.metadata.insert("version".to_string(), version);
+            }
+            if let Some(id) = appstream.id {
+                app.metadata.insert("appstream_id".to_string(), id);
+            }
+        }
+
+        if let Some(icon) = image.icon.and_then(|icon| self.save_icon(&location, &icon)) {
+            app.icon = Some(icon.to_string_lossy().to_string());
+        }
+    }
+
+    /// Write the `.DirIcon` of the AppImage at `location` to the icon directory.
+    fn save_icon(&self, location: &str, icon: &[u8]) -> Option<PathBuf> {
+        let dir = self.icon_dir.as_ref()?;
+        let extension = if icon.starts_with(b"\x89PNG") {
+            "png"
+        } else if String::from_utf8_lossy(&icon[..icon.len().min(512)]).contains("<svg") {
+            "svg"
+        } else {
+            return None;
+        };
+        let stem = Path::new(location).file_stem()?.to_string_lossy();
+        // Named after the image path, so AppImages with the same file name do not clash
+        let path = dir.join(format!("{}-{:016x}.{}", stem, fnv1a(location), extension));
+        if fs::read(&path).ok().as_deref() != Some(icon) {
+            fs::create_dir_all(dir).ok()?;
+            fs::write(&path, icon).ok()?;
+        }
+        Some(path)
+    }
+}
+
+/// 64-bit FNV-1a, a hash that is stable across runs and Rust versions.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
 }
 
 /// Strip architecture suffixes from a name.
@@ -196,22 +352,90 @@ impl AppProvider for StandaloneProvider {
     fn discover(&self) -> Result<Vec<Application>, ProviderError> {
         let mut apps = Vec::new();
 
-        for dir in Self::scan_dirs() {
+        for dir in self.scan_dirs() {
             if !dir.is_dir() {
                 continue;
             }
 
-            let depth = if dir == PathBuf::from("/opt") { 1 } else { 0 };
-            apps.extend(Self::scan_directory(&dir, depth));
+            let depth = if dir == Path::new("/opt") { 1 } else { 0 };
+            apps.extend(self.scan_directory(&dir, depth));
         }
 
         Ok(apps)
     }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.scan_dirs();
+        // /opt is scanned one level deep, so its subdirectories matter too
+        if let Ok(entries) = fs::read_dir("/opt") {
+            paths.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()));
+        }
+        paths
+    }
+
+    fn cache_key(&self) -> String {
+        self.context.cache_key(self.name())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::provider::appimage::tests::{iso_appimage, sample_tree, squashfs_appimage};
+    use tempfile::TempDir;
+
+    fn install(dir: &Path, name: &str, bytes: &[u8]) {
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_appimage_metadata() {
+        let tmp = TempDir::new().unwrap();
+        let bin = tmp.path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        let desktop = "[Desktop Entry]\nType=Application\nName=Krita\nGenericName=Painting\n\
+                       Exec=krita %F\nIcon=krita\nCategories=Graphics;\nTryExec=krita\n\
+                       X-AppImage-Version=5.2.2\nActions=New;\n\n\
+                       [Desktop Action New]\nName=New\nExec=krita --new-image\n";
+        install(&bin, "krita-5.2.2-x86_64.AppImage", &squashfs_appimage(&sample_tree(desktop)));
+        install(&bin, "Old_App.AppImage", &iso_appimage(&sample_tree("")));
+        install(&bin, "script", b"#!/bin/sh\n");
+
+        let icons = tmp.path().join("icons");
+        let provider = StandaloneProvider::new().with_icon_dir(Some(icons.clone()));
+        let mut apps = provider.scan_directory(&bin, 0);
+        apps.sort_by(|a, b| a.name.cmp(&b.name));
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["App", "Krita", "script"]);
+
+        let krita = &apps[1];
+        let location = bin.join("krita-5.2.2-x86_64.AppImage").canonicalize().unwrap();
+        let location = location.to_string_lossy().to_string();
+        assert_eq!(krita.generic_name.as_deref(), Some("Painting"));
+        assert_eq!(krita.categories, vec!["Graphics"]);
+        assert_eq!(krita.exec_args, vec![location.clone(), "%F".to_string()]);
+        assert_eq!(krita.exec_command, location);
+        assert_eq!(krita.actions[0].exec_args, vec![location, "--new-image".to_string()]);
+        // The AppStream release wins over the desktop entry's version
+        assert_eq!(krita.metadata["version"], "2.1.0");
+        assert_eq!(krita.metadata["appimage_type"], "2");
+        assert!(krita.hidden_reason.is_none());
+        let icon = PathBuf::from(krita.icon.as_ref().unwrap());
+        assert!(icon.starts_with(&icons));
+        assert_eq!(fs::read(&icon).unwrap(), b"\x89PNG\r\n\x1a\nicon");
+
+        // Without a usable desktop entry, names and categories come from AppStream
+        let old = &apps[0];
+        assert_eq!(old.metadata["appimage_type"], "1");
+        assert_eq!(old.categories, vec!["Office"]);
+        assert_eq!(old.description.as_deref(), Some("Does things"));
+        assert_ne!(old.icon, krita.icon);
+
+        assert!(apps[2].icon.is_none());
+        assert!(apps[2].metadata.is_empty());
+    }
 
     #[test]
     fn test_extract_name_appimage() {
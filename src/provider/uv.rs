@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::app::{AppSource, Application};
+use crate::provider::python::find_dist_info;
+use crate::provider::process::{self, has_command};
+use crate::provider::{AppProvider, ProviderError};
+
+#[derive(Default)]
+pub struct UvProvider;
+
+/// A tool installed with `uv tool install`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UvTool {
+    pub package: String,
+    pub version: Option<String>,
+    pub dir: Option<PathBuf>,
+    /// Entry point names with their installed path, when known.
+    pub entrypoints: Vec<(String, Option<PathBuf>)>,
+}
+
+#[derive(Deserialize)]
+struct Receipt {
+    tool: ReceiptTool,
+}
+
+#[derive(Deserialize)]
+struct ReceiptTool {
+    #[serde(default)]
+    entrypoints: Vec<ReceiptEntrypoint>,
+}
+
+#[derive(Deserialize)]
+struct ReceiptEntrypoint {
+    name: String,
+    #[serde(rename = "install-path")]
+    install_path: Option<PathBuf>,
+}
+
+impl UvProvider {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn has_uv() -> bool {
+        has_command("uv")
+    }
+
+    /// `$UV_TOOL_DIR`, or `$XDG_DATA_HOME/uv/tools` (default `~/.local/share/uv/tools`).
+    fn tools_dir() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("UV_TOOL_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
+            return Some(PathBuf::from(data_home).join("uv/tools"));
+        }
+        dirs::home_dir().map(|h| h.join(".local/share/uv/tools"))
+    }
+
+    /// `tools_dir` and the receipt of each tool in it, which `uv tool upgrade` rewrites
+    /// without touching `tools_dir`.
+    fn watch_paths_in(tools_dir: &Path) -> Vec<PathBuf> {
+        let mut receipts: Vec<PathBuf> = fs::read_dir(tools_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path().join("uv-receipt.toml"))
+            .collect();
+        receipts.sort();
+        std::iter::once(tools_dir.to_path_buf()).chain(receipts).collect()
+    }
+
+    /// Parse a tool's `uv-receipt.toml` into its entry points.
+    pub fn parse_receipt(content: &str) -> Result<Vec<(String, Option<PathBuf>)>, ProviderError> {
+        let receipt: Receipt =
+            toml::from_str(content).map_err(|e| ProviderError::ParseError(e.to_string()))?;
+        Ok(receipt
+            .tool
+            .entrypoints
+            .into_iter()
+            .map(|e| (e.name, e.install_path))
+            .collect())
+    }
+
+    /// Parse `uv tool list --show-paths` output.
+    ///
+    /// ```text
+    /// black v24.1.0 (/home/me/.local/share/uv/tools/black)
+    /// - black (/home/me/.local/bin/black)
+    /// ```
+    pub fn parse_tool_list(output: &str) -> Vec<UvTool> {
+        let mut tools: Vec<UvTool> = Vec::new();
+
+        for line in output.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("warning:") {
+                continue;
+            }
+
+            if let Some(entry) = line.strip_prefix("- ") {
+                let (name, path) = split_path_suffix(entry);
+                if let Some(tool) = tools.last_mut() {
+                    tool.entrypoints.push((name.to_string(), path));
+                }
+                continue;
+            }
+
+            let (head, dir) = split_path_suffix(line);
+            let mut parts = head.split_whitespace();
+            let package = match parts.next() {
+                Some(p) => p.to_string(),
+                None => continue,
+            };
+            let version = parts
+                .next()
+                .map(|v| v.strip_prefix('v').unwrap_or(v).to_string());
+
+            tools.push(UvTool {
+                package,
+                version,
+                dir,
+                entrypoints: Vec::new(),
+            });
+        }
+
+        tools
+    }
+
+    /// Read every tool's receipt from the uv tools directory.
+    fn read_tools_dir(tools_dir: &Path) -> Vec<UvTool> {
+        let entries = match fs::read_dir(tools_dir) {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut tools = Vec::new();
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let receipt_path = dir.join("uv-receipt.toml");
+            let content = match fs::read_to_string(&receipt_path) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+
+            let entrypoints = match Self::parse_receipt(&content) {
+                Ok(e) => e,
+                Err(e) => {
+                    eprintln!("appgrep: warning: {}: {}", receipt_path.display(), e);
+                    continue;
+                }
+            };
+
+            let package = entry.file_name().to_string_lossy().to_string();
+            tools.push(UvTool {
+                package,
+                version: None,
+                dir: Some(dir),
+                entrypoints,
+            });
+        }
+
+        tools
+    }
+
+    fn list_tools_cli() -> Vec<UvTool> {
+        let output = match process::output(Command::new("uv").args(["tool", "list", "--show-paths"])) {
+            Ok(o) if o.status.success() => o,
+            _ => return Vec::new(),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse_tool_list(&stdout)
+    }
+
+    /// Build one application per entry point of a tool.
+    fn tool_apps(tool: &UvTool) -> Vec<Application> {
+        let dist_info = tool
+            .dir
+            .as_deref()
+            .and_then(|d| find_dist_info(d, &tool.package))
+            .unwrap_or_default();
+        let version = tool.version.clone().or(dist_info.version);
+
+        let mut metadata = BTreeMap::new();
+        metadata.insert("package".to_string(), tool.package.clone());
+        if let Some(ref v) = version {
+            metadata.insert("version".to_string(), v.clone());
+        }
+
+        let mut apps = Vec::new();
+        for (name, install_path) in &tool.entrypoints {
+            let path = match install_path {
+                Some(p) if p.exists() => p.clone(),
+                _ => match tool.dir.as_ref().map(|d| d.join("bin").join(name)) {
+                    Some(p) if p.exists() => p,
+                    _ => continue,
+                },
+            };
+
+            let abs_path = path.canonicalize().unwrap_or(path);
+            let exec = abs_path.to_string_lossy().to_string();
+            let location = tool
+                .dir
+                .as_ref()
+                .map(|d| d.to_string_lossy().to_string())
+                .unwrap_or_else(|| exec.clone());
+
+            apps.push(Application {
+                name: name.clone(),
+                exec_command: exec,
+                source: AppSource::Uv,
+                location,
+                categories: vec!["Python".to_string()],
+                description: dist_info.summary.clone(),
+                metadata: metadata.clone(),
+                ..Default::default()
+            });
+        }
+
+        apps
+    }
+}
+
+/// Split a trailing ` (/some/path)` off a `uv tool list` line.
+fn split_path_suffix(s: &str) -> (&str, Option<PathBuf>) {
+    if let Some(start) = s.rfind(" (") {
+        if let Some(inner) = s[start + 2..].strip_suffix(')') {
+            return (s[..start].trim(), Some(PathBuf::from(inner)));
+        }
+    }
+    (s.trim(), None)
+}
+
+impl AppProvider for UvProvider {
+    fn name(&self) -> &str {
+        "uv"
+    }
+
+    fn is_available(&self) -> bool {
+        Self::tools_dir().is_some_and(|d| d.is_dir()) || Self::has_uv()
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        let tools = match Self::tools_dir() {
+            Some(d) if d.is_dir() => Self::read_tools_dir(&d),
+            _ if Self::has_uv() => Self::list_tools_cli(),
+            _ => return Ok(Vec::new()),
+        };
+
+        Ok(tools.iter().flat_map(Self::tool_apps).collect())
+    }
+
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        match Self::tools_dir() {
+            Some(dir) => Self::watch_paths_in(&dir),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_provider_name() {
+        let provider = UvProvider::new();
+        assert_eq!(provider.name(), "uv");
+    }
+
+    #[test]
+    fn test_parse_receipt() {
+        let content = r#"
+[tool]
+requirements = [{ name = "black" }]
+entrypoints = [
+    { name = "black", install-path = "/home/me/.local/bin/black" },
+    { name = "blackd", install-path = "/home/me/.local/bin/blackd" },
+]
+"#;
+        let entrypoints = UvProvider::parse_receipt(content).unwrap();
+        assert_eq!(entrypoints.len(), 2);
+        assert_eq!(entrypoints[0].0, "black");
+        assert_eq!(
+            entrypoints[0].1,
+            Some(PathBuf::from("/home/me/.local/bin/black"))
+        );
+    }
+
+    #[test]
+    fn test_parse_receipt_invalid() {
+        assert!(UvProvider::parse_receipt("not = [toml").is_err());
+    }
+
+    #[test]
+    fn test_parse_tool_list() {
+        let output = "black v24.1.0 (/home/me/.local/share/uv/tools/black)\n- black (/home/me/.local/bin/black)\n- blackd (/home/me/.local/bin/blackd)\nruff v0.3.0\n- ruff\n";
+        let tools = UvProvider::parse_tool_list(output);
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].package, "black");
+        assert_eq!(tools[0].version, Some("24.1.0".to_string()));
+        assert_eq!(
+            tools[0].dir,
+            Some(PathBuf::from("/home/me/.local/share/uv/tools/black"))
+        );
+        assert_eq!(tools[0].entrypoints.len(), 2);
+        assert_eq!(tools[1].package, "ruff");
+        assert_eq!(tools[1].entrypoints, vec![("ruff".to_string(), None)]);
+    }
+
+    #[test]
+    fn test_parse_tool_list_empty() {
+        assert!(UvProvider::parse_tool_list("").is_empty());
+        assert!(UvProvider::parse_tool_list("warning: No tools installed\n").is_empty());
+    }
+
+    #[test]
+    fn test_read_tools_dir() {
+        let tmp = TempDir::new().unwrap();
+        let tool_dir = tmp.path().join("ruff");
+        let bin = tool_dir.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let script = bin.join("ruff");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(
+            tool_dir.join("uv-receipt.toml"),
+            "[tool]\nentrypoints = [{ name = \"ruff\" }]\n",
+        )
+        .unwrap();
+        let dist = tool_dir.join("lib/python3.12/site-packages/ruff-0.3.0.dist-info");
+        fs::create_dir_all(&dist).unwrap();
+
+        let tools = UvProvider::read_tools_dir(tmp.path());
+        assert_eq!(tools.len(), 1);
+
+        let apps = UvProvider::tool_apps(&tools[0]);
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "ruff");
+        assert_eq!(apps[0].source, AppSource::Uv);
+        assert_eq!(apps[0].metadata.get("version").unwrap(), "0.3.0");
+    }
+
+    #[test]
+    fn test_watch_paths_include_receipts() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("tools");
+        fs::create_dir_all(dir.join("ruff")).unwrap();
+        fs::create_dir_all(dir.join("black")).unwrap();
+        assert_eq!(
+            UvProvider::watch_paths_in(&dir),
+            vec![
+                dir.clone(),
+                dir.join("black/uv-receipt.toml"),
+                dir.join("ruff/uv-receipt.toml"),
+            ]
+        );
+        assert_eq!(UvProvider::watch_paths_in(&tmp.path().join("missing")).len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+//! Sandbox permissions of flatpak applications and filtering applications by them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::Application;
+
+/// What a sandboxed application may access: the `[Context]` and bus policy groups of
+/// its flatpak metadata, with overrides applied.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SandboxPermissions {
+    /// Subsystems shared with the host, such as `network` and `ipc`.
+    pub shared: Vec<String>,
+    /// Sockets such as `x11`, `wayland` and `pulseaudio`.
+    pub sockets: Vec<String>,
+    /// Devices such as `dri` or `all`.
+    pub devices: Vec<String>,
+    /// Host paths such as `home`, `xdg-download:ro` or `~/Games`, with an optional
+    /// `:ro`, `:rw` or `:create` suffix.
+    pub filesystems: Vec<String>,
+    /// Bus names on the session bus and the access granted (`see`, `talk` or `own`).
+    pub session_bus: BTreeMap<String, String>,
+    pub system_bus: BTreeMap<String, String>,
+}
+
+impl SandboxPermissions {
+    pub fn is_empty(&self) -> bool {
+        self.shared.is_empty()
+            && self.sockets.is_empty()
+            && self.devices.is_empty()
+            && self.filesystems.is_empty()
+            && self.session_bus.is_empty()
+            && self.system_bus.is_empty()
+    }
+}
+
+/// The `filesystems` entry without its access suffix.
+pub(crate) fn filesystem_path(entry: &str) -> &str {
+    ["ro", "rw", "create"]
+        .iter()
+        .find_map(|mode| entry.strip_suffix(mode).and_then(|e| e.strip_suffix(':')))
+        .unwrap_or(entry)
+}
+
+/// Whether a bus policy entry, possibly a `org.example.*` wildcard, covers `name`.
+fn bus_name_matches(entry: &str, name: &str) -> bool {
+    match entry.strip_suffix(".*") {
+        Some(prefix) => name == prefix || name.starts_with(&format!("{}.", prefix)),
+        None => entry == name,
+    }
+}
+
+/// The kind of permission a [`PermissionFilter`] looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionKind {
+    Share,
+    Socket,
+    Device,
+    Filesystem,
+    SessionBus,
+    SystemBus,
+}
+
+impl PermissionKind {
+    const ALL: &'static [PermissionKind] = &[
+        PermissionKind::Share,
+        PermissionKind::Socket,
+        PermissionKind::Device,
+        PermissionKind::Filesystem,
+        PermissionKind::SessionBus,
+        PermissionKind::SystemBus,
+    ];
+}
+
+impl fmt::Display for PermissionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionKind::Share => write!(f, "share"),
+            PermissionKind::Socket => write!(f, "socket"),
+            PermissionKind::Device => write!(f, "device"),
+            PermissionKind::Filesystem => write!(f, "filesystem"),
+            PermissionKind::SessionBus => write!(f, "session-bus"),
+            PermissionKind::SystemBus => write!(f, "system-bus"),
+        }
+    }
+}
+
+/// A permission applications must have to be listed, written `<kind>=<value>` like
+/// `filesystem=home` or `session-bus=org.freedesktop.secrets`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionFilter {
+    pub kind: PermissionKind,
+    pub value: String,
+}
+
+impl PermissionFilter {
+    /// Whether `app` is granted this permission. Broader grants count: `host` gives
+    /// access to `home`, `all` devices include `dri`, and `org.example.*` bus policies
+    /// cover `org.example.Foo`. Applications without a sandbox never match.
+    pub fn matches(&self, app: &Application) -> bool {
+        let permissions = match &app.permissions {
+            Some(p) => p,
+            None => return false,
+        };
+        let value = self.value.as_str();
+        match self.kind {
+            PermissionKind::Share => permissions.shared.iter().any(|s| s == value),
+            PermissionKind::Socket => permissions.sockets.iter().any(|s| s == value),
+            PermissionKind::Device => permissions
+                .devices
+                .iter()
+                .any(|d| d == value || d == "all"),
+            PermissionKind::Filesystem => permissions.filesystems.iter().any(|entry| {
+                let path = filesystem_path(entry);
+                path == filesystem_path(value) || (path == "host" && value == "home")
+            }),
+            PermissionKind::SessionBus => permissions
+                .session_bus
+                .keys()
+                .any(|entry| bus_name_matches(entry, value)),
+            PermissionKind::SystemBus => permissions
+                .system_bus
+                .keys()
+                .any(|entry| bus_name_matches(entry, value)),
+        }
+    }
+}
+
+impl FromStr for PermissionFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once('=')
+            .filter(|(_, value)| !value.is_empty())
+            .ok_or_else(|| format!("invalid filter '{}': expected <kind>=<value>", s))?;
+        let kind = PermissionKind::ALL
+            .iter()
+            .copied()
+            .find(|k| k.to_string() == kind)
+            .ok_or_else(|| {
+                format!(
+                    "invalid filter kind '{}': expected share, socket, device, filesystem, session-bus or system-bus",
+                    kind
+                )
+            })?;
+        Ok(Self {
+            kind,
+            value: value.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for PermissionFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.kind, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppSource;
+
+    fn make_app(permissions: Option<SandboxPermissions>) -> Application {
+        Application {
+            name: "App".to_string(),
+            exec_command: "flatpak run org.example.App".to_string(),
+            source: AppSource::Flatpak,
+            location: "org.example.App".to_string(),
+            permissions,
+            ..Default::default()
+        }
+    }
+
+    fn filter(s: &str) -> PermissionFilter {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            filter("session-bus=org.freedesktop.secrets"),
+            PermissionFilter {
+                kind: PermissionKind::SessionBus,
+                value: "org.freedesktop.secrets".to_string(),
+            }
+        );
+        assert!("filesystem".parse::<PermissionFilter>().is_err());
+        assert!("filesystem=".parse::<PermissionFilter>().is_err());
+        assert!("talk=org.example".parse::<PermissionFilter>().is_err());
+    }
+
+    #[test]
+    fn test_filesystem_filter() {
+        let app = make_app(Some(SandboxPermissions {
+            filesystems: vec!["xdg-download:ro".to_string(), "~/Games".to_string()],
+            ..Default::default()
+        }));
+        assert!(filter("filesystem=xdg-download").matches(&app));
+        assert!(filter("filesystem=~/Games").matches(&app));
+        assert!(!filter("filesystem=home").matches(&app));
+
+        let host = make_app(Some(SandboxPermissions {
+            filesystems: vec!["host".to_string()],
+            ..Default::default()
+        }));
+        assert!(filter("filesystem=home").matches(&host));
+        assert!(!filter("filesystem=home").matches(&make_app(None)));
+    }
+
+    #[test]
+    fn test_device_and_bus_filters() {
+        let app = make_app(Some(SandboxPermissions {
+            devices: vec!["all".to_string()],
+            session_bus: BTreeMap::from([("org.kde.*".to_string(), "own".to_string())]),
+            ..Default::default()
+        }));
+        assert!(filter("device=dri").matches(&app));
+        assert!(filter("session-bus=org.kde.StatusNotifierItem").matches(&app));
+        assert!(!filter("session-bus=org.kdeconnect").matches(&app));
+        assert!(!filter("system-bus=org.kde.foo").matches(&app));
+    }
+}
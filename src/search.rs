@@ -0,0 +1,213 @@
+//! Ranking applications against a search query.
+//!
+//! Each field of an application is fuzzy-matched on its own and the score multiplied by
+//! the field's weight, so a hit in the name outranks the same hit in a description.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Serialize;
+
+use crate::app::Application;
+
+/// A part of an application that searches look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Name,
+    GenericName,
+    Keywords,
+    Exec,
+    Categories,
+    Description,
+}
+
+impl SearchField {
+    /// Every field, from the highest default weight to the lowest.
+    pub const ALL: &'static [SearchField] = &[
+        SearchField::Name,
+        SearchField::GenericName,
+        SearchField::Keywords,
+        SearchField::Exec,
+        SearchField::Categories,
+        SearchField::Description,
+    ];
+
+    pub fn default_weight(self) -> f64 {
+        match self {
+            SearchField::Name => 1.0,
+            SearchField::GenericName => 0.8,
+            SearchField::Keywords => 0.7,
+            SearchField::Exec => 0.6,
+            SearchField::Categories => 0.5,
+            SearchField::Description => 0.4,
+        }
+    }
+
+    /// The values of this field in `app`; several for lists such as keywords.
+    fn values(self, app: &Application) -> Vec<&str> {
+        match self {
+            SearchField::Name => app.names().collect(),
+            SearchField::GenericName => app.generic_name.as_deref().into_iter().collect(),
+            SearchField::Keywords => app.keywords.iter().map(String::as_str).collect(),
+            SearchField::Exec => exec_basename(app).into_iter().collect(),
+            SearchField::Categories => app.categories.iter().map(String::as_str).collect(),
+            SearchField::Description => app.description.as_deref().into_iter().collect(),
+        }
+    }
+}
+
+impl FromStr for SearchField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SearchField::ALL
+            .iter()
+            .copied()
+            .find(|field| field.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown search field '{}': expected name, generic_name, keywords, exec, categories or description",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for SearchField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchField::Name => write!(f, "name"),
+            SearchField::GenericName => write!(f, "generic_name"),
+            SearchField::Keywords => write!(f, "keywords"),
+            SearchField::Exec => write!(f, "exec"),
+            SearchField::Categories => write!(f, "categories"),
+            SearchField::Description => write!(f, "description"),
+        }
+    }
+}
+
+/// An application found by a search, with how well and where it matched.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchMatch {
+    #[serde(flatten)]
+    pub app: Application,
+    pub score: i64,
+    pub matched_field: SearchField,
+}
+
+/// Scores `query` against every field of `app` and returns the best weighted score and
+/// the field it came from, or `None` if nothing matched.
+pub fn score(
+    matcher: &SkimMatcherV2,
+    app: &Application,
+    query: &str,
+    weights: &BTreeMap<SearchField, f64>,
+) -> Option<(i64, SearchField)> {
+    let mut best: Option<(i64, SearchField)> = None;
+    for &field in SearchField::ALL {
+        let weight = weights
+            .get(&field)
+            .copied()
+            .unwrap_or_else(|| field.default_weight());
+        let raw = field
+            .values(app)
+            .into_iter()
+            .filter_map(|value| matcher.fuzzy_match(value, query))
+            .max();
+        let score = match raw {
+            Some(raw) => (raw as f64 * weight).round() as i64,
+            None => continue,
+        };
+        if score > 0 && best.map_or(true, |(best_score, _)| score > best_score) {
+            best = Some((score, field));
+        }
+    }
+    best
+}
+
+/// The file name of the program an application runs, skipping an `env VAR=value`
+/// prefix.
+fn exec_basename(app: &Application) -> Option<&str> {
+    let program = if app.exec_args.is_empty() {
+        app.exec_command.split_whitespace().find(|a| !a.contains('='))
+    } else {
+        let mut args = app.exec_args.iter().map(String::as_str);
+        match args.next() {
+            Some("env") => args.find(|a| !a.contains('=')),
+            first => first,
+        }
+    }?;
+    Path::new(program).file_name().and_then(|n| n.to_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppSource;
+
+    fn make_app(name: &str, exec_args: &[&str]) -> Application {
+        Application {
+            name: name.to_string(),
+            exec_command: exec_args.join(" "),
+            source: AppSource::Desktop,
+            exec_args: exec_args.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_field_names_round_trip() {
+        for field in SearchField::ALL {
+            assert_eq!(field.to_string().parse::<SearchField>().unwrap(), *field);
+        }
+        assert!("title".parse::<SearchField>().is_err());
+    }
+
+    #[test]
+    fn test_score_reports_best_weighted_field() {
+        let matcher = SkimMatcherV2::default();
+        let weights = BTreeMap::new();
+        let mut app = make_app("Firefox", &["/usr/lib/firefox/firefox", "%u"]);
+        app.generic_name = Some("Web Browser".to_string());
+        app.keywords = vec!["internet".to_string(), "www".to_string()];
+        app.categories = vec!["Network".to_string(), "WebBrowser".to_string()];
+        app.description = Some("Browse the World Wide Web".to_string());
+
+        assert_eq!(score(&matcher, &app, "browser", &weights).unwrap().1, SearchField::GenericName);
+        assert_eq!(score(&matcher, &app, "www", &weights).unwrap().1, SearchField::Keywords);
+        assert_eq!(score(&matcher, &app, "firefox", &weights).unwrap().1, SearchField::Name);
+        assert_eq!(score(&matcher, &app, "network", &weights).unwrap().1, SearchField::Categories);
+        assert!(score(&matcher, &app, "spreadsheet", &weights).is_none());
+    }
+
+    #[test]
+    fn test_score_weights_are_configurable() {
+        let matcher = SkimMatcherV2::default();
+        let mut app = make_app("Browser", &["browser"]);
+        app.generic_name = Some("Browser".to_string());
+        let (default_score, field) = score(&matcher, &app, "browser", &BTreeMap::new()).unwrap();
+        assert_eq!(field, SearchField::Name);
+
+        let weights = BTreeMap::from([(SearchField::Name, 0.0), (SearchField::GenericName, 2.0)]);
+        let (score, field) = score(&matcher, &app, "browser", &weights).unwrap();
+        assert_eq!(field, SearchField::GenericName);
+        assert_eq!(score, default_score * 2);
+    }
+
+    #[test]
+    fn test_exec_basename() {
+        assert_eq!(exec_basename(&make_app("a", &["/usr/bin/gimp-2.10", "%U"])), Some("gimp-2.10"));
+        assert_eq!(
+            exec_basename(&make_app("a", &["env", "GDK_BACKEND=x11", "/opt/app/bin/app"])),
+            Some("app")
+        );
+        let mut app = make_app("a", &[]);
+        app.exec_command = "/usr/bin/htop --tree".to_string();
+        assert_eq!(exec_basename(&app), Some("htop"));
+    }
+}
@@ -1,3 +1,6 @@
+// `Command::cargo_bin` is deprecated in newer assert_cmd releases
+#![allow(deprecated)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 
@@ -25,7 +28,7 @@ fn test_version_exits_0() {
 fn test_list_exits_0() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .arg("list")
+        .args(["--no-cache", "list"])
         .assert()
         .success();
 }
@@ -34,21 +37,22 @@ fn test_list_exits_0() {
 fn test_list_json_outputs_valid_json_array() {
     let output = Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--format", "json", "list"])
+        .args(["--no-cache", "--format", "json", "list"])
         .output()
         .unwrap();
 
     assert!(output.status.success());
     let stdout = String::from_utf8_lossy(&output.stdout);
     let parsed: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
-    assert!(parsed.is_array());
+    assert!(parsed["apps"].is_array());
+    assert!(parsed["warnings"].is_array());
 }
 
 #[test]
 fn test_has_nonexistent_exits_1() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["has", "nonexistent_app_xyz_12345"])
+        .args(["--no-cache", "has", "nonexistent_app_xyz_12345"])
         .assert()
         .code(1);
 }
@@ -57,7 +61,7 @@ fn test_has_nonexistent_exits_1() {
 fn test_info_nonexistent_exits_1() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["info", "nonexistent_app_xyz_12345"])
+        .args(["--no-cache", "info", "nonexistent_app_xyz_12345"])
         .assert()
         .code(1)
         .stderr(predicate::str::contains("not found"));
@@ -67,7 +71,7 @@ fn test_info_nonexistent_exits_1() {
 fn test_path_nonexistent_exits_1() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["path", "nonexistent_app_xyz_12345"])
+        .args(["--no-cache", "path", "nonexistent_app_xyz_12345"])
         .assert()
         .code(1)
         .stderr(predicate::str::contains("not found"));
@@ -77,7 +81,7 @@ fn test_path_nonexistent_exits_1() {
 fn test_search_exits_0() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["search", "nonexistent_app_xyz_12345"])
+        .args(["--no-cache", "search", "nonexistent_app_xyz_12345"])
         .assert()
         .success();
 }
@@ -86,7 +90,7 @@ fn test_search_exits_0() {
 fn test_list_with_source_filter() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--source", "desktop", "list"])
+        .args(["--no-cache", "--source", "desktop", "list"])
         .assert()
         .success();
 }
@@ -95,7 +99,7 @@ fn test_list_with_source_filter() {
 fn test_list_tsv_format() {
     let output = Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--format", "tsv", "list"])
+        .args(["--no-cache", "--format", "tsv", "list"])
         .output()
         .unwrap();
 
@@ -109,7 +113,7 @@ fn test_list_tsv_format() {
 fn test_list_names_format() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--format", "names", "list"])
+        .args(["--no-cache", "--format", "names", "list"])
         .assert()
         .success();
 }
@@ -118,7 +122,7 @@ fn test_list_names_format() {
 fn test_list_exec_format() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--format", "exec", "list"])
+        .args(["--no-cache", "--format", "exec", "list"])
         .assert()
         .success();
 }
@@ -127,7 +131,7 @@ fn test_list_exec_format() {
 fn test_has_json_nonexistent() {
     let output = Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--format", "json", "has", "nonexistent_app_xyz_12345"])
+        .args(["--no-cache", "--format", "json", "has", "nonexistent_app_xyz_12345"])
         .output()
         .unwrap();
 
@@ -142,7 +146,7 @@ fn test_has_json_nonexistent() {
 fn test_no_color_flag() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--no-color", "list"])
+        .args(["--no-cache", "--no-color", "list"])
         .assert()
         .success();
 }
@@ -151,9 +155,11 @@ fn test_no_color_flag() {
 
 #[test]
 fn test_run_nonexistent_exits_1() {
+    let state = tempfile::TempDir::new().unwrap();
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["run", "nonexistent_app_xyz_12345"])
+        .env("XDG_STATE_HOME", state.path())
+        .args(["--no-cache", "run", "nonexistent_app_xyz_12345"])
         .assert()
         .code(1)
         .stderr(predicate::str::contains("not found"));
@@ -201,7 +207,7 @@ fn test_completions_fish_exits_0() {
 fn test_list_with_source_cargo() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--source", "cargo", "list"])
+        .args(["--no-cache", "--source", "cargo", "list"])
         .assert()
         .success();
 }
@@ -210,7 +216,7 @@ fn test_list_with_source_cargo() {
 fn test_list_with_source_dpkg() {
     Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--source", "dpkg", "list"])
+        .args(["--no-cache", "--source", "dpkg", "list"])
         .assert()
         .success();
 }
@@ -219,7 +225,7 @@ fn test_list_with_source_dpkg() {
 fn test_list_with_stats_flag() {
     let output = Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--stats", "list"])
+        .args(["--no-cache", "--stats", "list"])
         .output()
         .unwrap();
 
@@ -258,6 +264,7 @@ fn test_invalid_source_exits_2() {
 }
 
 #[test]
+#[cfg(feature = "cargo")]
 fn test_doctor_shows_cargo_provider() {
     let output = Command::cargo_bin("appgrep")
         .unwrap()
@@ -274,12 +281,980 @@ fn test_doctor_shows_cargo_provider() {
 fn test_list_json_with_source_filter() {
     let output = Command::cargo_bin("appgrep")
         .unwrap()
-        .args(["--format", "json", "--source", "standalone", "list"])
+        .args(["--no-cache", "--format", "json", "--source", "standalone", "list"])
         .output()
         .unwrap();
 
     assert!(output.status.success());
     let stdout = String::from_utf8_lossy(&output.stdout);
     let parsed: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
-    assert!(parsed.is_array());
+    assert!(parsed["apps"].is_array());
+    assert!(parsed["warnings"].is_array());
+}
+
+#[test]
+fn test_list_with_all_binaries_flag() {
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .args(["--no-cache", "--all-binaries", "--source", "dpkg", "list"])
+        .assert()
+        .success();
+}
+
+#[test]
+#[cfg(feature = "dpkg")]
+fn test_has_finds_packaged_binary() {
+    // `env` ships in coreutils, which never has `env` as its primary binary name
+    if !std::path::Path::new("/var/lib/dpkg/status").exists() {
+        return;
+    }
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .args(["--no-cache", "has", "env"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_list_writes_cache_and_reuses_it() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let first = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CACHE_HOME", tmp.path())
+        .args(["--format", "json", "list"])
+        .output()
+        .unwrap();
+    assert!(first.status.success());
+
+    let second = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CACHE_HOME", tmp.path())
+        .args(["--format", "json", "list"])
+        .output()
+        .unwrap();
+    assert!(second.status.success());
+    assert_eq!(first.stdout, second.stdout);
+
+    let refreshed = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CACHE_HOME", tmp.path())
+        .args(["--refresh", "--format", "json", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(first.stdout, refreshed.stdout);
+}
+
+#[test]
+fn test_no_cache_writes_nothing() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CACHE_HOME", tmp.path())
+        .args(["--no-cache", "list"])
+        .assert()
+        .success();
+    assert!(!tmp.path().join("appgrep").exists());
+}
+
+#[test]
+fn test_refresh_conflicts_with_no_cache() {
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .args(["--refresh", "--no-cache", "list"])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+#[test]
+fn test_doctor_shows_cache_section() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CACHE_HOME", tmp.path())
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cache:"));
+}
+
+/// Write a config file into a fresh temp dir and return both.
+fn temp_config(content: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let path = tmp.path().join("config.toml");
+    std::fs::write(&path, content).unwrap();
+    (tmp, path)
+}
+
+#[test]
+fn test_config_show_reports_origins() {
+    let (tmp, path) = temp_config(
+        "[standalone]\nscan_dirs = [\"/srv/tools/bin\"]\n[sources]\ndisabled = [\"npm\"]\n",
+    );
+    let output = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .args(["config", "show"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout
+        .lines()
+        .find(|l| l.contains("/srv/tools/bin"))
+        .expect("configured scan dir listed");
+    assert!(line.contains(&*path.to_string_lossy()));
+    assert!(stdout.contains("loaded"));
+    assert!(stdout.lines().any(|l| l.contains("npm") && l.contains("config.toml")));
+}
+
+#[test]
+#[cfg(feature = "standalone")]
+fn test_config_extra_scan_dir_is_listed() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tools = tempfile::TempDir::new().unwrap();
+    let tool = tools.path().join("internal-deploy-tool");
+    std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let (tmp, path) = temp_config(&format!(
+        "[standalone]\nscan_dirs = [\"{}\"]\n",
+        tools.path().display()
+    ));
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .args(["--no-cache", "--format", "names", "--source", "standalone", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("internal-deploy-tool"));
+
+    // Ignore rules hide it again
+    std::fs::write(
+        &path,
+        format!(
+            "[standalone]\nscan_dirs = [\"{}\"]\n[ignore]\nnames = [\"internal-*\"]\n",
+            tools.path().display()
+        ),
+    )
+    .unwrap();
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .args(["--no-cache", "--format", "names", "--source", "standalone", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("internal-deploy-tool").not());
+}
+
+#[test]
+#[cfg(feature = "cargo")]
+fn test_config_disabled_provider_in_doctor() {
+    let (tmp, path) = temp_config("[sources]\ndisabled = [\"cargo\"]\n");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("disabled in config"));
+}
+
+#[test]
+fn test_invalid_config_fails() {
+    let (tmp, path) = temp_config("[sources]\npriority = [\"not a source\"]\n");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid source 'not a source'"));
+}
+
+#[test]
+fn test_invalid_config_does_not_block_completions() {
+    let (tmp, path) = temp_config("[sources\n");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .args(["completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("complete"));
+
+    // `config show` is how the broken file gets diagnosed
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("APPGREP_CONFIG", &path)
+        .env("XDG_CONFIG_DIRS", tmp.path())
+        .args(["config", "show"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("config.toml"));
+}
+
+/// Install a plugin named `acme` into a temp config home.
+#[cfg(feature = "plugins")]
+fn temp_plugin_home() -> tempfile::TempDir {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = tempfile::TempDir::new().unwrap();
+    let dir = home.path().join("appgrep/providers.d");
+    std::fs::create_dir_all(&dir).unwrap();
+    let plugin = dir.join("acme");
+    std::fs::write(
+        &plugin,
+        r#"#!/bin/sh
+case "$1" in
+    probe) exit 0 ;;
+    discover) echo '[{"name": "acme-deployer", "exec_command": "/srv/acme/bin/deployer", "description": "In-house deployer"}]' ;;
+esac
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+    home
+}
+
+#[test]
+#[cfg(feature = "plugins")]
+fn test_plugin_apps_are_listed() {
+    let home = temp_plugin_home();
+    let output = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CONFIG_DIRS", home.path().join("none"))
+        .args(["--no-cache", "--format", "json", "--source", "acme", "list"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let apps = parsed["apps"].as_array().unwrap();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0]["name"], "acme-deployer");
+    assert_eq!(apps[0]["source"], "acme");
+}
+
+#[test]
+#[cfg(feature = "plugins")]
+fn test_plugin_shows_in_doctor_and_table() {
+    let home = temp_plugin_home();
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CONFIG_DIRS", home.path().join("none"))
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("acme"));
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CONFIG_DIRS", home.path().join("none"))
+        .args(["--no-cache", "--no-color", "search", "deployer"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("acme-deployer"));
+}
+
+/// Add a plugin to `home` whose discovery never finishes.
+#[cfg(feature = "plugins")]
+fn add_stalled_plugin(home: &std::path::Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let plugin = home.join("appgrep/providers.d/stalled");
+    std::fs::write(
+        &plugin,
+        "#!/bin/sh\ncase \"$1\" in\n    probe) exit 0 ;;\n    discover) exec sleep 30 ;;\nesac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+#[cfg(feature = "plugins")]
+fn test_timed_out_provider_reported_in_json_warnings() {
+    let home = temp_plugin_home();
+    add_stalled_plugin(home.path());
+    std::fs::write(
+        home.path().join("appgrep/config.toml"),
+        "[timeouts]\nstalled = 0.5\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CONFIG_DIRS", home.path().join("none"))
+        .env_remove("APPGREP_CONFIG")
+        .args(["--no-cache", "--format", "json", "--source", "acme", "--source", "stalled"])
+        .arg("list")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let stdout: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let apps = stdout["apps"].as_array().unwrap();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0]["name"], "acme-deployer");
+
+    let warnings = stdout["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0]["provider"], "stalled");
+    assert_eq!(warnings[0]["kind"], "timeout");
+    assert_eq!(warnings[0]["message"], "timed out after 500ms");
+}
+
+#[test]
+#[cfg(feature = "plugins")]
+fn test_timeout_flag_warns_and_keeps_other_results() {
+    let home = temp_plugin_home();
+    add_stalled_plugin(home.path());
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("XDG_CONFIG_DIRS", home.path().join("none"))
+        .args(["--no-cache", "--timeout", "0.5", "--format", "names", "search", "deployer"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("acme-deployer"))
+        .stderr(predicate::str::contains(
+            "appgrep: warning: provider 'stalled' timed out after 500ms",
+        ));
+}
+
+#[test]
+fn test_invalid_timeout_flag_exits_2() {
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .args(["--timeout", "0", "list"])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+#[test]
+fn test_unknown_plugin_source_exits_2() {
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .args(["--source", "no-such-plugin", "list"])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+/// Create a data home with a script at a path containing a space, which records the
+/// arguments of each invocation, and a desktop entry named `name` launching it with
+/// `exec_args`.
+#[cfg(feature = "desktop")]
+fn temp_recording_app(name: &str, exec_args: &str) -> tempfile::TempDir {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = tempfile::TempDir::new().unwrap();
+    let bin = home.path().join("My Apps");
+    std::fs::create_dir_all(&bin).unwrap();
+    let script = bin.join("record");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\nout=\"$(dirname \"$0\")/args-$$\"\nprintf '%s\\n' \"$@\" > \"$out.tmp\"\nmv \"$out.tmp\" \"$out.txt\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let apps = home.path().join("applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(
+        apps.join("recorder.desktop"),
+        format!(
+            "[Desktop Entry]\nType=Application\nName={}\nIcon=recorder\nExec=\"{}\" {}\n",
+            name,
+            script.display(),
+            exec_args
+        ),
+    )
+    .unwrap();
+    home
+}
+
+/// Wait for `count` invocations of the recording script and return their arguments.
+#[cfg(feature = "desktop")]
+fn recorded_args(home: &std::path::Path, count: usize) -> Vec<Vec<String>> {
+    let dir = home.join("My Apps");
+    for _ in 0..100 {
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+            .collect();
+        if files.len() >= count {
+            files.sort();
+            let mut invocations: Vec<Vec<String>> = files
+                .iter()
+                .map(|f| {
+                    let content = std::fs::read_to_string(f).unwrap();
+                    content.lines().map(str::to_string).collect()
+                })
+                .collect();
+            invocations.sort();
+            return invocations;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    panic!("the recording script ran fewer than {} times", count);
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_uses_exec_quoting_and_field_codes() {
+    let home = temp_recording_app("Quoted Recorder Xyz", r#"--title="a \\"b\\"" %i --name=%c %U"#);
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .args(["--no-cache", "run", "Quoted Recorder Xyz"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("My Apps/record\""));
+
+    assert_eq!(
+        recorded_args(home.path(), 1),
+        vec![vec![
+            r#"--title=a "b""#,
+            "--icon",
+            "recorder",
+            "--name=Quoted Recorder Xyz",
+        ]]
+    );
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_passes_files_to_list_code() {
+    let home = temp_recording_app("List Recorder Xyz", "--open %F");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .args(["--no-cache", "run", "List Recorder Xyz", "--", "a.pdf", "file:///tmp/b%20c.pdf"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        recorded_args(home.path(), 1),
+        vec![vec!["--open", "a.pdf", "/tmp/b c.pdf"]]
+    );
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_repeats_single_file_code_per_file() {
+    let home = temp_recording_app("Single Recorder Xyz", "%u");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .args(["--no-cache", "run", "Single Recorder Xyz", "--", "a.txt", "https://example.com/"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        recorded_args(home.path(), 2),
+        vec![vec!["a.txt"], vec!["https://example.com/"]]
+    );
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_warns_when_app_takes_no_files() {
+    let home = temp_recording_app("Plain Recorder Xyz", "--plain");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .args(["--no-cache", "run", "Plain Recorder Xyz", "--", "a.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("does not take files or URLs"));
+
+    assert_eq!(recorded_args(home.path(), 1), vec![vec!["--plain"]]);
+}
+
+/// Mark the recording app in `home` as a terminal application.
+#[cfg(feature = "desktop")]
+fn make_terminal_app(home: &std::path::Path) {
+    use std::io::Write;
+
+    let mut entry = std::fs::OpenOptions::new()
+        .append(true)
+        .open(home.join("applications/recorder.desktop"))
+        .unwrap();
+    entry.write_all(b"Terminal=true\n").unwrap();
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_terminal_app_in_terminal_emulator() {
+    let home = temp_recording_app("Terminal Recorder Xyz", "--inner");
+    make_terminal_app(home.path());
+    let terminal = home.path().join("My Apps/record");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .env("TERMINAL", &terminal)
+        .args(["--no-cache", "run", "Terminal Recorder Xyz"])
+        .assert()
+        .success();
+
+    let terminal = terminal.to_string_lossy().to_string();
+    assert_eq!(
+        recorded_args(home.path(), 1),
+        vec![vec!["-e".to_string(), terminal, "--inner".to_string()]]
+    );
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_no_terminal_starts_directly() {
+    let home = temp_recording_app("Direct Recorder Xyz", "--inner");
+    make_terminal_app(home.path());
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .env("TERMINAL", "/nonexistent/terminal")
+        .args(["--no-cache", "run", "--no-terminal", "Direct Recorder Xyz"])
+        .assert()
+        .success();
+
+    assert_eq!(recorded_args(home.path(), 1), vec![vec!["--inner"]]);
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_terminal_app_without_terminal_fails() {
+    let home = temp_recording_app("Stranded Recorder Xyz", "--inner");
+    make_terminal_app(home.path());
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .env_remove("TERMINAL")
+        .env("PATH", home.path().join("empty"))
+        .args(["--no-cache", "run", "Stranded Recorder Xyz"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("no terminal emulator was found"));
+}
+
+/// Create a data home with a desktop entry named `name` running the shell `script`.
+#[cfg(feature = "desktop")]
+fn temp_script_app(name: &str, script: &str) -> tempfile::TempDir {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = tempfile::TempDir::new().unwrap();
+    let path = home.path().join("app.sh");
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let apps = home.path().join("applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(
+        apps.join("script.desktop"),
+        format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\n", name, path.display()),
+    )
+    .unwrap();
+    home
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_wait_propagates_exit_code() {
+    let home = temp_script_app("Failing Script Xyz", "echo output; exit 3");
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .env_remove("XDG_RUNTIME_DIR")
+        .args(["--no-cache", "run", "--wait", "Failing Script Xyz"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("output"));
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_detaches_into_new_session() {
+    let home = temp_script_app(
+        "Session Script Xyz",
+        "cut -d' ' -f6 /proc/$$/stat > \"$(dirname \"$0\")/sid.tmp\"\n\
+         mv \"$(dirname \"$0\")/sid.tmp\" \"$(dirname \"$0\")/sid\"",
+    );
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .env_remove("XDG_RUNTIME_DIR")
+        .args(["--no-cache", "run", "Session Script Xyz"])
+        .assert()
+        .success();
+
+    let sid_file = home.path().join("sid");
+    for _ in 0..100 {
+        if sid_file.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let sid = std::fs::read_to_string(&sid_file).unwrap();
+    let stat = std::fs::read_to_string("/proc/self/stat").unwrap();
+    let own_sid = stat.rsplit(')').next().unwrap().split_whitespace().nth(3).unwrap();
+    assert_ne!(sid.trim(), own_sid);
+}
+
+/// Give the recording app in `home` a `second` action passing `--second`.
+#[cfg(feature = "desktop")]
+fn add_recording_action(home: &std::path::Path) {
+    use std::io::Write;
+
+    let script = home.join("My Apps/record");
+    let mut entry = std::fs::OpenOptions::new()
+        .append(true)
+        .open(home.join("applications/recorder.desktop"))
+        .unwrap();
+    write!(
+        entry,
+        "Actions=second;\n\n[Desktop Action second]\nName=Second Window\nExec=\"{}\" --second %U\n",
+        script.display()
+    )
+    .unwrap();
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_action() {
+    let home = temp_recording_app("Action Recorder Xyz", "--main");
+    add_recording_action(home.path());
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .args(["--no-cache", "run", "Action Recorder Xyz", "--action", "second", "--", "a.txt"])
+        .assert()
+        .success();
+
+    assert_eq!(recorded_args(home.path(), 1), vec![vec!["--second", "a.txt"]]);
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_unknown_action_fails() {
+    let home = temp_recording_app("Unknown Action Recorder Xyz", "--main");
+    add_recording_action(home.path());
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", home.path())
+        .args(["--no-cache", "run", "Unknown Action Recorder Xyz", "--action", "third"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("has no action 'third' (available: second)"));
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_info_json_lists_actions() {
+    let home = temp_recording_app("Info Action Recorder Xyz", "--main");
+    add_recording_action(home.path());
+    let output = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .args(["--no-cache", "--format", "json", "info", "Info Action Recorder Xyz"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(parsed["actions"][0]["id"], "second");
+    assert_eq!(parsed["actions"][0]["name"], "Second Window");
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_show_hidden_lists_filtered_entries_with_reason() {
+    let home = tempfile::TempDir::new().unwrap();
+    let apps = home.path().join("applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(
+        apps.join("ghost.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Ghost Tool Xyz\nExec=ghost-tool-xyz\n\
+         TryExec=ghost-tool-xyz\n",
+    )
+    .unwrap();
+    std::fs::write(
+        apps.join("kde-only.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Kde Only Xyz\nExec=sh\nOnlyShowIn=KDE;\n",
+    )
+    .unwrap();
+
+    let list = |extra: &[&str]| {
+        let output = Command::cargo_bin("appgrep")
+            .unwrap()
+            .env("XDG_DATA_HOME", home.path())
+            .env("XDG_CURRENT_DESKTOP", "GNOME")
+            .args(["--no-cache", "--source", "desktop", "--format", "json"])
+            .args(extra)
+            .arg("list")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()["apps"].clone()
+    };
+    let reason = |apps: &serde_json::Value, name: &str| {
+        apps.as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["name"] == name)
+            .map(|a| a["hidden_reason"].clone())
+    };
+
+    let visible = list(&[]);
+    assert_eq!(reason(&visible, "Ghost Tool Xyz"), None);
+    assert_eq!(reason(&visible, "Kde Only Xyz"), None);
+
+    let all = list(&["--show-hidden"]);
+    assert_eq!(
+        reason(&all, "Ghost Tool Xyz").unwrap(),
+        "TryExec=ghost-tool-xyz not found"
+    );
+    assert_eq!(
+        reason(&all, "Kde Only Xyz").unwrap(),
+        "OnlyShowIn=KDE; (current desktop: GNOME)"
+    );
+    assert_eq!(list(&["--all"]).as_array().unwrap().len(), all.as_array().unwrap().len());
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_user_desktop_entry_masks_system_entry() {
+    let user = tempfile::TempDir::new().unwrap();
+    let system = tempfile::TempDir::new().unwrap();
+    let user_apps = user.path().join("applications");
+    let system_apps = system.path().join("applications");
+    std::fs::create_dir_all(&user_apps).unwrap();
+    std::fs::create_dir_all(system_apps.join("kde4")).unwrap();
+    let entry = |name: &str| format!("[Desktop Entry]\nType=Application\nName={}\nExec=sh\n", name);
+    std::fs::write(system_apps.join("masked-xyz.desktop"), entry("Masked Entry Xyz")).unwrap();
+    std::fs::write(
+        user_apps.join("masked-xyz.desktop"),
+        format!("{}Hidden=true\n", entry("Masked Entry Xyz")),
+    )
+    .unwrap();
+    std::fs::write(system_apps.join("kde4/nested-xyz.desktop"), entry("Nested Entry Xyz")).unwrap();
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", user.path())
+        .env("XDG_DATA_DIRS", system.path())
+        .args(["--no-cache", "--source", "desktop", "--format", "names", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nested Entry Xyz"))
+        .stdout(predicate::str::contains("Masked Entry Xyz").not());
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_locale_translates_names_and_keeps_untranslated_for_search() {
+    let home = tempfile::TempDir::new().unwrap();
+    let apps = home.path().join("applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(
+        apps.join("files-xyz.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Filesxyz\nName[de]=Dateienxyz\nExec=sh\n",
+    )
+    .unwrap();
+
+    for query in ["Filesxyz", "Dateienxyz"] {
+        Command::cargo_bin("appgrep")
+            .unwrap()
+            .env("XDG_DATA_HOME", home.path())
+            .args(["--no-cache", "--source", "desktop", "--format", "names"])
+            .args(["--locale", "de_DE.UTF-8", "search", query])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Dateienxyz"));
+    }
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("LANG", "de_AT.UTF-8")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES")
+        .args(["--no-cache", "--source", "desktop", "--format", "names", "search", "Filesxyz"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Dateienxyz"));
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .args(["--no-cache", "--source", "desktop", "--format", "names"])
+        .args(["--locale", "C", "search", "Filesxyz"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Filesxyz"));
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_search_matches_generic_name_and_keywords_in_json() {
+    let home = tempfile::TempDir::new().unwrap();
+    let apps = home.path().join("applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(
+        apps.join("surfer-xyz.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Surfer\nGenericName=Webxyz Browser\nExec=sh\n",
+    )
+    .unwrap();
+    std::fs::write(
+        apps.join("mailer-xyz.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Mailer\nKeywords=email;webxyz;\nExec=true\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("LC_ALL", "C")
+        .args(["--no-cache", "--source", "desktop", "--format", "json", "search", "webxyz"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let results = parsed["apps"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["name"], "Surfer");
+    assert_eq!(results[0]["matched_field"], "generic_name");
+    assert_eq!(results[1]["name"], "Mailer");
+    assert_eq!(results[1]["matched_field"], "keywords");
+    assert!(results[0]["score"].as_i64().unwrap() > results[1]["score"].as_i64().unwrap());
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_run_records_history_unless_no_history() {
+    let home = temp_recording_app("History Recorder Xyz", "");
+    let state = tempfile::TempDir::new().unwrap();
+    let history_json = || {
+        let output = Command::cargo_bin("appgrep")
+            .unwrap()
+            .env("XDG_STATE_HOME", state.path())
+            .args(["--format", "json", "history"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", state.path())
+        .args(["--no-cache", "run", "History Recorder Xyz"])
+        .assert()
+        .success();
+    recorded_args(home.path(), 1);
+    let history = history_json();
+    assert_eq!(history[0]["name"], "History Recorder Xyz");
+    assert_eq!(history[0]["count"], 1);
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", state.path())
+        .args(["--no-cache", "--no-history", "run", "History Recorder Xyz"])
+        .assert()
+        .success();
+    recorded_args(home.path(), 2);
+    assert_eq!(history_json()[0]["count"], 1);
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_STATE_HOME", state.path())
+        .args(["history", "clear"])
+        .assert()
+        .success();
+    assert_eq!(history_json(), serde_json::json!([]));
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_failed_launch_is_not_recorded() {
+    let home = tempfile::TempDir::new().unwrap();
+    let apps = home.path().join("applications");
+    std::fs::create_dir_all(&apps).unwrap();
+    std::fs::write(
+        apps.join("missing.desktop"),
+        "[Desktop Entry]\nType=Application\nName=Missing Binary Xyz\nExec=/nonexistent/missing-xyz\n",
+    )
+    .unwrap();
+    let state = tempfile::TempDir::new().unwrap();
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("XDG_DATA_HOME", home.path())
+        .env("XDG_STATE_HOME", state.path())
+        .env_remove("XDG_RUNTIME_DIR")
+        .args(["--no-cache", "run", "Missing Binary Xyz"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("Failed to launch"));
+    assert!(!state.path().join("appgrep/history.json").exists());
+}
+
+#[test]
+#[cfg(feature = "flatpak")]
+fn test_filter_lists_flatpak_apps_by_permission() {
+    let root = tempfile::TempDir::new().unwrap();
+    for (app_id, filesystems) in [("org.example.Reader", "home;"), ("org.example.Clock", "")] {
+        let app_dir = root.path().join("app").join(app_id);
+        let commit = app_dir.join("x86_64/stable/abc123");
+        std::fs::create_dir_all(&commit).unwrap();
+        std::os::unix::fs::symlink("abc123", app_dir.join("x86_64/stable/active")).unwrap();
+        std::os::unix::fs::symlink("x86_64/stable", app_dir.join("current")).unwrap();
+        std::fs::write(
+            commit.join("metadata"),
+            format!("[Application]\nname={}\n\n[Context]\nfilesystems={}\n", app_id, filesystems),
+        )
+        .unwrap();
+    }
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .env("FLATPAK_SYSTEM_DIR", root.path())
+        .env("FLATPAK_USER_DIR", root.path().join("user"))
+        .env("FLATPAK_CONFIG_DIR", root.path().join("etc"))
+        .args(["--no-cache", "--source", "flatpak", "--format", "names"])
+        .args(["--filter", "filesystem=home", "list"])
+        .assert()
+        .success()
+        .stdout("org.example.Reader\n");
+
+    Command::cargo_bin("appgrep")
+        .unwrap()
+        .args(["--filter", "home", "list"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("expected <kind>=<value>"));
 }
@@ -0,0 +1,119 @@
+use appgrep::{
+    AppProvider, AppSource, Application, DiscoveryEngine, Formatter, OutputFormat, ProviderError,
+};
+
+/// A provider returning a fixed list of applications.
+struct StaticProvider {
+    name: &'static str,
+    apps: Vec<(&'static str, &'static str)>,
+}
+
+impl AppProvider for StaticProvider {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn discover(&self) -> Result<Vec<Application>, ProviderError> {
+        Ok(self
+            .apps
+            .iter()
+            .map(|(name, exec)| {
+                let mut app =
+                    Application::new(*name, *exec, AppSource::Custom(self.name.to_string()));
+                app.location = exec.to_string();
+                app
+            })
+            .collect())
+    }
+}
+
+fn engine() -> DiscoveryEngine {
+    DiscoveryEngine::builder()
+        .builtin_providers(false)
+        .plugins(false)
+        .provider(StaticProvider {
+            name: "acme",
+            apps: vec![("deployer", "/srv/acme/deployer"), ("monitor", "/srv/acme/monitor")],
+        })
+        .provider(StaticProvider {
+            name: "tools",
+            apps: vec![("linter", "/srv/tools/linter")],
+        })
+        .build()
+}
+
+#[test]
+fn test_custom_providers_only() {
+    let engine = engine();
+    let names: Vec<&str> = engine.providers().iter().map(|p| p.name()).collect();
+    assert_eq!(names, vec!["acme", "tools"]);
+
+    let apps = engine.discover_all();
+    let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, vec!["deployer", "linter", "monitor"]);
+}
+
+#[test]
+fn test_discover_filtered_and_search() {
+    let engine = engine();
+    let apps = engine.discover_filtered(&[AppSource::Custom("tools".to_string())]);
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0].name, "linter");
+
+    let all = engine.discover_all();
+    let results = engine.search("deploy", &all);
+    assert_eq!(results[0].name, "deployer");
+    assert!(engine.find_by_name("monitor", &all).is_some());
+}
+
+#[test]
+fn test_exclude_provider() {
+    let engine = DiscoveryEngine::builder()
+        .builtin_providers(false)
+        .plugins(false)
+        .provider(StaticProvider {
+            name: "acme",
+            apps: vec![("deployer", "/srv/acme/deployer")],
+        })
+        .exclude("acme")
+        .build();
+    assert!(engine.providers().is_empty());
+    assert!(engine.discover_all().is_empty());
+}
+
+#[test]
+#[cfg(feature = "desktop")]
+fn test_builtin_providers_can_be_excluded() {
+    let engine = DiscoveryEngine::builder()
+        .plugins(false)
+        .exclude("dpkg")
+        .build();
+    let names: Vec<&str> = engine.providers().iter().map(|p| p.name()).collect();
+    assert!(names.contains(&"desktop"));
+    assert!(!names.contains(&"dpkg"));
+}
+
+#[test]
+fn test_formatter_output() {
+    let engine = engine();
+    let apps = engine.discover_all();
+
+    let mut out = Vec::new();
+    Formatter::new(OutputFormat::Names, true)
+        .format_list(&apps, &mut out)
+        .unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "deployer\nlinter\nmonitor\n");
+
+    let mut out = Vec::new();
+    Formatter::new(OutputFormat::Json, true)
+        .format_list(&apps, &mut out)
+        .unwrap();
+    let document: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let parsed: Vec<Application> = serde_json::from_value(document["apps"].clone()).unwrap();
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(parsed[0].source, AppSource::Custom("acme".to_string()));
+}
@@ -1,10 +1,14 @@
+#[cfg(feature = "desktop")]
 use std::path::PathBuf;
 
 /// Helper to get the fixtures directory path.
+#[cfg(feature = "desktop")]
 fn fixtures_dir() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
 }
 
+// Desktop entries are parsed with `configparser`, which only the desktop provider links
+#[cfg(feature = "desktop")]
 mod desktop_provider {
     use super::*;
 
@@ -63,10 +67,10 @@ mod desktop_provider {
 
         let name = config.get("Desktop Entry", "Name").unwrap();
         // The name should contain non-ASCII characters
-        assert!(name.len() > 0);
+        assert!(!name.is_empty());
 
         let comment = config.get("Desktop Entry", "Comment").unwrap();
-        assert!(comment.len() > 0);
+        assert!(!comment.is_empty());
     }
 }
 
@@ -103,7 +107,7 @@ mod standalone_name_extraction {
         let name = strip_arch(name);
         let name = strip_version(&name);
 
-        name.trim_end_matches(|c: char| c == '-' || c == '_' || c == '.')
+        name.trim_end_matches(['-', '_', '.'])
             .to_string()
     }
 
@@ -151,11 +155,24 @@ mod standalone_name_extraction {
 }
 
 mod deduplication {
+    use appgrep::AppSource;
+
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_source_priority_order() {
         // Desktop=0, Flatpak=1, Snap=2, Standalone=3
         assert!(0 < 1); // Desktop < Flatpak
         assert!(1 < 2); // Flatpak < Snap
         assert!(2 < 3); // Snap < Standalone
     }
+
+    #[test]
+    fn test_nix_and_custom_rank_last() {
+        let nix = AppSource::Nix.priority();
+        assert!(AppSource::ALL
+            .iter()
+            .filter(|s| !matches!(s, AppSource::Nix))
+            .all(|s| s.priority() < nix));
+        assert!(AppSource::Custom("acme".to_string()).priority() > nix);
+    }
 }